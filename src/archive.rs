@@ -0,0 +1,315 @@
+use crate::errors::ZugzugError;
+use crate::fsutil;
+use crate::store::{ArchivedDir, Dir};
+use chrono::prelude::*;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+
+/// Supported archive formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArchiveFormat {
+    Zip,
+    TarGz,
+    TarXz,
+    TarZst,
+}
+
+impl ArchiveFormat {
+    /// File extension including the leading dot
+    pub fn extension(self) -> &'static str {
+        match self {
+            ArchiveFormat::Zip => ".zip",
+            ArchiveFormat::TarGz => ".tar.gz",
+            ArchiveFormat::TarXz => ".tar.xz",
+            ArchiveFormat::TarZst => ".tar.zst",
+        }
+    }
+
+    /// Arguments selecting the compression for `tar`
+    fn tar_compression(self) -> &'static [&'static str] {
+        match self {
+            ArchiveFormat::Zip => &[],
+            ArchiveFormat::TarGz => &["-z"],
+            ArchiveFormat::TarXz => &["-J"],
+            // zstd only spreads the work over threads once the input is
+            // large enough, so small directories don't pay for it
+            ArchiveFormat::TarZst => &["-I", "zstd -T0"],
+        }
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<ArchiveFormat, ZugzugError> {
+        match s {
+            "zip" => Ok(ArchiveFormat::Zip),
+            "tar.gz" | "tgz" | "gzip" => Ok(ArchiveFormat::TarGz),
+            "tar.xz" | "txz" | "xz" => Ok(ArchiveFormat::TarXz),
+            "tar.zst" | "tzst" | "zstd" => Ok(ArchiveFormat::TarZst),
+            _ => Err(ZugzugError::new(&format!("Unknown archive format '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveFormat::Zip => write!(f, "zip"),
+            ArchiveFormat::TarGz => write!(f, "tar.gz"),
+            ArchiveFormat::TarXz => write!(f, "tar.xz"),
+            ArchiveFormat::TarZst => write!(f, "tar.zst"),
+        }
+    }
+}
+
+/// Where to write an archive
+pub enum Destination {
+    File(PathBuf),
+    Stdout,
+}
+
+/// Pack a directory into an archive using the system `zip`/`tar`
+///
+/// The archive contains the directory itself as its single top-level entry.
+pub fn create(
+    dir: &Path,
+    format: ArchiveFormat,
+    destination: &Destination,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let parent = dir
+        .parent()
+        .ok_or_else(|| ZugzugError::new("Can't archive a root directory"))?;
+    let name = dir
+        .file_name()
+        .ok_or_else(|| ZugzugError::new("Can't archive a root directory"))?;
+    let output = match destination {
+        Destination::File(path) => path.clone(),
+        Destination::Stdout => PathBuf::from("-"),
+    };
+
+    let mut command = match format {
+        ArchiveFormat::Zip => {
+            let mut command = Command::new("zip");
+            command.args(["-r", "-q"]).arg(&output).arg(name);
+            command
+        }
+        _ => {
+            let mut command = Command::new("tar");
+            command
+                .args(format.tar_compression())
+                .arg("-cf")
+                .arg(&output)
+                .arg(name);
+            command
+        }
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .current_dir(parent)
+        .status()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run {}: {}", program, e)))?;
+    if !status.success() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} exited with {}",
+            program, status
+        ))));
+    }
+    Ok(())
+}
+
+/// Pack a tracked directory into the archive location before it is removed
+///
+/// Archives are kept as `<root>/<bucket>/<dir name><extension>`. An
+/// existing archive is never overwritten.
+pub fn archive_dir(
+    dir: &Dir,
+    root: &Path,
+    format: ArchiveFormat,
+) -> Result<ArchivedDir, Box<dyn error::Error + 'static>> {
+    if format == ArchiveFormat::Zip {
+        return Err(Box::new(ZugzugError::new(
+            "Directories can only be archived as tar.gz, tar.xz or tar.zst",
+        )));
+    }
+    let bucket_root = root.join(&dir.bucket);
+    fs::create_dir_all(&bucket_root)?;
+    let path = bucket_root.join(format!("{}{}", dir.full_name(), format.extension()));
+    if path.exists() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "Archive {} already exists",
+            path.display()
+        ))));
+    }
+    let size = fsutil::dir_size(&dir.path)?;
+    if let Err(err) = create(&dir.path, format, &Destination::File(path.clone())) {
+        let _ = fs::remove_file(&path);
+        return Err(err);
+    }
+    Ok(ArchivedDir {
+        bucket: dir.bucket.clone(),
+        name: dir.full_name(),
+        archive: path.to_string_lossy().into_owned(),
+        size,
+        archived: Local::now().to_rfc3339(),
+        format: format.to_string(),
+    })
+}
+
+/// Unpack an archive made by `archive_dir` into a directory
+pub fn extract(
+    archive: &Path,
+    format: ArchiveFormat,
+    into: &Path,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let status = Command::new("tar")
+        .args(format.tar_compression())
+        .arg("-xf")
+        .arg(archive)
+        .arg("-C")
+        .arg(into)
+        .status()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run tar: {}", e)))?;
+    if !status.success() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "tar exited with {}",
+            status
+        ))));
+    }
+    Ok(())
+}
+
+/// How an archive was made browsable by `browse`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Browsing {
+    /// Mounted read-only with FUSE, nothing was extracted
+    Mounted,
+    /// Extracted into a cache, changes there are not saved to the archive
+    Extracted,
+}
+
+/// Make an archive made by `archive_dir` browsable in `root`
+///
+/// The archive is mounted read-only with `archivemount` when it is
+/// installed, unless `always_extract` is set. Otherwise it is extracted into
+/// `root` once and the copy reused until the archive changes. Returns the
+/// archived directory inside `root`.
+pub fn browse(
+    archived: &ArchivedDir,
+    root: &Path,
+    always_extract: bool,
+) -> Result<(PathBuf, Browsing), Box<dyn error::Error + 'static>> {
+    let archive = Path::new(&archived.archive);
+    let dir = root.join(&archived.name);
+    if is_mounted(root) {
+        return Ok((dir, Browsing::Mounted));
+    }
+    if root.exists() {
+        let stale = fs::metadata(archive)?.modified()? > fs::metadata(root)?.modified()?;
+        if !stale && dir.exists() {
+            return Ok((dir, Browsing::Extracted));
+        }
+        fs::remove_dir_all(root)?;
+    }
+    fs::create_dir_all(root)?;
+    if !always_extract && mount(archive, root)? {
+        return Ok((dir, Browsing::Mounted));
+    }
+    // extract next to the cache, so an interrupted extraction is never
+    // taken for a complete one
+    let partial = root.with_extension("partial");
+    if partial.exists() {
+        fs::remove_dir_all(&partial)?;
+    }
+    fs::create_dir_all(&partial)?;
+    if let Err(e) = extract(archive, archived.format.parse()?, &partial) {
+        let _ = fs::remove_dir_all(&partial);
+        return Err(e);
+    }
+    fs::remove_dir(root)?;
+    fs::rename(&partial, root)?;
+    Ok((dir, Browsing::Extracted))
+}
+
+/// Undo `browse`, unmounting the archive or removing its extracted copy
+///
+/// Returns whether there was anything to undo.
+pub fn unbrowse(root: &Path) -> Result<bool, Box<dyn error::Error + 'static>> {
+    if !root.exists() {
+        return Ok(false);
+    }
+    if is_mounted(root) {
+        unmount(root)?;
+    }
+    fs::remove_dir_all(root)?;
+    Ok(true)
+}
+
+// mount an archive read-only with archivemount, returning false when it
+// isn't installed
+fn mount(archive: &Path, root: &Path) -> Result<bool, Box<dyn error::Error + 'static>> {
+    let status = match Command::new("archivemount")
+        .args(["-o", "readonly"])
+        .arg(archive)
+        .arg(root)
+        .status()
+    {
+        Ok(status) => status,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "Unable to run archivemount: {}",
+                e
+            ))))
+        }
+    };
+    if !status.success() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "archivemount exited with {}, use --extract to extract a copy instead",
+            status
+        ))));
+    }
+    Ok(true)
+}
+
+// FUSE mounts are undone with fusermount on Linux and umount elsewhere
+fn unmount(root: &Path) -> Result<(), Box<dyn error::Error + 'static>> {
+    let mut command = if cfg!(target_os = "linux") {
+        let mut command = Command::new("fusermount");
+        command.arg("-u");
+        command
+    } else {
+        Command::new("umount")
+    };
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = command
+        .arg(root)
+        .status()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run {}: {}", program, e)))?;
+    if !status.success() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} exited with {}",
+            program, status
+        ))));
+    }
+    Ok(())
+}
+
+// a mount point is on another device than the directory it is in
+#[cfg(unix)]
+fn is_mounted(root: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (fs::metadata(root), root.parent().map(fs::metadata)) {
+        (Ok(root), Some(Ok(parent))) => root.dev() != parent.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_mounted(_root: &Path) -> bool {
+    false
+}
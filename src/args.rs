@@ -1,56 +1,293 @@
-use crate::store::Store;
-use clap::{App, Arg, ArgMatches, SubCommand};
-use prettytable::format;
+use crate::archive::{self, ArchiveFormat, Destination};
+use crate::backup;
+use crate::batch;
+use crate::calendar;
+use crate::config::{Config, DeletePolicy};
+use crate::dates;
+use crate::dedup;
+use crate::diff::{self, DirDiff};
+use crate::direnv;
+use crate::envvars;
+use crate::errors::ZugzugError;
+use crate::filter::{DirSubject, Filter};
+use crate::finder;
+use crate::foreach;
+use crate::fsutil;
+use crate::fuzzy;
+use crate::git;
+use crate::hooks;
+use crate::i18n;
+use crate::import;
+use crate::link;
+use crate::log;
+use crate::merge;
+use crate::output::{self, Color, Format};
+use crate::pattern::{self, DirRegex};
+use crate::platform;
+use crate::powershell;
+use crate::prompt;
+use crate::publish;
+use crate::readonly;
+use crate::report;
+use crate::scaffold::{self, ProjectType};
+use crate::secrets;
+use crate::serve;
+use crate::shellext;
+use crate::snapshot;
+use crate::store::{
+    self, ArchivedDir, Bucket, Context, DatePosition, Dir, DirMatch, LazyStore, RemovedDir, Store,
+};
+use crate::watch;
+use crate::webhook::{self, Event};
+use crate::zoxide;
+use chrono::{DateTime, Local, NaiveDate};
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
 use prettytable::Table;
+use serde_json::json;
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
 use std::error;
 use std::fs;
-use std::iter::repeat;
-use std::path::{Component, Path};
+use std::io::{self, Read};
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
 /// Create a simple table with no headers and aligned columns
+///
+/// The table is drawn in the style selected with `--style`.
 fn simple_table() -> Table {
     let mut table = Table::new();
-    let format = format::FormatBuilder::new().padding(0, 1).build();
-    table.set_format(format);
+    table.set_format(output::table_format());
     table
 }
 
+/// Find a tracked directory by exact or approximate name
+///
+/// Exact matches are preferred. Otherwise the best fuzzy match is used,
+/// after confirmation if its score is low. When several directories match
+/// equally well, the user is offered a choice between them.
+fn resolve_dir(store: &Store, name: &str) -> Result<Dir, Box<dyn error::Error + 'static>> {
+    let not_found = || ZugzugError::new(&t!("No directory named '{name}'", name = name));
+    match store.dir_match(name) {
+        DirMatch::Found(dir, score)
+            if score >= fuzzy::CONFIDENT_SCORE
+                || prompt::confirm_guess(&t!(
+                    "Did you mean '{name}'?",
+                    name = dir.full_name()
+                ))? =>
+        {
+            Ok(dir)
+        }
+        DirMatch::Ambiguous(dirs) => {
+            let items: Vec<String> = dirs
+                .iter()
+                .map(|dir| format!("{}  {}", dir.bucket, dir.full_name()))
+                .collect();
+            let question = t!("Directory name '{name}' is ambiguous:", name = name);
+            match prompt::select(&question, &items)? {
+                Some(i) => Ok(dirs[i].clone()),
+                None => Err(store::ambiguous_dirs(name, &dirs)),
+            }
+        }
+        _ => Err(Box::new(not_found())),
+    }
+}
+
+// resolve a directory that is about to be opened, recording the access
+//
+// The store is saved right away, since whatever runs in the directory may
+// use zz itself.
+fn open_dir(store: &mut Store, name: &str) -> Result<Dir, Box<dyn error::Error + 'static>> {
+    let dir = resolve_dir(store, name)?;
+    store.record_access(&dir.path)?;
+    store.save()?;
+    Ok(dir)
+}
+
 /// Add a new bucket to create directories in
-fn handle_bucket_add(name: &str, dir: &str) -> Result<(), Box<dyn error::Error + 'static>> {
-    let path = Path::new(dir);
-    if !path.exists() {
-        println!("Path does not exist: {}", dir);
+///
+/// A leading `~` and `$VAR` references in the path are expanded, and the
+/// path is made absolute. With `relative_to_home` it is stored as `~/...`
+/// instead, so the store works for other users and machines.
+///
+/// # Errors
+///
+/// - When the path is a file, or a directory the user can't make
+///   directories in
+///
+/// # Example
+///
+/// ```
+/// zz bucket add tmp .
+/// zz bucket add scratch ~/scratch --relative-to-home
+/// ```
+fn handle_bucket_add(
+    store: &mut Store,
+    name: &str,
+    dir: &str,
+    relative_to_home: bool,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let expanded = fsutil::expand_path(dir)?;
+    if !Path::new(&expanded).exists() {
+        println!("Path does not exist: {}", expanded);
         return Ok(());
     }
-
-    match Store::load() {
-        Ok(mut store) => {
-            store.add_bucket(name, dir)?;
+    readonly::check()?;
+    let path = fs::canonicalize(&expanded)?;
+    fsutil::check_bucket_dir(&path)?;
+    let dir = &match relative_to_home {
+        true => {
+            let home = dirs::home_dir()
+                .and_then(|home| fs::canonicalize(home).ok())
+                .ok_or_else(|| ZugzugError::new("Could not get home directory"))?;
+            let inside = path.strip_prefix(&home).map_err(|_| {
+                ZugzugError::new(&format!(
+                    "{} is not inside the home directory {}",
+                    path.display(),
+                    home.display()
+                ))
+            })?;
+            Path::new("~").join(inside).to_string_lossy().into_owned()
         }
-        Err(e) => println!("{}", e),
+        false => path.to_string_lossy().into_owned(),
+    };
+    store.add_bucket(name, dir)?;
+    if format.is_json() {
+        output::print_json(&json!({"added": {"name": name, "path": dir}}));
     }
     Ok(())
 }
 
-/// Set the default bucket for creating new directories
-fn handle_bucket_default(name: Option<&str>) -> Result<(), Box<dyn error::Error + 'static>> {
+/// Get or set the default bucket for creating new directories
+///
+/// # Example
+///
+/// ```
+/// # Print the default bucket
+/// zz default
+///
+/// # Set the default bucket
+/// zz default my_bucket
+///
+/// # Unset the default bucket
+/// zz default --unset
+/// ```
+fn handle_bucket_default(
+    store: &mut Store,
+    name: Option<&str>,
+    unset: bool,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if unset {
+        store.unset_default_bucket()?;
+        if format.is_json() {
+            output::print_json(&json!({ "default": null }));
+        }
+        return Ok(());
+    }
     match name {
-        Some(name) => match Store::load() {
-            Ok(mut store) => {
-                store.set_default_bucket(name)?;
+        Some(name) => {
+            let name = store.resolve_bucket(name)?.name.clone();
+            store.set_default_bucket(&name)?;
+            if format.is_json() {
+                output::print_json(&json!({ "default": name }));
             }
-            Err(e) => println!("{}", e),
-        },
-        None => match Store::load() {
-            Ok(store) => {
-                if let Some(bucket) = store.default_bucket() {
-                    println!("{}", bucket.name);
-                } else {
-                    println!("Default bucket is not set");
-                }
+        }
+        None => {
+            if format.is_json() {
+                let name = store.default_bucket().map(|b| b.name.clone());
+                output::print_json(&json!({ "default": name }));
+            } else if let Some(bucket) = store.default_bucket() {
+                println!("{}", bucket.name);
+            } else {
+                println!("Default bucket is not set");
             }
-            Err(e) => println!("{}", e),
-        },
+        }
+    }
+    Ok(())
+}
+
+/// Manage contexts, named modes with their own default bucket
+///
+/// While a context is in use, `zz mkdir` and friends create directories in
+/// its default bucket, and commands working on all buckets, like `zz ls`,
+/// only see the buckets it lists. `zz default` changes the context's default
+/// bucket. `ZZ_CONTEXT` picks a context for a single shell.
+///
+/// # Example
+///
+/// ```
+/// zz context add work --default work -b work -b scratch
+/// zz context use work
+/// zz context
+/// zz context ls
+/// zz context use --none
+/// zz context rm work
+/// ```
+fn handle_context(
+    store: &mut Store,
+    matches: &ArgMatches,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(matches) = matches.subcommand_matches("add") {
+        let default_bucket = match matches.value_of("default") {
+            Some(name) => Some(store.resolve_bucket(name)?.name.clone()),
+            None => None,
+        };
+        let buckets = matches
+            .values_of("bucket")
+            .unwrap_or_default()
+            .map(|name| Ok(store.resolve_bucket(name)?.name.clone()))
+            .collect::<Result<Vec<String>, Box<dyn error::Error + 'static>>>()?;
+        let context = Context {
+            default_bucket,
+            buckets,
+        };
+        store.set_context(matches.value_of("NAME").unwrap(), context)?;
+    } else if let Some(matches) = matches.subcommand_matches("use") {
+        store.use_context(matches.value_of("NAME"))?;
+    } else if let Some(matches) = matches.subcommand_matches("rm") {
+        store.remove_context(matches.value_of("NAME").unwrap())?;
+    } else if matches.subcommand_matches("ls").is_some() {
+        let active = store.active_context().map(|(name, _)| name.to_string());
+        let mut entries = vec![];
+        let mut table = simple_table();
+        for (name, context) in store.contexts() {
+            let is_active = active.as_ref() == Some(name);
+            entries.push(json!({
+                "name": name,
+                "active": is_active,
+                "default": context.default_bucket,
+                "buckets": context.buckets,
+            }));
+            table.add_row(row![
+                if is_active { "*" } else { "" },
+                name,
+                context.default_bucket.as_deref().unwrap_or("-"),
+                match context.buckets.is_empty() {
+                    true => "all".to_string(),
+                    false => context.buckets.join(", "),
+                }
+            ]);
+        }
+        match format {
+            Format::Json | Format::JsonLines => output::print_json_records(entries, format),
+            _ => table.printstd(),
+        }
+    } else if format.is_json() {
+        let active = store.active_context().map(|(name, _)| name);
+        output::print_json(&json!({ "context": active }));
+    } else {
+        match store.active_context() {
+            Some((name, _)) => println!("{}", name),
+            None => println!("No context in use"),
+        }
     }
     Ok(())
 }
@@ -62,223 +299,5931 @@ fn handle_bucket_default(name: Option<&str>) -> Result<(), Box<dyn error::Error
 /// If the bucket was the default bucket, the default bucket becomes
 /// unset until manually changed.
 ///
+/// Buckets added by other users of a shared store are only forgotten with
+/// `force`.
+///
 /// # Example
 ///
 /// ```
 /// zz bucket forget my_bucket
 /// ```
-fn handle_bucket_forget(name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
-    match Store::load() {
-        Ok(mut store) => {
-            let original_length = store.buckets().len();
-            store.forget_bucket(name)?;
-            let new_length = store.buckets().len();
-            if new_length == original_length {
-                println!("Bucket '{}' does not exist", name);
-            }
+fn handle_bucket_forget(
+    store: &mut Store,
+    name: &str,
+    force: bool,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let name = &store
+        .match_bucket(name)?
+        .map_or_else(|| name.to_string(), |b| b.name.clone());
+    if let Some(owner) = store.find_bucket(name).and_then(|b| b.owner.clone()) {
+        if !force && Some(&owner) != store::current_user().as_ref() {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "Bucket '{}' belongs to {}, use --force to forget it anyway",
+                name, owner
+            ))));
         }
-        Err(e) => println!("{}", e),
+    }
+    if store.find_bucket(name).is_some()
+        && !prompt::confirm_change(&t!("Forget bucket '{name}'?", name = name))?
+    {
+        return Ok(());
+    }
+    let original_length = store.buckets().len();
+    store.forget_bucket(name)?;
+    let new_length = store.buckets().len();
+    if format.is_json() {
+        output::print_json(&json!({
+            "forgotten": name,
+            "existed": new_length != original_length,
+        }));
+    } else if new_length == original_length {
+        println!("Bucket '{}' does not exist", name);
     }
     Ok(())
 }
 
-/// List buckets by name with its path
+/// Manage environment variables inherited by a bucket's directories
 ///
 /// # Example
 ///
 /// ```
-/// # List buckets
-/// zz bucket ls
+/// zz bucket env set my_bucket RUST_LOG=debug
+/// zz bucket env unset my_bucket RUST_LOG
+/// zz bucket env ls my_bucket
 /// ```
-fn handle_bucket_ls() {
-    match Store::load() {
-        Ok(store) => {
-            let mut table = simple_table();
-            for bucket in store.buckets().into_iter() {
-                table.add_row(row![bucket.name, bucket.path]);
-            }
-            table.printstd();
+fn handle_bucket_env(
+    store: &mut Store,
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(matches) = matches.subcommand_matches("set") {
+        let name = store
+            .resolve_bucket(matches.value_of("NAME").unwrap())?
+            .name
+            .clone();
+        let vars = matches
+            .values_of("VARS")
+            .unwrap()
+            .map(envvars::parse_assignment)
+            .collect::<Result<Vec<_>, _>>()?;
+        store.set_bucket_env(&name, &vars)?;
+    } else if let Some(matches) = matches.subcommand_matches("unset") {
+        let name = store
+            .resolve_bucket(matches.value_of("NAME").unwrap())?
+            .name
+            .clone();
+        let keys: Vec<&str> = matches.values_of("KEYS").unwrap().collect();
+        store.unset_bucket_env(&name, &keys)?;
+    } else if let Some(matches) = matches.subcommand_matches("ls") {
+        let bucket = store.resolve_bucket(matches.value_of("NAME").unwrap())?;
+        for (key, value) in bucket.env.iter() {
+            println!("{}={}", key, value);
         }
-        Err(e) => println!("{}", e),
     }
+    Ok(())
 }
 
-/// List all directories across buckets
+/// Manage the post-create hooks of a bucket
+///
+/// Hooks are shell commands run inside every new directory of the bucket,
+/// with the directory's environment exported.
 ///
 /// # Example
 ///
 /// ```
-/// # Long list
-/// zz ls
+/// zz bucket hook add my_bucket 'git init -q'
+/// zz bucket hook ls my_bucket
+/// zz bucket hook clear my_bucket
+/// ```
+fn handle_bucket_hook(
+    store: &mut Store,
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(matches) = matches.subcommand_matches("add") {
+        let name = store
+            .resolve_bucket(matches.value_of("NAME").unwrap())?
+            .name
+            .clone();
+        store.add_bucket_hook(&name, matches.value_of("COMMAND").unwrap())?;
+    } else if let Some(matches) = matches.subcommand_matches("ls") {
+        let bucket = store.resolve_bucket(matches.value_of("NAME").unwrap())?;
+        for hook in bucket.hooks.post_create.iter() {
+            println!("{}", hook);
+        }
+    } else if let Some(matches) = matches.subcommand_matches("clear") {
+        let name = store
+            .resolve_bucket(matches.value_of("NAME").unwrap())?
+            .name
+            .clone();
+        store.clear_bucket_hooks(&name)?;
+    }
+    Ok(())
+}
+
+/// Manage the paths a bucket spans
+///
+/// `zz ls` merges the directories of all paths. New directories go into
+/// the primary path, or with the `most-free` placement into the path with
+/// the most free space.
+///
+/// # Example
 ///
-/// # List directories in a specific bucket
-/// zz ls -b my_bucket
 /// ```
-fn handle_ls(filter_bucket_name: Option<&str>) {
-    match Store::load() {
-        Err(e) => println!("{}", e),
-        Ok(store) => {
-            let mut table = simple_table();
-            store
-                .buckets()
-                .into_iter()
-                .filter(|b| match filter_bucket_name {
-                    Some(bucket_name) => b.name == bucket_name,
-                    None => true,
-                })
-                .filter_map(|b| match fs::read_dir(Path::new(&b.path)) {
-                    Ok(result) => Some((b, result)),
-                    Err(err) => {
-                        println!("Unable to read dir: {}", err);
-                        None
-                    }
-                })
-                .flat_map(|(bucket, read_result)| repeat(bucket).zip(read_result))
-                .for_each(|(bucket, dir)| match dir {
-                    Ok(dir) => {
-                        let path = dir.path();
-                        let path_str = path.to_str().unwrap();
-                        let last_part = path.components().last();
-                        if let Some(Component::Normal(last)) = last_part {
-                            let name_with_date = last.to_str().unwrap();
-                            let strings: Vec<&str> = name_with_date.splitn(2, "_").collect();
-                            let (date, name) = (strings[0], strings[1]);
-                            table.add_row(row![bucket.name, date, name, path_str]);
-                        } else {
-                            panic!(format!("Couldn't get dir name from path: {}", path_str));
-                        }
-                    }
-                    Err(err) => {
-                        println!("Error reading dir: {}", err);
-                    }
-                });
-            table.printstd();
+/// zz bucket path add scratch /mnt/big/scratch
+/// zz bucket path placement scratch most-free
+/// zz bucket path ls scratch
+/// zz bucket path rm scratch /mnt/big/scratch
+/// ```
+fn handle_bucket_path(
+    store: &mut Store,
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(matches) = matches.subcommand_matches("add") {
+        let name = store
+            .resolve_bucket(matches.value_of("NAME").unwrap())?
+            .name
+            .clone();
+        let dir = fsutil::expand_path(matches.value_of("DIR").unwrap())?;
+        if !Path::new(&dir).exists() {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "{} does not exist",
+                dir
+            ))));
+        }
+        readonly::check()?;
+        let dir = fs::canonicalize(&dir)?.to_string_lossy().into_owned();
+        fsutil::check_bucket_dir(Path::new(&dir))?;
+        store.add_bucket_path(&name, &dir, matches.is_present("primary"))?;
+    } else if let Some(matches) = matches.subcommand_matches("rm") {
+        let bucket = store.resolve_bucket(matches.value_of("NAME").unwrap())?;
+        let dir = fsutil::expand_path(matches.value_of("DIR").unwrap())?;
+        let canonical = fs::canonicalize(&dir).ok();
+        let path = bucket
+            .paths()
+            .into_iter()
+            .find(|path| {
+                *path == Path::new(&dir)
+                    || (canonical.is_some() && fs::canonicalize(path).ok() == canonical)
+            })
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or(dir);
+        let name = bucket.name.clone();
+        store.remove_bucket_path(&name, &path)?;
+    } else if let Some(matches) = matches.subcommand_matches("ls") {
+        let bucket = store.resolve_bucket(matches.value_of("NAME").unwrap())?;
+        for path in bucket.paths() {
+            println!("{}", path.display());
+        }
+    } else if let Some(matches) = matches.subcommand_matches("placement") {
+        let name = store
+            .resolve_bucket(matches.value_of("NAME").unwrap())?
+            .name
+            .clone();
+        match matches.value_of("PLACEMENT") {
+            Some(placement) => store.set_bucket_most_free(&name, placement == "most-free")?,
+            None => match store.resolve_bucket(&name)?.most_free {
+                true => println!("most-free"),
+                false => println!("primary"),
+            },
         }
     }
+    Ok(())
 }
 
-/// Make a new directory in a bucket
+/// Move a bucket's directory somewhere else
 ///
-/// By default this will create a new directory prefixed with the current date
-/// in the default bucket.
+/// The bucket keeps its name, and its directories their metadata and
+/// access history. Moving onto another filesystem copies and verifies the
+/// bucket before the original is removed. The new path is expanded and
+/// made absolute like in `zz bucket add`.
 ///
-/// # Errors
+/// # Example
 ///
-/// - When `-b/--bucket` is used, and the bucket doesn't exist
-/// - When `-b/--bucket` is not used and there is no default bucket
-fn handle_mkdir(name: &str, bucket: Option<&str>) {
-    match Store::load() {
-        Ok(store) => {
-            let selected_bucket = match bucket {
-                Some(bucket_name) => store.find_bucket(bucket_name),
-                None => store.default_bucket(),
-            };
+/// ```
+/// zz bucket mv my_bucket /mnt/big/my_bucket
+/// ```
+fn handle_bucket_mv(
+    store: &mut Store,
+    name: &str,
+    dir: &str,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    readonly::check()?;
+    let bucket = store.resolve_bucket(name)?.clone();
+    let path = env::current_dir()?.join(fsutil::expand_path(dir)?);
+    if path.exists() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} already exists",
+            path.display()
+        ))));
+    }
+    // the bucket isn't there yet, but the directory it goes in may be
+    let path = match (path.parent().map(fs::canonicalize), path.file_name()) {
+        (Some(Ok(parent)), Some(file_name)) => parent.join(file_name),
+        _ => path,
+    };
+    let dir = path.to_string_lossy().into_owned();
+    if !prompt::confirm_change(&t!(
+        "Move bucket '{name}' to {path}?",
+        name = bucket.name,
+        path = dir
+    ))? {
+        return Ok(());
+    }
+    fsutil::move_dir(Path::new(&bucket.path), &path)?;
+    store.move_bucket(&bucket.name, &dir)?;
+    Ok(())
+}
+
+/// Show or change the permission mode of a bucket's new directories
+///
+/// # Example
+///
+/// ```
+/// # Keep new directories private to their owner
+/// zz bucket mode my_bucket 0700
+///
+/// zz bucket mode my_bucket
+/// zz bucket mode my_bucket --unset
+/// ```
+fn handle_bucket_mode(
+    store: &mut Store,
+    name: &str,
+    mode: Option<&str>,
+    unset: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let bucket = store.resolve_bucket(name)?.clone();
+    if unset {
+        store.set_bucket_mode(&bucket.name, None)?;
+    } else if let Some(mode) = mode {
+        store.set_bucket_mode(&bucket.name, Some(mode))?;
+    } else if let Some(mode) = bucket.mode {
+        println!("{}", mode);
+    }
+    Ok(())
+}
+
+/// Turn journal mode of a bucket on or off
+///
+/// A journal bucket holds one directory per day, named by the date only,
+/// which `zz today` creates on first use.
+///
+/// # Example
+///
+/// ```
+/// zz bucket journal notes
+/// zz bucket journal notes --off
+/// ```
+fn handle_bucket_journal(
+    store: &mut Store,
+    name: &str,
+    off: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let name = store.resolve_bucket(name)?.name.clone();
+    store.set_bucket_journal(&name, !off)
+}
+
+/// Show or set where a bucket's directories carry their date
+///
+/// `suffix` names new directories like `report_20250301`, so listings
+/// sorted by name group them by topic, and `none` leaves dates out. Existing
+/// directories keep their names, with dated prefixes still understood.
+///
+/// # Example
+///
+/// ```
+/// zz bucket date-position reports suffix
+/// zz bucket date-position reports
+/// ```
+fn handle_bucket_date_position(
+    store: &mut Store,
+    name: &str,
+    date_position: Option<&str>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let bucket = store.resolve_bucket(name)?.clone();
+    match date_position {
+        Some(date_position) => {
+            store.set_bucket_date_position(&bucket.name, date_position.parse()?)?
+        }
+        None => println!("{}", bucket.date_position),
+    }
+    Ok(())
+}
+
+/// Show or set the order of buckets
+///
+/// Buckets are listed in this order by `zz bucket ls` and `zz ls`, and when
+/// a name is ambiguous, the bucket listed first wins. Buckets not named
+/// come after the named ones, in the order they were added. Without
+/// `names` the current order is printed, with `reset` it is removed.
+///
+/// # Example
+///
+/// ```
+/// zz bucket order work scratch
+/// zz bucket order
+/// zz bucket order --reset
+/// ```
+fn handle_bucket_order(
+    store: &mut Store,
+    names: &[&str],
+    reset: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if !names.is_empty() || reset {
+        let names = names
+            .iter()
+            .map(|name| Ok(store.resolve_bucket(name)?.name.clone()))
+            .collect::<Result<Vec<String>, Box<dyn error::Error + 'static>>>()?;
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        return store.set_bucket_order(&names);
+    }
+    for bucket in store.buckets_iter() {
+        match bucket.priority {
+            Some(priority) => println!("{} {}", priority, bucket.name),
+            None => println!("- {}", bucket.name),
+        }
+    }
+    Ok(())
+}
+
+/// Register an existing directory tree as a bucket
+///
+/// Directories already named `YYYYMMDD_name` are picked up as they are,
+/// and the project type of each is detected from its manifest. Near misses
+/// like `2020-01-31 notes` or `notes_20200131` are listed, and renamed to
+/// the zz format with `adopt`, with the date where an existing bucket's
+/// `date_position` puts it. The bucket is named after the directory
+/// unless `name` is given. `dry_run` only shows what would happen.
+///
+/// # Example
+///
+/// ```
+/// zz bucket import ~/work --dry-run
+/// zz bucket import ~/work --adopt
+/// ```
+fn handle_bucket_import(
+    store: &mut Store,
+    dir: &str,
+    name: Option<&str>,
+    adopt: bool,
+    dry_run: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if !dry_run {
+        readonly::check()?;
+    }
+    let root = fs::canonicalize(dir)
+        .map_err(|e| ZugzugError::new(&format!("Unable to read {}: {}", dir, e)))?;
+    let root_str = root.to_string_lossy().into_owned();
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => root
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .ok_or_else(|| ZugzugError::new("Unable to name the bucket, use --name"))?,
+    };
+    let date_position = store
+        .find_bucket(&name)
+        .map(|bucket| bucket.date_position)
+        .unwrap_or_default();
+    match store.find_bucket(&name) {
+        Some(bucket) if fs::canonicalize(&bucket.path).ok().as_ref() != Some(&root) => {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "A bucket named '{}' already exists at {}",
+                name, bucket.path
+            ))));
+        }
+        Some(_) => {}
+        None if dry_run => println!("Would add bucket {} at {}", name, root_str),
+        None => {
+            fsutil::check_bucket_dir(&root)?;
+            store.add_bucket(&name, &root_str)?;
+            println!("Added bucket {} at {}", name, root_str);
+        }
+    }
 
-            if let Some(bucket) = selected_bucket {
-                match bucket.make_dir(name) {
-                    Ok(path) => println!("{}", path.to_str().unwrap()),
-                    Err(e) => println!("Error: {}", e),
+    let mut table = simple_table();
+    let mut counts = (0, 0, 0);
+    for candidate in import::scan(&root, date_position)? {
+        let (status, dir_name, note) = match candidate {
+            import::Candidate::Recognized(dir_name) => {
+                counts.0 += 1;
+                let path = root.join(&dir_name);
+                let detected = match store.dir_meta(&path).and_then(|m| m.project_type) {
+                    Some(_) => None,
+                    None => scaffold::detect(&path),
                 };
-            } else {
-                println!("No bucket to choose from");
+                if let (Some(project_type), false) = (detected, dry_run) {
+                    store.set_project_type(&path, &project_type.to_string())?;
+                }
+                let note = detected.map(|t| t.to_string()).unwrap_or_default();
+                ("imported", dir_name, note)
             }
-        }
-        Err(e) => println!("{}", e),
+            import::Candidate::NearMiss { name, adopted } => {
+                counts.1 += 1;
+                let status = match (adopt, dry_run) {
+                    (false, _) => "near miss",
+                    (true, true) => "would adopt",
+                    (true, false) if root.join(&adopted).exists() => "taken",
+                    (true, false) => {
+                        fs::rename(root.join(&name), root.join(&adopted))?;
+                        "adopted"
+                    }
+                };
+                (status, name, format!("-> {}", adopted))
+            }
+            import::Candidate::Other(dir_name) => {
+                counts.2 += 1;
+                ("ignored", dir_name, String::new())
+            }
+        };
+        table.add_row(row![status, dir_name, note]);
+    }
+    table.printstd();
+    println!(
+        "{} recognized, {} near misses, {} ignored",
+        counts.0, counts.1, counts.2
+    );
+    if counts.1 > 0 && !adopt {
+        println!("Use --adopt to rename near misses to the zz format");
     }
+    Ok(())
 }
 
-/// Parse CLI arguments
-pub fn parse_args<'a>() -> Result<ArgMatches<'a>, Box<dyn error::Error + 'static>> {
-    let matches = App::new("zz")
-        .version("0.1.0")
-        .author("Erik R. <eronshagen@gmail.com>")
-        .about("Manage temporary working directories")
-        .subcommand(
-            SubCommand::with_name("bucket")
-                .about("Manage buckets")
-                .subcommand(
-                    SubCommand::with_name("add")
-                        .arg(
-                            Arg::with_name("NAME")
-                                .help("Name of the bucket")
-                                .required(true),
-                        )
-                        .arg(
-                            Arg::with_name("DIR")
-                                .help("Path to the bucket")
-                                .required(true),
-                        ),
-                )
-                .subcommand(
-                    SubCommand::with_name("default")
-                        .about("Get or set the default bucket")
-                        .arg(
-                            Arg::with_name("NAME")
-                                .help("Set the default bucket to this bucket")
-                                .required(false),
-                        ),
-                )
-                .subcommand(
-                    SubCommand::with_name("forget").arg(
-                        Arg::with_name("NAME")
-                            .help("Name of the bucket")
-                            .required(true),
-                    ),
-                )
-                .subcommand(SubCommand::with_name("ls").about("List buckets")),
-        )
-        .subcommand(
-            SubCommand::with_name("ls").about("List directories").arg(
-                Arg::with_name("bucket")
-                    .short("b")
-                    .long("bucket")
-                    .value_name("BUCKET_NAME")
-                    // .takes_value(true) ???
-                    .help("List directories in this bucket"),
-            ),
-        )
-        .subcommand(
-            SubCommand::with_name("mkdir")
-                .about("Make a new directory")
-                .arg(
-                    Arg::with_name("bucket")
-                        .help("Select bucket to create the directory in")
-                        .short("b")
-                        .long("bucket")
-                        .value_name("BUCKET_NAME"),
-                )
-                .arg(
-                    Arg::with_name("NAME")
-                        .help("Name of the dir")
-                        .required(true),
-                ),
-        )
-        .get_matches();
-    Ok(matches)
+/// Bring zoxide's history of directories in buckets into zz
+///
+/// Every path zoxide knows with at least `min_score` that is inside a
+/// bucket counts as use of the bucket directory it is in, so `zz recent`
+/// and `zz find` rank it accordingly. Scores of several paths in the same
+/// directory add up.
+///
+/// # Example
+///
+/// ```
+/// zz import zoxide
+/// zz import zoxide -b work --min-score 5
+/// ```
+fn handle_import_zoxide(
+    store: &mut Store,
+    bucket: Option<&str>,
+    min_score: f64,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let buckets: Vec<String> = store
+        .select_buckets(bucket)?
+        .into_iter()
+        .map(|b| b.name.clone())
+        .collect();
+    let mut scores: BTreeMap<PathBuf, f64> = BTreeMap::new();
+    for (score, path) in zoxide::scores()? {
+        if score < min_score {
+            continue;
+        }
+        if let Some((bucket, Some(dir))) = store.locate(&path) {
+            if buckets.contains(&bucket.name) && dir.path.is_dir() {
+                *scores.entry(dir.path).or_insert(0.0) += score;
+            }
+        }
+    }
+    let mut entries = vec![];
+    let mut table = simple_table();
+    for (path, score) in scores {
+        let modified = fs::metadata(&path)?.modified()?;
+        entries.push((path.clone(), (score.round() as u64).max(1), modified.into()));
+        table.add_row(row![r->format!("{:.1}", score), path.display()]);
+    }
+    if entries.is_empty() {
+        println!("zoxide knows no directories in these buckets");
+        return Ok(());
+    }
+    store.import_access(&entries)?;
+    table.printstd();
+    println!("Imported {} directories from zoxide", entries.len());
+    Ok(())
 }
 
-/// Dispatch sub-command handlers based on the parsed args
-pub fn handle_parsed_args(matches: ArgMatches) -> Result<(), Box<dyn error::Error + 'static>> {
-    if let Some(matches) = matches.subcommand_matches("bucket") {
-        if let Some(matches) = matches.subcommand_matches("add") {
-            handle_bucket_add(
-                matches.value_of("NAME").unwrap(),
-                matches.value_of("DIR").unwrap(),
-            )?;
-        } else if let Some(matches) = matches.subcommand_matches("default") {
-            handle_bucket_default(matches.value_of("NAME"))?;
-        } else if let Some(matches) = matches.subcommand_matches("forget") {
-            handle_bucket_forget(matches.value_of("NAME").unwrap())?
-        } else if let Some(_matches) = matches.subcommand_matches("ls") {
-            handle_bucket_ls()
+/// Find directories that look like buckets and offer to register them
+///
+/// Walks `root` up to `depth` levels deep for directories with at least
+/// `min_dated` children named like zz directories. Directories that are
+/// buckets already are skipped, and each other one is registered under its
+/// own name if confirmed.
+///
+/// # Example
+///
+/// ```
+/// zz discover ~
+/// zz discover ~/src --depth 2 --min 10
+/// ```
+fn handle_discover(
+    store: &mut Store,
+    root: &str,
+    depth: usize,
+    min_dated: usize,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let known: Vec<PathBuf> = store
+        .buckets_iter()
+        .filter_map(|b| fs::canonicalize(&b.path).ok())
+        .collect();
+    let root = fs::canonicalize(root)?;
+    let found: Vec<import::Discovered> = import::discover(&root, depth, min_dated)?
+        .into_iter()
+        .filter(|d| !known.contains(&d.path))
+        .collect();
+    if found.is_empty() {
+        println!("No new buckets found below {}", root.display());
+        return Ok(());
+    }
+    for discovered in found {
+        let mut name = match discovered.path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        let path = discovered.path.to_string_lossy().into_owned();
+        if store.find_bucket(&name).is_some() {
+            let question = t!(
+                "A bucket named '{name}' exists already. Name for {path} (enter=skip):",
+                name = name,
+                path = path
+            );
+            match prompt::input(&question, None)? {
+                Some(other) if store.find_bucket(&other).is_none() => name = other,
+                _ => {
+                    println!(
+                        "Skipping {}, a bucket named '{}' exists already. Use zz bucket import {} --name",
+                        path, name, path
+                    );
+                    continue;
+                }
+            }
         }
-    } else if let Some(matches) = matches.subcommand_matches("ls") {
-        handle_ls(matches.value_of("bucket"));
-    } else if let Some(matches) = matches.subcommand_matches("mkdir") {
-        handle_mkdir(
-            matches.value_of("NAME").unwrap(),
-            matches.value_of("bucket"),
+        let question = t!(
+            "{path} has {dated} of {total} directories dated. Add it as bucket '{name}'?",
+            path = path,
+            dated = discovered.dated,
+            total = discovered.total,
+            name = name
         );
+        if prompt::confirm(&question)? {
+            readonly::check()?;
+            fsutil::check_bucket_dir(&discovered.path)?;
+            store.add_bucket(&name, &path)?;
+        }
+    }
+    Ok(())
+}
+
+/// List buckets by name with its path
+///
+/// The default bucket is marked with `*`. Each bucket also shows how many
+/// directories it contains and the dates of its oldest and newest
+/// directory. With `size`, the total size of the directories is computed as
+/// well, which may take a while for large buckets.
+///
+/// # Example
+///
+/// ```
+/// # List buckets
+/// zz bucket ls
+///
+/// # Include the total size of each bucket
+/// zz bucket ls --size
+///
+/// # Machine readable
+/// zz bucket ls --json
+/// ```
+fn handle_bucket_ls(store: &Store, size: bool, format: Format) {
+    let default_name = store.default_bucket().map(|b| b.name.clone());
+    let mut sizes: HashMap<String, u64> = HashMap::new();
+    if size {
+        for (dir, dir_size) in fsutil::dir_sizes(store.all_dirs()) {
+            *sizes.entry(dir.bucket).or_insert(0) += dir_size;
+        }
+    }
+
+    let mut titles = vec!["default", "name", "path", "dirs", "oldest", "newest"];
+    if size {
+        titles.push("size");
+    }
+    titles.push("owner");
+    let mut table = simple_table();
+    let mut entries = vec![];
+    for bucket in store.buckets_iter() {
+        let is_default = default_name.as_ref() == Some(&bucket.name);
+        let marker = if is_default { "*" } else { "" };
+        let dirs = bucket.dirs().unwrap_or_default();
+        let mut dates: Vec<&str> = dirs
+            .iter()
+            .map(|d| d.date.as_str())
+            .filter(|d| !d.is_empty())
+            .collect();
+        dates.sort_unstable();
+        if format.is_json() {
+            let mut entry = json!({
+                "name": bucket.name,
+                "path": bucket.path,
+                "extra_paths": bucket.extra_paths,
+                "default": is_default,
+                "dirs": dirs.len(),
+                "oldest": dates.first(),
+                "newest": dates.last(),
+                "owner": bucket.owner,
+            });
+            if size {
+                entry["size"] = json!(sizes.get(&bucket.name).cloned().unwrap_or(0));
+            }
+            entries.push(entry);
+            continue;
+        }
+        if format == Format::Porcelain {
+            let size = match size {
+                true => sizes.get(&bucket.name).cloned().unwrap_or(0).to_string(),
+                false => String::new(),
+            };
+            output::print_porcelain(
+                "bucket",
+                &[
+                    &bucket.name,
+                    &bucket.path,
+                    if is_default { "1" } else { "0" },
+                    &dirs.len().to_string(),
+                    dates.first().unwrap_or(&""),
+                    dates.last().unwrap_or(&""),
+                    bucket.owner.as_deref().unwrap_or(""),
+                    &size,
+                ],
+            );
+            continue;
+        }
+        let mut row = row![
+            marker,
+            bucket.name,
+            bucket.path,
+            r->dirs.len(),
+            dates.first().unwrap_or(&"-"),
+            dates.last().unwrap_or(&"-")
+        ];
+        if size {
+            row.add_cell(cell!(r->fsutil::human_size(
+                sizes.get(&bucket.name).cloned().unwrap_or(0)
+            )));
+        }
+        row.add_cell(cell!(bucket.owner.as_deref().unwrap_or_default()));
+        table.add_row(row);
+    }
+    match format {
+        Format::Json | Format::JsonLines => output::print_json_records(entries, format),
+        Format::Table => table.printstd(),
+        Format::Markdown => output::print_markdown(&titles, &table),
+        Format::Porcelain => {}
+    }
+}
+
+/// Which directories `zz ls` lists
+struct ListFilter<'a> {
+    bucket: Option<&'a str>,
+    project_type: Option<&'a str>,
+    /// Glob matched against the name without the date
+    name: Option<&'a str>,
+    regex: Option<DirRegex>,
+    /// `--where` expression, see `Filter`
+    filter: Option<Filter>,
+    /// Also list deprecated directories
+    deprecated: bool,
+}
+
+/// How `zz ls` shows the directories it lists
+struct ListView<'a> {
+    git: bool,
+    journal: bool,
+    sort: Option<&'a str>,
+    group_by: Option<&'a str>,
+    columns: Vec<String>,
+    symlinks: Symlinks,
+}
+
+/// What `zz ls` does with directories in a bucket that are symlinks
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Symlinks {
+    /// List them with the path and size of their target
+    Follow,
+    /// List them as `link -> target`
+    Mark,
+    Skip,
+}
+
+const SYMLINKS: &[&str] = &["follow", "mark", "skip"];
+
+impl FromStr for Symlinks {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<Symlinks, ZugzugError> {
+        match s {
+            "follow" => Ok(Symlinks::Follow),
+            "mark" => Ok(Symlinks::Mark),
+            "skip" => Ok(Symlinks::Skip),
+            _ => Err(ZugzugError::new(&format!(
+                "Unknown symlink handling '{}', expected one of {}",
+                s,
+                SYMLINKS.join(", ")
+            ))),
+        }
+    }
+}
+
+impl Symlinks {
+    // `--follow-symlinks` and `--no-symlinks`, then `ls.symlinks`
+    fn from_matches(matches: &ArgMatches) -> Result<Symlinks, Box<dyn error::Error + 'static>> {
+        if matches.is_present("follow-symlinks") {
+            return Ok(Symlinks::Follow);
+        }
+        if matches.is_present("no-symlinks") {
+            return Ok(Symlinks::Skip);
+        }
+        match Config::load()?.ls.symlinks {
+            Some(symlinks) => Ok(symlinks.parse()?),
+            None => Ok(Symlinks::Mark),
+        }
+    }
+}
+
+/// List all directories across buckets
+///
+/// Deprecated directories are left out unless `--deprecated` is given.
+///
+/// # Example
+///
+/// ```
+/// # Long list
+/// zz ls
+///
+/// # List directories in a specific bucket
+/// zz ls -b my_bucket
+///
+/// # List directories scaffolded as rust projects
+/// zz ls --type rust
+///
+/// # Show which git repositories have uncommitted or unpushed work
+/// zz ls --git
+///
+/// # Only directories whose name (without the date) matches a glob
+/// zz ls --name 'api-*'
+///
+/// # Match a regex against the full path instead
+/// zz ls --regex '/clients/[a-z]+/' --match-on path
+///
+/// # Calendar of the days with entries in journal buckets
+/// zz ls --journal
+///
+/// # One table per month, with the number of directories in each
+/// zz ls --group-by month
+///
+/// # Choose the columns and their order
+/// zz ls --columns date,name,size,tags
+///
+/// # Show symlinked directories as their targets, or not at all
+/// zz ls --follow-symlinks
+/// zz ls --no-symlinks
+///
+/// # Filter with an expression
+/// zz ls --where 'age>30d and size>1G and tag!=keep'
+///
+/// # Apply a view saved in the config, e.g. `stale = "age>60d sort:size"`
+/// zz ls --view stale
+///
+/// # Include directories set aside with `zz deprecate`
+/// zz ls --deprecated
+/// ```
+fn handle_ls(store: &Store, filter: &ListFilter, view: &ListView, format: Format) {
+    let &ListView {
+        git,
+        journal,
+        sort,
+        group_by,
+        ref columns,
+        symlinks,
+    } = view;
+    let buckets = match store.select_buckets(filter.bucket) {
+        Ok(buckets) => buckets,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+    let mut dirs: Vec<Dir> = buckets
+        .into_iter()
+        .filter(|b| !journal || b.journal)
+        .filter_map(|b| match b.dirs() {
+            Ok(dirs) => Some(dirs),
+            Err(err) => {
+                warn!("Unable to read dir: {}", err);
+                None
+            }
+        })
+        .flatten()
+        .filter(|dir| match filter.project_type {
+            Some(project_type) => {
+                store
+                    .dir_meta(&dir.path)
+                    .and_then(|meta| meta.project_type)
+                    .as_deref()
+                    == Some(project_type)
+            }
+            None => true,
+        })
+        .filter(|dir| {
+            filter
+                .name
+                .is_none_or(|glob| pattern::glob_match(glob, &dir.name))
+        })
+        .filter(|dir| {
+            filter
+                .regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(dir))
+        })
+        .filter(|dir| {
+            filter
+                .filter
+                .as_ref()
+                .is_none_or(|filter| filter.matches(&DirSubject::new(dir, store)))
+        })
+        .filter(|dir| filter.deprecated || !store.dir_deprecated(&dir.path))
+        .collect();
+    // targets of the directories that are symlinks
+    let targets: HashMap<PathBuf, PathBuf> = dirs
+        .iter()
+        .filter(|dir| link::is_link(&dir.path))
+        .filter_map(|dir| Some((dir.path.clone(), fs::canonicalize(&dir.path).ok()?)))
+        .collect();
+    if symlinks == Symlinks::Skip {
+        dirs.retain(|dir| !targets.contains_key(&dir.path));
+    }
+    // where a directory's contents are looked at
+    let real_path = |dir: &Dir| match (symlinks, targets.get(&dir.path)) {
+        (Symlinks::Follow, Some(target)) => target.clone(),
+        _ => dir.path.clone(),
+    };
+    if journal {
+        print_journal(&dirs);
+        return;
+    }
+    let mut columns: Vec<&str> = columns.iter().map(String::as_str).collect();
+    if git && !columns.contains(&"git") {
+        columns.push("git");
+    }
+    // show what the listing is sorted by
+    if let Some(key @ ("accessed" | "size")) = sort {
+        if !columns.contains(&key) {
+            columns.push(key);
+        }
+    }
+    let git = columns.contains(&"git");
+    let sizes: HashMap<PathBuf, u64> = match columns.contains(&"size") {
+        true => {
+            let real_dirs = dirs.iter().map(|dir| Dir {
+                path: real_path(dir),
+                ..dir.clone()
+            });
+            fsutil::dir_sizes(real_dirs.collect())
+                .into_iter()
+                .map(|(dir, size)| (dir.path, size))
+                .collect()
+        }
+        false => HashMap::new(),
+    };
+    match sort {
+        // biggest first, sizes are already known for the size column
+        Some("size") => dirs.sort_by_key(|dir| Reverse(sizes.get(&real_path(dir)).copied())),
+        Some(key) => sort_dirs(store, &mut dirs, key),
+        None => {}
+    }
+    // bucket groups follow the bucket order, sorted within
+    if group_by == Some("bucket") {
+        sort_by_bucket(store, &mut dirs);
+    }
+    // month groups come oldest first with undated directories last, sorted
+    // within by `--sort` or else by date
+    if group_by == Some("month") {
+        match sort {
+            Some(_) => {
+                dirs.sort_by_key(|dir| (dir.naive_date().is_none(), group_key(dir, group_by)))
+            }
+            None => dirs.sort_by_key(|dir| (dir.naive_date().is_none(), dir.date_key())),
+        }
+    }
+    let needs_meta = columns
+        .iter()
+        .any(|c| ["tags", "type", "description"].contains(c));
+    // groups keep the order in which their first directory was listed
+    let mut groups: Vec<(String, Table)> = vec![];
+    let mut entries = vec![];
+    for dir in dirs {
+        let git_status = match git {
+            true => git::status(&dir.path),
+            false => None,
+        };
+        let status = git_status.as_ref().map(|s| s.to_string());
+        let size = sizes.get(&real_path(&dir)).cloned().unwrap_or(0);
+        let target = targets.get(&dir.path);
+        let path = match (symlinks, target) {
+            (Symlinks::Follow, Some(target)) => target.display().to_string(),
+            _ => dir.path_str().to_string(),
+        };
+        let meta = match needs_meta {
+            true => store.dir_meta(&dir.path).unwrap_or_default(),
+            false => Default::default(),
+        };
+        if format.is_json() {
+            let mut entry = json!({
+                "bucket": dir.bucket,
+                "date": dir.date,
+                "name": dir.name,
+                "path": path,
+            });
+            if let (Symlinks::Mark, Some(target)) = (symlinks, target) {
+                entry["link"] = json!(target);
+            }
+            for column in columns.iter() {
+                let value = match *column {
+                    "size" => json!(size),
+                    "tags" => json!(meta.tags),
+                    "type" => json!(meta.project_type),
+                    "description" => json!(meta.description),
+                    "git" => json!(status),
+                    "accessed" => {
+                        json!(store.last_accessed(&dir.path).map(|t| t.to_rfc3339()))
+                    }
+                    _ => continue,
+                };
+                entry[*column] = value;
+            }
+            entries.push(entry);
+            continue;
+        }
+        if format == Format::Porcelain {
+            output::print_porcelain(
+                "dir",
+                &[
+                    &dir.bucket,
+                    &dir.date,
+                    &dir.name,
+                    &path,
+                    status.as_deref().unwrap_or(""),
+                ],
+            );
+            continue;
+        }
+        let row = columns
+            .iter()
+            .map(|column| match *column {
+                "bucket" => cell!(dir.bucket),
+                "date" => cell!(dates::display(&dir.date)),
+                "name" => cell!(dir.name),
+                "size" => cell!(r->fsutil::human_size(size)),
+                "tags" => cell!(meta.tags.iter().cloned().collect::<Vec<_>>().join(",")),
+                "type" => cell!(meta.project_type.as_deref().unwrap_or_default()),
+                "description" => cell!(meta.description.as_deref().unwrap_or_default()),
+                "git" => match &git_status {
+                    // markdown is plain text
+                    Some(s) if format == Format::Markdown => cell!(s),
+                    Some(s) if s.has_unsaved_work() => {
+                        cell!(output::paint(&s.to_string(), Color::Red))
+                    }
+                    Some(s) => cell!(output::paint(&s.to_string(), Color::Green)),
+                    None => cell!(""),
+                },
+                "accessed" => cell!(format_accessed(store, &dir)),
+                _ => match (symlinks, target) {
+                    (Symlinks::Mark, Some(target)) => {
+                        cell!(format!("{} -> {}", path, target.display()))
+                    }
+                    _ => cell!(path),
+                },
+            })
+            .collect();
+        let key = group_key(&dir, group_by);
+        let index = match groups.iter().position(|(k, _)| *k == key) {
+            Some(index) => index,
+            None => {
+                groups.push((key, simple_table()));
+                groups.len() - 1
+            }
+        };
+        groups[index].1.add_row(row);
+    }
+    if format.is_json() {
+        output::print_json_records(entries, format);
+        return;
+    }
+    for (i, (key, table)) in groups.iter().enumerate() {
+        if group_by.is_some() {
+            if i > 0 {
+                println!();
+            }
+            match format {
+                Format::Markdown => println!("### {} ({})\n", key, table.len()),
+                _ => println!("{} ({})", key, table.len()),
+            }
+        }
+        match format {
+            Format::Markdown => output::print_markdown(&columns, table),
+            _ => table.printstd(),
+        }
+    }
+}
+
+/// Columns `zz ls --columns` can print
+const COLUMNS: &[&str] = &[
+    "bucket",
+    "date",
+    "name",
+    "path",
+    "size",
+    "tags",
+    "type",
+    "description",
+    "git",
+    "accessed",
+];
+
+// the columns `zz ls` prints, from `--columns`, `ls.columns` in the config
+// or the default layout
+fn list_columns(arg: Option<&str>) -> Result<Vec<String>, Box<dyn error::Error + 'static>> {
+    let columns: Vec<String> = match arg {
+        Some(arg) => arg.split(',').map(|c| c.trim().to_string()).collect(),
+        None => Config::load()?.ls.columns,
+    };
+    if columns.is_empty() {
+        return Ok(["bucket", "date", "name", "path"]
+            .iter()
+            .map(|c| c.to_string())
+            .collect());
+    }
+    if let Some(unknown) = columns.iter().find(|c| !COLUMNS.contains(&c.as_str())) {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "Unknown column '{}', expected one of {}",
+            unknown,
+            COLUMNS.join(", ")
+        ))));
+    }
+    Ok(columns)
+}
+
+/// A listing saved under `[views]` in the config
+///
+/// A view is a filter expression, see `Filter`, mixed with `sort:KEY`,
+/// `group:KEY` and `columns:A,B,C` words, e.g. `age>60d sort:size`.
+#[derive(Default)]
+struct SavedView {
+    filter: Option<String>,
+    sort: Option<String>,
+    group_by: Option<String>,
+    columns: Option<String>,
+}
+
+impl SavedView {
+    fn load(name: &str) -> Result<SavedView, Box<dyn error::Error + 'static>> {
+        let config = Config::load()?;
+        let text = config.views.get(name).ok_or_else(|| {
+            ZugzugError::new(&format!("No view '{}' in [views] of the config", name))
+        })?;
+        Ok(SavedView::parse(text)?)
+    }
+
+    fn parse(text: &str) -> Result<SavedView, ZugzugError> {
+        let mut view = SavedView::default();
+        let mut filter = vec![];
+        for word in text.split_whitespace() {
+            let setting = match word.split_once(':') {
+                Some(("sort", key)) => (&mut view.sort, key, SORT_KEYS),
+                Some(("group", key)) => (&mut view.group_by, key, GROUP_KEYS),
+                Some(("columns", columns)) => {
+                    view.columns = Some(columns.to_string());
+                    continue;
+                }
+                _ => {
+                    filter.push(word);
+                    continue;
+                }
+            };
+            let (value, key, keys) = setting;
+            if !keys.contains(&key) {
+                return Err(ZugzugError::new(&format!(
+                    "Unknown key '{}' in view, expected one of {}",
+                    key,
+                    keys.join(", ")
+                )));
+            }
+            *value = Some(key.to_string());
+        }
+        if !filter.is_empty() {
+            view.filter = Some(filter.join(" "));
+        }
+        Ok(view)
+    }
+
+    /// The view's expression, and-ed with the one given on the command line
+    fn filter(&self, extra: Option<&str>) -> Result<Option<Filter>, ZugzugError> {
+        let expr = match (self.filter.as_deref(), extra) {
+            (Some(own), Some(extra)) => Some(format!("({}) and ({})", own, extra)),
+            (own, extra) => own.or(extra).map(str::to_string),
+        };
+        expr.map(|expr| expr.parse()).transpose()
+    }
+}
+
+/// Keys `zz ls --group-by` accepts
+const GROUP_KEYS: &[&str] = &["bucket", "month"];
+
+// heading of the group a directory is listed under, the month as YYYY-MM
+fn group_key(dir: &Dir, group_by: Option<&str>) -> String {
+    match group_by {
+        Some("bucket") => dir.bucket.clone(),
+        Some("month") => match dir.naive_date() {
+            Some(date) => date.format("%Y-%m").to_string(),
+            None => "undated".to_string(),
+        },
+        _ => String::new(),
+    }
+}
+
+/// Keys `zz ls --sort` accepts
+const SORT_KEYS: &[&str] = &["date", "name", "accessed", "frecency", "size", "bucket"];
+
+// sort directories newest first by date, by name, most recently accessed
+// first, by frecency, with directories never opened through zz last, or by
+// bucket order and then date
+fn sort_dirs(store: &Store, dirs: &mut [Dir], key: &str) {
+    match key {
+        "bucket" => {
+            sort_dirs(store, dirs, "date");
+            sort_by_bucket(store, dirs);
+        }
+        "name" => dirs.sort_by(|a, b| a.name.cmp(&b.name).then(a.date_key().cmp(&b.date_key()))),
+        "accessed" => dirs.sort_by_key(|dir| Reverse(store.last_accessed(&dir.path))),
+        "frecency" => {
+            sort_dirs(store, dirs, "date");
+            dirs.sort_by(|a, b| {
+                store
+                    .frecency(&b.path)
+                    .partial_cmp(&store.frecency(&a.path))
+                    .unwrap_or(Ordering::Equal)
+            });
+        }
+        _ => dirs.sort_by_key(|dir| Reverse((dir.date_key(), dir.full_name()))),
+    }
+}
+
+// stable sort of directories by the order of their buckets
+fn sort_by_bucket(store: &Store, dirs: &mut [Dir]) {
+    let order: Vec<&str> = store.buckets_iter().map(|b| b.name.as_str()).collect();
+    dirs.sort_by_key(|dir| order.iter().position(|name| *name == dir.bucket));
+}
+
+fn format_accessed(store: &Store, dir: &Dir) -> String {
+    store
+        .last_accessed(&dir.path)
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_default()
+}
+
+/// List the most recent directories across buckets
+///
+/// Directories are ranked by frecency, how often and how recently they
+/// were opened through zz (e.g. with `zz exec` or an editor), then by date.
+/// With `accessed` only the last access counts, with `by_date` only the
+/// date.
+///
+/// # Example
+///
+/// ```
+/// zz recent
+/// zz recent --accessed -n 5
+/// zz recent --by-date
+/// ```
+fn handle_recent(
+    store: &Store,
+    bucket: Option<&str>,
+    count: usize,
+    accessed: bool,
+    by_date: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let mut dirs = vec![];
+    for bucket in store.select_buckets(bucket)? {
+        dirs.extend(bucket.dirs()?);
+    }
+    if accessed {
+        dirs.retain(|dir| store.last_accessed(&dir.path).is_some());
+        sort_dirs(store, &mut dirs, "accessed");
+    } else if by_date {
+        sort_dirs(store, &mut dirs, "date");
+    } else {
+        sort_dirs(store, &mut dirs, "frecency");
+    }
+    let mut table = simple_table();
+    for dir in dirs.iter().take(count) {
+        let mut row = row![
+            dir.bucket,
+            dates::display(&dir.date),
+            dir.name,
+            dir.path_str()
+        ];
+        if accessed {
+            row.add_cell(cell!(format_accessed(store, dir)));
+        }
+        table.add_row(row);
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// List the directories that have gone untouched the longest
+///
+/// A directory counts as touched on its date and whenever anything in it
+/// was modified, so an old directory still being worked on isn't stale.
+/// The inverse of `zz recent`, meant as a list of candidates to archive.
+///
+/// # Example
+///
+/// ```
+/// zz stale
+/// zz stale -n 20 -b my_bucket
+/// ```
+fn handle_stale(
+    store: &Store,
+    bucket: Option<&str>,
+    count: usize,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let mut dirs = vec![];
+    for bucket in store.select_buckets(bucket)? {
+        match bucket.dirs() {
+            Ok(bucket_dirs) => dirs.extend(bucket_dirs),
+            Err(err) => warn!("Unable to read dir: {}", err),
+        }
+    }
+    let mut touched: Vec<(Dir, NaiveDate)> = fsutil::dirs_modified(dirs)
+        .into_iter()
+        .map(|(dir, modified)| {
+            let modified = DateTime::<Local>::from(modified).date_naive();
+            let last = dir.naive_date().map_or(modified, |date| date.max(modified));
+            (dir, last)
+        })
+        .collect();
+    touched.sort_by(|(a, a_last), (b, b_last)| {
+        a_last
+            .cmp(b_last)
+            .then_with(|| a.full_name().cmp(&b.full_name()))
+    });
+    touched.truncate(count);
+    let last_touched: HashMap<PathBuf, NaiveDate> = touched
+        .iter()
+        .map(|(dir, last)| (dir.path.clone(), *last))
+        .collect();
+    let mut sized = fsutil::dir_sizes(touched.into_iter().map(|(dir, _)| dir).collect());
+    sized.sort_by_key(|(dir, _)| (last_touched.get(&dir.path).copied(), dir.full_name()));
+
+    let mut table = simple_table();
+    for (dir, size) in sized {
+        let tags = store
+            .dir_meta(&dir.path)
+            .map(|m| m.tags)
+            .unwrap_or_default();
+        table.add_row(row![
+            last_touched[&dir.path].format("%Y-%m-%d"),
+            r->fsutil::human_size(size),
+            dir.bucket,
+            dates::display(&dir.date),
+            dir.name,
+            tags.into_iter().collect::<Vec<_>>().join(","),
+            dir.path_str()
+        ]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// Find directories by fuzzy name, putting the ones in use on top
+///
+/// Matches are ranked by how well they match, weighted by their frecency.
+/// With `first`, only the path of the best match is printed, for `cd`.
+///
+/// # Example
+///
+/// ```
+/// zz find api
+/// cd "$(zz find --first api)"
+/// ```
+fn handle_find(
+    store: &mut Store,
+    query: &str,
+    count: usize,
+    first: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let mut ranked: Vec<(Dir, f64)> = store
+        .fuzzy_match_dirs(query)
+        .into_iter()
+        .filter(|(_, score)| *score >= fuzzy::MINIMUM_SCORE)
+        .map(|(dir, score)| {
+            let rank = score * (1.0 + store.frecency(&dir.path).ln_1p());
+            (dir, rank)
+        })
+        .collect();
+    ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+    if first {
+        let (dir, _) = ranked
+            .into_iter()
+            .next()
+            .ok_or_else(|| ZugzugError::new(&format!("No directory matches '{}'", query)))?;
+        store.record_access(&dir.path)?;
+        println!("{}", dir.path_str());
+        return Ok(());
+    }
+    let mut table = simple_table();
+    for (dir, _) in ranked.iter().take(count) {
+        table.add_row(row![
+            dir.bucket,
+            dates::display(&dir.date),
+            dir.name,
+            dir.path_str()
+        ]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// Summarize what happened in the buckets during a week or month
+///
+/// Shows per bucket how many directories were created and removed and how
+/// much the bucket grew, the tags of directories created or opened in the
+/// period, and the directories created, e.g. to reconstruct a timesheet.
+/// With `previous`, the last full week or month is reported on.
+///
+/// # Example
+///
+/// ```
+/// zz report
+/// zz report --period month --previous
+/// ```
+fn handle_report(
+    store: &Store,
+    period: report::Period,
+    previous: bool,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let (from, to) = report::range(period, previous, Local::now().date_naive());
+    let in_period = |date: Option<NaiveDate>| date.is_some_and(|d| d >= from && d <= to);
+
+    let mut created: Vec<(Dir, u64)> = fsutil::dir_sizes(
+        store
+            .all_dirs()
+            .into_iter()
+            .filter(|dir| in_period(dir.naive_date()))
+            .collect(),
+    );
+    created.sort_by(|a, b| (a.0.date_key(), &a.0.bucket).cmp(&(b.0.date_key(), &b.0.bucket)));
+    let removed: Vec<&RemovedDir> = store
+        .removed()
+        .iter()
+        .filter(|r| {
+            in_period(
+                DateTime::parse_from_rfc3339(&r.removed)
+                    .ok()
+                    .map(|t| t.with_timezone(&Local).date_naive()),
+            )
+        })
+        .collect();
+
+    // created, removed, bytes created, bytes removed
+    let mut buckets: BTreeMap<&str, (usize, usize, u64, u64)> = BTreeMap::new();
+    for (dir, size) in created.iter() {
+        let entry = buckets.entry(&dir.bucket).or_default();
+        entry.0 += 1;
+        entry.2 += size;
+    }
+    for r in removed.iter() {
+        let entry = buckets.entry(&r.bucket).or_default();
+        entry.1 += 1;
+        entry.3 += r.size;
+    }
+
+    let mut tags: BTreeMap<String, usize> = BTreeMap::new();
+    let opened = store.all_dirs().into_iter().filter(|dir| {
+        in_period(
+            store
+                .last_accessed(&dir.path)
+                .map(|t| t.with_timezone(&Local).date_naive()),
+        ) && !in_period(dir.naive_date())
+    });
+    for dir in created.iter().map(|(dir, _)| dir.clone()).chain(opened) {
+        for tag in store
+            .dir_meta(&dir.path)
+            .map(|m| m.tags)
+            .unwrap_or_default()
+        {
+            *tags.entry(tag).or_insert(0) += 1;
+        }
+    }
+    let mut tags: Vec<(String, usize)> = tags.into_iter().collect();
+    tags.sort_by_key(|(tag, count)| (Reverse(*count), tag.clone()));
+
+    if format.is_json() {
+        output::print_json(&json!({
+            "from": from.to_string(),
+            "to": to.to_string(),
+            "buckets": buckets.iter().map(|(name, (c, r, cb, rb))| json!({
+                "name": name,
+                "created": c,
+                "removed": r,
+                "growth": *cb as i64 - *rb as i64,
+            })).collect::<Vec<_>>(),
+            "tags": tags.iter().map(|(tag, count)| json!({"tag": tag, "dirs": count})).collect::<Vec<_>>(),
+            "created": created.iter().map(|(dir, size)| json!({
+                "bucket": dir.bucket,
+                "date": dir.date,
+                "name": dir.name,
+                "size": size,
+            })).collect::<Vec<_>>(),
+        }));
+        return Ok(());
+    }
+
+    match format {
+        Format::Markdown => println!("## Report for {} to {}\n", from, to),
+        _ => println!("Report for {} to {}", from, to),
+    }
+    let mut table = simple_table();
+    for (name, (c, r, cb, rb)) in buckets.iter() {
+        table.add_row(row![name, r->c, r->r, r->signed_size(*cb as i64 - *rb as i64)]);
+    }
+    print_titled(table, &["bucket", "created", "removed", "growth"], format);
+    if !tags.is_empty() {
+        println!();
+        let mut table = simple_table();
+        for (tag, count) in tags.iter().take(10) {
+            table.add_row(row![tag, r->count]);
+        }
+        print_titled(table, &["tag", "dirs"], format);
+    }
+    if !created.is_empty() {
+        println!();
+        let mut table = simple_table();
+        for (dir, size) in created.iter() {
+            table.add_row(
+                row![dates::display(&dir.date), dir.bucket, dir.name, r->fsutil::human_size(*size)],
+            );
+        }
+        print_titled(table, &["date", "bucket", "name", "size"], format);
+    }
+    Ok(())
+}
+
+// print a table under column titles, as markdown with `--format markdown`
+fn print_titled(mut table: Table, titles: &[&str], format: Format) {
+    if format == Format::Markdown {
+        output::print_markdown(titles, &table);
+    } else {
+        table.set_titles(titles.iter().map(|t| cell!(t)).collect());
+        table.printstd();
+    }
+}
+
+// a size difference, with a sign when it shrank
+fn signed_size(bytes: i64) -> String {
+    if bytes < 0 {
+        format!("-{}", fsutil::human_size(bytes.unsigned_abs()))
+    } else {
+        fsutil::human_size(bytes as u64)
+    }
+}
+
+// print a calendar per bucket marking the days that have directories
+fn print_journal(dirs: &[Dir]) {
+    let mut days: BTreeMap<&str, BTreeSet<NaiveDate>> = BTreeMap::new();
+    for dir in dirs {
+        if let Some(date) = dir.naive_date() {
+            days.entry(&dir.bucket).or_default().insert(date);
+        }
+    }
+    for (bucket, dates) in days {
+        println!("{}\n", bucket);
+        print!("{}", calendar::render(&dates));
+    }
+}
+
+/// Report the largest directories across buckets
+///
+/// Sizes are computed in parallel, with a progress bar on stderr while the
+/// buckets are being walked.
+///
+/// # Example
+///
+/// ```
+/// # Top 10 directories
+/// zz biggest
+///
+/// # Top 3 directories in a specific bucket
+/// zz biggest -n 3 -b my_bucket
+/// ```
+fn handle_biggest(
+    store: &Store,
+    count: usize,
+    filter_bucket_name: Option<&str>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let mut dirs = vec![];
+    for bucket in store.select_buckets(filter_bucket_name)? {
+        match bucket.dirs() {
+            Ok(bucket_dirs) => dirs.extend(bucket_dirs),
+            Err(err) => warn!("Unable to read dir: {}", err),
+        }
+    }
+
+    let mut sized = fsutil::dir_sizes(dirs);
+    sized.sort_by_key(|&(_, size)| Reverse(size));
+
+    let mut table = simple_table();
+    for (dir, size) in sized.into_iter().take(count) {
+        table.add_row(row![
+            r->fsutil::human_size(size),
+            dir.bucket,
+            dates::display(&dir.date),
+            dir.name,
+            dir.path_str()
+        ]);
+    }
+    table.printstd();
+    Ok(())
+}
+
+/// Summarize the differences between two tracked directories
+///
+/// Files are reported as added (`+`), removed (`-`) or changed (`~`) going
+/// from the first directory to the second.
+///
+/// # Example
+///
+/// ```
+/// zz diff 20190301_run 20190302_run
+///
+/// # Include unified diffs of changed files
+/// zz diff --content 20190301_run 20190302_run
+/// ```
+fn handle_diff(
+    store: &Store,
+    a: &str,
+    b: &str,
+    content: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let a = resolve_dir(store, a)?;
+    let b = resolve_dir(store, b)?;
+    let diff = DirDiff::compute(&a.path, &b.path)?;
+    if diff.is_empty() {
+        println!("No differences");
+        return Ok(());
+    }
+    for file in diff.added.iter() {
+        println!("+ {}", file.display());
+    }
+    for file in diff.removed.iter() {
+        println!("- {}", file.display());
+    }
+    for file in diff.changed.iter() {
+        println!("~ {}", file.display());
+    }
+    if content {
+        for file in diff.changed.iter() {
+            diff::print_content_diff(&a.path, &b.path, file)?;
+        }
+    }
+    Ok(())
+}
+
+/// List tracked directories that contain no files
+///
+/// With `delete`, the empty directories are removed after confirmation.
+///
+/// # Example
+///
+/// ```
+/// # List empty directories
+/// zz empty
+///
+/// # Remove empty directories in a specific bucket
+/// zz empty -b my_bucket --delete
+/// ```
+fn handle_empty(
+    store: &mut Store,
+    filter_bucket_name: Option<&str>,
+    delete: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let mut table = simple_table();
+    let mut empty = vec![];
+    for bucket in store.select_buckets(filter_bucket_name)? {
+        let dirs = match bucket.dirs() {
+            Ok(dirs) => dirs,
+            Err(err) => {
+                warn!("Unable to read dir: {}", err);
+                continue;
+            }
+        };
+        for dir in dirs {
+            match fsutil::is_empty_tree(&dir.path) {
+                Ok(true) => {
+                    table.add_row(row![
+                        dir.bucket,
+                        dates::display(&dir.date),
+                        dir.name,
+                        dir.path_str()
+                    ]);
+                    empty.push(dir);
+                }
+                Ok(false) => {}
+                Err(err) => warn!("Unable to read {}: {}", dir.path_str(), err),
+            }
+        }
+    }
+    table.printstd();
+
+    if !delete || empty.is_empty() {
+        return Ok(());
+    }
+    readonly::check()?;
+    if !prompt::confirm(&t!(
+        "Remove {count} empty directories?",
+        count = empty.len()
+    ))? {
+        return Ok(());
+    }
+    remove_dirs(store, empty, None)
+}
+
+/// Find identical files across directories and hard-link them
+///
+/// Files of at least `min_size` bytes are compared by size, hash and then
+/// byte by byte. Only what would be linked and the space it saves is shown
+/// unless `apply` is given. Linked files share their contents, so changing
+/// one in place changes them all.
+///
+/// # Example
+///
+/// ```
+/// zz dedup -b scratch
+/// zz dedup -b scratch --min-size 10M --apply
+/// ```
+fn handle_dedup(
+    store: &Store,
+    selection: &DirSelection,
+    min_size: &str,
+    apply: bool,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let min_size = fsutil::parse_size(min_size)
+        .ok_or_else(|| ZugzugError::new(&format!("'{}' is not a size, e.g. 1M", min_size)))?;
+    let duplicates = dedup::find(&selection.select(store)?, min_size)?;
+    let saved: u64 = duplicates.iter().map(|d| d.saved).sum();
+    if format.is_json() {
+        let found: Vec<_> = duplicates
+            .iter()
+            .map(
+                |d| json!({ "size": d.size, "keep": d.keep, "copies": d.copies, "saved": d.saved }),
+            )
+            .collect();
+        output::print_json(&json!({ "duplicates": found, "saved": saved }));
+        if !apply {
+            return Ok(());
+        }
+    } else {
+        let mut table = simple_table();
+        for duplicate in &duplicates {
+            table.add_row(row![
+                r->fsutil::human_size(duplicate.saved),
+                r->format!("{}x", duplicate.copies.len() + 1),
+                duplicate.keep.display()
+            ]);
+        }
+        table.printstd();
+    }
+    if duplicates.is_empty() {
+        println!("No duplicate files");
+        return Ok(());
+    }
+    if !apply {
+        println!(
+            "Linking would save {}, use --apply to link",
+            fsutil::human_size(saved)
+        );
+        return Ok(());
+    }
+    readonly::check()?;
+    if !prompt::confirm(&t!(
+        "Replace {count} copies by hard links?",
+        count = duplicates.iter().map(|d| d.copies.len()).sum::<usize>()
+    ))? {
+        return Ok(());
+    }
+    let mut freed = 0;
+    for duplicate in &duplicates {
+        match dedup::link(duplicate) {
+            Ok(()) => freed += duplicate.saved,
+            Err(e) => warn!(
+                "Unable to link copies of {}: {}",
+                duplicate.keep.display(),
+                e
+            ),
+        }
+    }
+    println!("Saved {}", fsutil::human_size(freed));
+    Ok(())
+}
+
+/// Report directories that are likely copies of each other
+///
+/// Directories are compared by `fsutil::fingerprint`, so reading them is
+/// cheap, and only those with the same name but another date count unless
+/// `any_name` is given. The newest of each group is kept, the others are
+/// suggested for removal, best checked with `zz diff` first. Directories
+/// without files are left to `zz empty`.
+///
+/// # Example
+///
+/// ```
+/// zz dupes
+/// zz dupes -b scratch --any-name
+/// ```
+fn handle_dupes(
+    store: &Store,
+    filter_bucket_name: Option<&str>,
+    any_name: bool,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let mut dirs = vec![];
+    for bucket in store.select_buckets(filter_bucket_name)? {
+        match bucket.dirs() {
+            Ok(bucket_dirs) => dirs.extend(bucket_dirs),
+            Err(err) => warn!("Unable to read dir: {}", err),
+        }
+    }
+    let mut groups: BTreeMap<(String, u64), Vec<Dir>> = BTreeMap::new();
+    for (dir, (fingerprint, files)) in fsutil::dir_fingerprints(dirs) {
+        if files == 0 {
+            continue;
+        }
+        let name = match any_name {
+            true => String::new(),
+            false => dir.name.clone(),
+        };
+        groups.entry((name, fingerprint)).or_default().push(dir);
+    }
+    let mut groups: Vec<Vec<Dir>> = groups.into_values().filter(|g| g.len() > 1).collect();
+    for group in groups.iter_mut() {
+        group.sort_by_key(|dir| Reverse((dir.date_key(), dir.full_name())));
+    }
+
+    if format.is_json() {
+        let found: Vec<_> = groups
+            .iter()
+            .map(|group| {
+                let duplicates: Vec<&str> = group[1..].iter().map(|d| d.path_str()).collect();
+                json!({ "keep": group[0].path_str(), "duplicates": duplicates })
+            })
+            .collect();
+        output::print_json(&json!(found));
+        return Ok(());
+    }
+    if groups.is_empty() {
+        println!("No duplicate directories");
+        return Ok(());
+    }
+    let mut table = simple_table();
+    let mut candidates = vec![];
+    for group in &groups {
+        for (i, dir) in group.iter().enumerate() {
+            let status = match i {
+                0 => "keep",
+                _ => "duplicate",
+            };
+            table.add_row(row![
+                status,
+                dir.bucket,
+                dates::display(&dir.date),
+                dir.name,
+                dir.path_str()
+            ]);
+            if i > 0 {
+                candidates.push(dir.full_name());
+            }
+        }
+    }
+    table.printstd();
+    println!(
+        "{} likely duplicates, check with zz diff and remove with: zz rm {}",
+        candidates.len(),
+        candidates.join(" ")
+    );
+    Ok(())
+}
+
+/// Remove directories only if they contain no files, like `rmdir`
+///
+/// Names must match exactly, so scripts never remove the wrong directory
+/// or wait on a question, unless `confirm = "always"`. Empty
+/// subdirectories don't count as contents. Directories that aren't empty
+/// are reported and kept.
+///
+/// # Example
+///
+/// ```
+/// zz rmdir 20190301_run 20190302_run
+/// ```
+fn handle_rmdir(store: &mut Store, names: &[&str]) -> Result<(), Box<dyn error::Error + 'static>> {
+    readonly::check()?;
+    let mut empty = vec![];
+    let mut kept = 0;
+    for name in names {
+        let dir = store
+            .match_dir(name)?
+            .ok_or_else(|| ZugzugError::new(&format!("No directory named '{}'", name)))?;
+        if fsutil::is_empty_tree(&dir.path)? {
+            empty.push(dir);
+        } else {
+            println!("Keeping {}, it is not empty", dir.path_str());
+            kept += 1;
+        }
+    }
+    if !empty.is_empty()
+        && !prompt::confirm_change(&t!(
+            "Remove {count} empty directories?",
+            count = empty.len()
+        ))?
+    {
+        return Ok(());
+    }
+    remove_dirs(store, empty, None)?;
+    match kept {
+        0 => Ok(()),
+        _ => Err(Box::new(ZugzugError::new(&format!(
+            "{} of {} directories were not empty",
+            kept,
+            names.len()
+        )))),
+    }
+}
+
+/// Delete directories from disk, announcing each removal to the webhooks
+///
+/// With an archive format, each directory is packed into the archive
+/// location and recorded in the store first, and kept when that fails. The
+/// store forgets the metadata and access history of removed directories,
+/// after their tags were logged with the removal.
+fn remove_dirs(
+    store: &mut Store,
+    dirs: Vec<Dir>,
+    archive_format: Option<ArchiveFormat>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let config = Config::load()?;
+    let archive_root = config.archive_dir()?;
+    for dir in dirs {
+        // read first, tags may live in the directory itself
+        let tags = store
+            .dir_meta(&dir.path)
+            .map(|m| m.tags)
+            .unwrap_or_default();
+        if let Some(format) = archive_format {
+            match archive::archive_dir(&dir, &archive_root, format) {
+                Ok(archived) => {
+                    println!("Archived {} to {}", dir.full_name(), archived.archive);
+                    store.add_archived(archived)?;
+                }
+                Err(err) => {
+                    println!("Keeping {}, unable to archive: {}", dir.path_str(), err);
+                    continue;
+                }
+            }
+        }
+        let size = fsutil::dir_size(&dir.path).unwrap_or(0);
+        match fs::remove_dir_all(&dir.path) {
+            Ok(()) => {
+                webhook::notify(&config.webhooks, Event::Removed, &dir.bucket, &dir.path);
+                store.add_removed(RemovedDir {
+                    bucket: dir.bucket.clone(),
+                    name: dir.full_name(),
+                    size,
+                    removed: Local::now().to_rfc3339(),
+                    tags,
+                })?;
+                store.forget_dir_records(&dir.path)?;
+            }
+            Err(err) => println!("Unable to remove {}: {}", dir.path_str(), err),
+        }
+    }
+    Ok(())
+}
+
+/// Leave out git repositories with uncommitted or unpushed work
+fn skip_unsaved_work(dirs: Vec<Dir>) -> Vec<Dir> {
+    dirs.into_iter()
+        .filter(|dir| match git::status(&dir.path) {
+            Some(status) if status.has_unsaved_work() => {
+                println!(
+                    "Skipping {}, it has unsaved git work ({}), use --force to remove it",
+                    dir.path_str(),
+                    status
+                );
+                false
+            }
+            _ => true,
+        })
+        .collect()
+}
+
+/// How `zz prune` and `zz rm` remove directories
+struct Removal<'a> {
+    archive_first: bool,
+    archive_format: Option<&'a str>,
+    /// Remove git repositories with unsaved work too
+    force: bool,
+}
+
+impl<'a> Removal<'a> {
+    fn from_matches(matches: &'a ArgMatches) -> Removal<'a> {
+        Removal {
+            archive_first: matches.is_present("archive-first"),
+            archive_format: matches.value_of("archive-format"),
+            force: matches.is_present("force"),
+        }
+    }
+}
+
+/// Archive format to use before removing directories, if any
+///
+/// Directories are archived with `--archive-first` or when the delete policy
+/// says so, using `--archive-format`, `archive.format` or gzip.
+fn archive_before_delete(
+    config: &Config,
+    removal: &Removal,
+) -> Result<Option<ArchiveFormat>, Box<dyn error::Error + 'static>> {
+    if !removal.archive_first && config.prune.delete_policy != DeletePolicy::Archive {
+        return Ok(None);
+    }
+    let format = removal
+        .archive_format
+        .or(config.archive.format.as_deref())
+        .unwrap_or("gzip");
+    Ok(Some(format.parse()?))
+}
+
+/// Remove directories past the retention age
+///
+/// The age comes from `--older-than` or `prune.older_than` in the config.
+/// Directories past their own TTL, like scratch directories, are pruned
+/// regardless. Deprecated directories are only pruned when old enough too,
+/// and then listed and removed first. Directories tagged with any of
+/// `prune.exclude_tags` or a `--keep-tag` are left alone. With `--archive-first` or `prune.delete_policy =
+/// "archive"` they are packed into the archive location before removal.
+/// Git repositories with uncommitted or unpushed work are skipped unless
+/// `--force` is given.
+/// With `--where`, the directories matching the expression are pruned
+/// instead, still limited by `--older-than` when it is given.
+/// Matching directories are listed per bucket with their size and age and
+/// the total space that would be reclaimed. With `--dry-run` nothing else
+/// happens, otherwise they are removed after confirmation.
+///
+/// # Example
+///
+/// ```
+/// # Review what a 30 day retention would remove
+/// zz prune --older-than 30 --dry-run
+///
+/// # Apply the configured retention to a bucket, sparing demo directories
+/// zz prune -b my_bucket --keep-tag demo
+///
+/// # Large directories nobody opened in two months
+/// zz prune --where 'size>1G and accessed>60d' --dry-run
+/// ```
+fn handle_prune(
+    store: &mut Store,
+    filter_bucket_name: Option<&str>,
+    older_than: Option<i64>,
+    keep_tags: &[&str],
+    filter: Option<&Filter>,
+    removal: &Removal,
+    dry_run: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let config = Config::load()?;
+    // an expression replaces the configured retention age and TTLs
+    let days = match filter {
+        Some(_) => older_than,
+        None => older_than.or(config.prune.older_than),
+    };
+    let archive_format = archive_before_delete(&config, removal)?;
+    let mut dirs = vec![];
+    for bucket in store.select_buckets(filter_bucket_name)? {
+        match bucket.dirs() {
+            Ok(bucket_dirs) => dirs.extend(bucket_dirs),
+            Err(err) => warn!("Unable to read dir: {}", err),
+        }
+    }
+    let mut excluded = config.prune.exclude_tags.clone();
+    excluded.extend(keep_tags.iter().map(|tag| tag.to_string()));
+    dirs.retain(|dir| {
+        let too_old = |days| dir.age_days().is_some_and(|age| age > days);
+        let selected = match filter {
+            Some(filter) => {
+                days.is_none_or(too_old) && filter.matches(&DirSubject::new(dir, store))
+            }
+            None => days.is_some_and(too_old) || store.dir_expired(dir),
+        };
+        selected && !store.dir_has_any_tag(&dir.path, &excluded)
+    });
+    if !removal.force {
+        dirs = skip_unsaved_work(dirs);
+    }
+    if dirs.is_empty() {
+        println!("Nothing to prune");
+        return Ok(());
+    }
+
+    let mut sized = fsutil::dir_sizes(dirs);
+    // deprecated directories first, they are the first to go
+    sized.sort_by_key(|(dir, _)| {
+        (
+            dir.bucket.clone(),
+            !store.dir_deprecated(&dir.path),
+            dir.date_key(),
+            dir.name.clone(),
+        )
+    });
+    let total: u64 = sized.iter().map(|&(_, size)| size).sum();
+    for group in sized.chunk_by(|(a, _), (b, _)| a.bucket == b.bucket) {
+        let mut table = simple_table();
+        for (dir, size) in group {
+            table.add_row(row![
+                r->fsutil::human_size(*size),
+                r->format!("{}d", dir.age_days().unwrap_or_default()),
+                dir.full_name(),
+                dir.path_str()
+            ]);
+        }
+        println!("{}", group[0].0.bucket);
+        table.printstd();
+    }
+    let verb = if dry_run {
+        "Would reclaim"
+    } else {
+        "Will reclaim"
+    };
+    println!(
+        "{} {} from {} directories",
+        verb,
+        fsutil::human_size(total),
+        sized.len()
+    );
+    if dry_run {
+        return Ok(());
+    }
+
+    readonly::check()?;
+    if !prompt::confirm(&t!("Remove {count} directories?", count = sized.len()))? {
+        return Ok(());
+    }
+    remove_dirs(
+        store,
+        sized.into_iter().map(|(dir, _)| dir).collect(),
+        archive_format,
+    )
+}
+
+/// Remove directories
+///
+/// Directories are either named explicitly or, with `-i`, picked from a
+/// checklist of the directories in the selected buckets, optionally limited
+/// to those older than a number of days. The whole selection is removed in
+/// a single confirmed batch, archived first and sparing git repositories
+/// with unsaved work like with `zz prune`.
+///
+/// # Example
+///
+/// ```
+/// zz rm 20190301_run
+///
+/// # Pick directories older than 30 days in a bucket to remove
+/// zz rm -i -b my_bucket --older-than 30
+/// ```
+fn handle_rm(
+    store: &mut Store,
+    names: &[&str],
+    interactive: bool,
+    filter_bucket_name: Option<&str>,
+    older_than: Option<i64>,
+    filter: Option<&Filter>,
+    removal: &Removal,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let archive_format = archive_before_delete(&Config::load()?, removal)?;
+    let mut candidates = vec![];
+    if names.is_empty() {
+        if !interactive {
+            return Err(Box::new(ZugzugError::new(
+                "Name the directories to remove, or use -i to pick them",
+            )));
+        }
+        for bucket in store.select_buckets(filter_bucket_name)? {
+            match bucket.dirs() {
+                Ok(dirs) => candidates.extend(dirs),
+                Err(err) => warn!("Unable to read dir: {}", err),
+            }
+        }
+        if let Some(days) = older_than {
+            candidates.retain(|dir| dir.age_days().is_some_and(|age| age > days));
+        }
+        if let Some(filter) = filter {
+            candidates.retain(|dir| filter.matches(&DirSubject::new(dir, store)));
+        }
+        candidates.sort_by(|a, b| {
+            (&a.bucket, a.date_key(), &a.name).cmp(&(&b.bucket, b.date_key(), &b.name))
+        });
+    } else {
+        for name in names {
+            candidates.push(resolve_dir(store, name)?);
+        }
+    }
+    if candidates.is_empty() {
+        println!("No directories to remove");
+        return Ok(());
+    }
+
+    let selected: Vec<Dir> = if interactive {
+        let items: Vec<String> = candidates
+            .iter()
+            .map(|dir| format!("{}  {}", dir.bucket, dir.full_name()))
+            .collect();
+        let picked = prompt::checklist(&items)?;
+        candidates
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| picked.contains(i))
+            .map(|(_, dir)| dir)
+            .collect()
+    } else {
+        candidates
+    };
+    let selected = if removal.force {
+        selected
+    } else {
+        skip_unsaved_work(selected)
+    };
+    if selected.is_empty() {
+        return Ok(());
+    }
+
+    readonly::check()?;
+    for dir in selected.iter() {
+        println!("{}", dir.path_str());
+    }
+    if !prompt::confirm(&t!("Remove {count} directories?", count = selected.len()))? {
+        return Ok(());
+    }
+    remove_dirs(store, selected, archive_format)
+}
+
+/// Directories picked by bucket, tag, age, name glob and `--where`
+/// expression, as `zz backup`, `zz foreach` and `zz count` do
+struct DirSelection<'a> {
+    bucket: Option<&'a str>,
+    tag: Option<&'a str>,
+    name: Option<&'a str>,
+    older_than: Option<i64>,
+    newer_than: Option<i64>,
+    filter: Option<Filter>,
+}
+
+impl<'a> DirSelection<'a> {
+    fn from_matches(
+        matches: &'a ArgMatches,
+    ) -> Result<DirSelection<'a>, Box<dyn error::Error + 'static>> {
+        Ok(DirSelection {
+            bucket: matches.value_of("bucket"),
+            tag: matches.value_of("tag"),
+            name: matches.value_of("name"),
+            older_than: days(matches, "older-than")?,
+            newer_than: days(matches, "newer-than")?,
+            filter: where_filter(matches)?,
+        })
+    }
+
+    fn select(&self, store: &Store) -> Result<Vec<Dir>, Box<dyn error::Error + 'static>> {
+        let mut dirs = vec![];
+        for bucket in store.select_buckets(self.bucket)? {
+            match bucket.dirs() {
+                Ok(bucket_dirs) => dirs.extend(bucket_dirs),
+                Err(err) => warn!("Unable to read dir: {}", err),
+            }
+        }
+        if let Some(tag) = self.tag {
+            dirs.retain(|dir| store.dir_has_any_tag(&dir.path, &[tag.to_string()]));
+        }
+        if let Some(glob) = self.name {
+            dirs.retain(|dir| pattern::glob_match(glob, &dir.name));
+        }
+        if let Some(days) = self.older_than {
+            dirs.retain(|dir| dir.age_days().is_some_and(|age| age > days));
+        }
+        if let Some(days) = self.newer_than {
+            dirs.retain(|dir| dir.age_days().is_some_and(|age| age <= days));
+        }
+        if let Some(filter) = &self.filter {
+            dirs.retain(|dir| filter.matches(&DirSubject::new(dir, store)));
+        }
+        Ok(dirs)
+    }
+}
+
+/// Print how many directories match a selection, for scripts
+///
+/// Directories are selected by bucket, tag, age, name glob and `--where`
+/// expression like with `zz foreach`.
+///
+/// # Example
+///
+/// ```
+/// if [ "$(zz count -b scratch --older-than 30)" -gt 0 ]; then
+///     zz prune -b scratch --older-than 30
+/// fi
+///
+/// zz count --name 'ticket-*' --tag open
+/// ```
+fn handle_count(
+    store: &Store,
+    selection: &DirSelection,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let count = selection.select(store)?.len();
+    if format.is_json() {
+        output::print_json(&json!({ "count": count }));
+    } else {
+        println!("{}", count);
+    }
+    Ok(())
+}
+
+/// Copy directories into a new hard-linked snapshot below the backup root
+///
+/// Directories are selected by bucket, tag, age and `--where` expression.
+/// The backup root comes from `--to` or `backup.root` in the config. Only
+/// files that changed since the previous snapshot are copied, the rest are
+/// hard-linked.
+///
+/// # Example
+///
+/// ```
+/// zz backup --to /mnt/backup/zz
+///
+/// # Back up this week's tagged work
+/// zz backup -b work --tag important --newer-than 7
+/// ```
+fn handle_backup(
+    store: &Store,
+    selection: &DirSelection,
+    to: Option<&str>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let config = Config::load()?;
+    let root = to
+        .map(String::from)
+        .or(config.backup.root)
+        .ok_or_else(|| ZugzugError::new("No backup root, use --to or set backup.root"))?;
+    let dirs = selection.select(store)?;
+    if dirs.is_empty() {
+        println!("Nothing to back up");
+        return Ok(());
+    }
+    let summary = backup::backup(&dirs, Path::new(&root))?;
+    println!(
+        "Backed up {} directories to {}: {} files copied ({}), {} linked",
+        dirs.len(),
+        summary.snapshot.display(),
+        summary.copied,
+        fsutil::human_size(summary.bytes),
+        summary.linked
+    );
+    Ok(())
+}
+
+/// Move a directory into another bucket
+///
+/// The directory keeps its name, metadata and access history. Moving onto
+/// another filesystem copies and verifies the directory before the original
+/// is removed.
+///
+/// # Example
+///
+/// ```
+/// zz mv api archive_bucket
+/// ```
+fn handle_mv(
+    store: &mut Store,
+    name: &str,
+    bucket_name: &str,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    readonly::check()?;
+    let dir = resolve_dir(store, name)?;
+    let bucket = store.resolve_bucket(bucket_name)?.clone();
+    if bucket.name == dir.bucket {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} is in bucket '{}' already",
+            dir.full_name(),
+            bucket.name
+        ))));
+    }
+    let target = bucket.mkdir_root().join(dir.full_name());
+    if target.exists() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} already exists",
+            target.display()
+        ))));
+    }
+    if !prompt::confirm_change(&t!(
+        "Move {name} to {bucket}?",
+        name = dir.full_name(),
+        bucket = bucket.name
+    ))? {
+        return Ok(());
+    }
+    fsutil::move_dir(&dir.path, &target)?;
+    store.move_dir_records(&dir.path, &target)?;
+    println!("{}", target.display());
+    Ok(())
+}
+
+/// Move the contents of one directory into another, then remove the first
+///
+/// Files only `source` has are moved, identical ones are kept once, and
+/// conflicts are skipped, overwritten or kept under a new name as
+/// `on_conflict` says. Tags and environment of `source` are added to
+/// `target`. Afterwards `source` is removed like with `zz rm`, or only
+/// deprecated with `deprecate`. A source still holding skipped files is
+/// kept.
+///
+/// # Example
+///
+/// ```
+/// # The same task, started twice
+/// zz merge 20190302_api 20190301_api
+/// zz merge 20190302_api 20190301_api --on-conflict overwrite --deprecate
+/// ```
+fn handle_merge(
+    store: &mut Store,
+    source: &str,
+    target: &str,
+    on_conflict: &str,
+    deprecate: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    readonly::check()?;
+    let on_conflict: merge::Conflict = on_conflict.parse()?;
+    let source = resolve_dir(store, source)?;
+    let target = resolve_dir(store, target)?;
+    if source.path == target.path {
+        return Err(Box::new(ZugzugError::new(
+            "Can't merge a directory into itself",
+        )));
+    }
+    if !prompt::confirm_change(&t!(
+        "Merge {source} into {target}?",
+        source = source.full_name(),
+        target = target.full_name()
+    ))? {
+        return Ok(());
+    }
+    let summary = merge::merge(&source.path, &target.path, on_conflict)?;
+    store.merge_dir_meta(&source.path, &target.path)?;
+    println!(
+        "{} moved, {} identical, {} overwritten, {} renamed, {} skipped",
+        summary.moved,
+        summary.identical,
+        summary.overwritten,
+        summary.renamed.len(),
+        summary.skipped.len()
+    );
+    for file in &summary.renamed {
+        println!("renamed {}", file.display());
+    }
+    for file in &summary.skipped {
+        println!("skipped {}", file.display());
+    }
+
+    if deprecate {
+        return store.set_dir_deprecated(&source.path, true);
+    }
+    if !summary.skipped.is_empty() {
+        println!("Kept {}, skipped files are still in it", source.full_name());
+        return Ok(());
+    }
+    if !prompt::confirm(&t!("Remove {name}?", name = source.full_name()))? {
+        return Ok(());
+    }
+    let removal = Removal {
+        archive_first: false,
+        archive_format: None,
+        force: false,
+    };
+    let archive_format = archive_before_delete(&Config::load()?, &removal)?;
+    remove_dirs(store, vec![source], archive_format)
+}
+
+/// Re-date a directory to today, keeping its name
+///
+/// Metadata and access history move along with it. With `links.latest`
+/// the bucket's `latest` link then points at it, like at a new directory.
+///
+/// # Example
+///
+/// ```
+/// # Back to working on last month's experiment
+/// zz bump experiment
+/// ```
+fn handle_bump(store: &mut Store, name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    readonly::check()?;
+    let dir = resolve_dir(store, name)?;
+    if dir.naive_date() == Some(Local::now().date_naive()) {
+        println!("{}", dir.path.display());
+        return Ok(());
+    }
+    if dir.name.is_empty() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} has no name besides its date to keep",
+            dir.full_name()
+        ))));
+    }
+    if dir.date_position == DatePosition::None {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} is in a bucket without dates",
+            dir.full_name()
+        ))));
+    }
+    let target = dir.path.with_file_name(dir.name_with_date(&store::today()));
+    if target.exists() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} already exists",
+            target.display()
+        ))));
+    }
+    if !prompt::confirm_change(&t!("Re-date {name} to today?", name = dir.full_name()))? {
+        return Ok(());
+    }
+    fs::rename(&dir.path, &target)?;
+    store.move_dir_records(&dir.path, &target)?;
+    if Config::load()?.links.latest {
+        if let Some(bucket) = store.find_bucket(&dir.bucket) {
+            if let Err(e) = link::update_latest(Path::new(&bucket.path), &target) {
+                warn!("Unable to update the latest link: {}", e);
+            }
+        }
+    }
+    println!("{}", target.display());
+    Ok(())
+}
+
+/// Take, list and restore snapshots of a directory
+///
+/// Snapshots are copies of the directory's contents kept in its
+/// `.zz-snapshots`, named by the time they were taken. Restoring one
+/// snapshots the current contents first, so it can be undone.
+///
+/// # Example
+///
+/// ```
+/// zz snapshot report
+/// zz snapshot ls report
+///
+/// # Back to the newest snapshot, or a given one
+/// zz snapshot restore report
+/// zz snapshot restore report 20250301T120000
+/// ```
+fn handle_snapshot(
+    store: &Store,
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(matches) = matches.subcommand_matches("ls") {
+        let dir = resolve_dir(store, matches.value_of("NAME").unwrap())?;
+        let mut table = simple_table();
+        for snapshot in snapshot::list(&dir.path)? {
+            table.add_row(row![
+                snapshot.name,
+                snapshot
+                    .taken
+                    .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                    .unwrap_or_default(),
+                r->fsutil::human_size(fsutil::dir_size(&snapshot.path)?)
+            ]);
+        }
+        table.printstd();
+    } else if let Some(matches) = matches.subcommand_matches("restore") {
+        readonly::check()?;
+        let dir = resolve_dir(store, matches.value_of("NAME").unwrap())?;
+        let chosen = snapshot::find(&dir.path, matches.value_of("SNAPSHOT"))?;
+        if !prompt::confirm(&t!(
+            "Replace the contents of {name} with snapshot {snapshot}?",
+            name = dir.full_name(),
+            snapshot = chosen.name
+        ))? {
+            return Ok(());
+        }
+        let current = snapshot::create(&dir.path)?;
+        println!("Saved the current contents as snapshot {}", current.name);
+        snapshot::restore(&dir.path, &chosen)?;
+        println!("Restored snapshot {}", chosen.name);
+    } else {
+        readonly::check()?;
+        let name = matches
+            .value_of("NAME")
+            .ok_or_else(|| ZugzugError::new("Name the directory to snapshot"))?;
+        let dir = resolve_dir(store, name)?;
+        println!("{}", snapshot::create(&dir.path)?.path.display());
+    }
+    Ok(())
+}
+
+/// Make a new directory holding a copy of an existing one
+///
+/// The copy is dated today and goes into the same bucket unless `--bucket`
+/// is given. It gets the original's type, environment, tags and
+/// description. On btrfs, XFS and APFS files are cloned copy-on-write, so
+/// even large directories are duplicated instantly without taking up space.
+///
+/// # Example
+///
+/// ```
+/// # Start today's experiment from yesterday's
+/// zz clone experiment
+///
+/// zz clone api api-v2 -b work
+/// ```
+fn handle_clone(
+    store: &mut Store,
+    name: &str,
+    new_name: Option<&str>,
+    bucket_name: Option<&str>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let source = resolve_dir(store, name)?;
+    let bucket = store
+        .resolve_bucket(bucket_name.unwrap_or(&source.bucket))?
+        .clone();
+    let config = Config::load()?;
+    fsutil::check_free_space(bucket.mkdir_root(), &config.mkdir)?;
+    let path = bucket.make_dir(new_name.unwrap_or(&source.name), false, Some(&config.names))?;
+    if let Err(e) = fsutil::copy_dir_contents(&source.path, &path, &[snapshot::DIR]) {
+        fs::remove_dir_all(&path)?;
+        return Err(Box::new(e));
+    }
+    store.copy_dir_meta(&source.path, &path)?;
+    println!("{}", path.display());
+    webhook::notify(&config.webhooks, Event::Created, &bucket.name, &path);
+    Ok(())
+}
+
+/// Make a new directory in a bucket
+///
+/// By default this will create a new directory prefixed with the current date
+/// in the default bucket.
+///
+/// A name of `-` reads the name from the first non-empty line of stdin, so
+/// names can come from other tools. It is cleaned up like a typed name.
+///
+/// With `--type`, the project type is recorded in the directory's metadata
+/// and a `.gitignore` plus a minimal skeleton for that type are created.
+///
+/// With `--envrc` (or `direnv.auto` in the config), a `.envrc` is generated
+/// from the configured template.
+///
+/// With `--private` the directory is only accessible by its owner (`0700`),
+/// otherwise it gets the bucket's mode if one is set.
+///
+/// Finally the bucket's post-create hooks are run inside the new directory.
+///
+/// # Errors
+///
+/// - When `-b/--bucket` is used, and the bucket doesn't exist
+/// - When `-b/--bucket` is not used and there is no default bucket
+/// - When the bucket's filesystem has less than `mkdir.min_free` available
+/// - When the name is `-` and stdin has no name on it
+///
+/// # Example
+///
+/// ```
+/// cd "$(zz mkdir experiment)"
+///
+/// gh issue view 1234 --json title -q .title | zz mkdir -
+/// ```
+fn handle_mkdir(
+    store: &mut Store,
+    name: &str,
+    bucket: Option<&str>,
+    project_type: Option<ProjectType>,
+    envrc: bool,
+    private: bool,
+    raw: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let config = Config::load()?;
+    let names = if raw { None } else { Some(&config.names) };
+    let name = match name {
+        "-" => name_from_stdin()?,
+        name => name.to_string(),
+    };
+    let name = name.as_str();
+    let selected_bucket = match bucket {
+        Some(bucket_name) => Some(store.resolve_bucket(bucket_name)?.clone()),
+        None => match store.default_bucket() {
+            Some(bucket) => Some(bucket.clone()),
+            None => choose_bucket(store)?,
+        },
+    };
+
+    if let Some(bucket) = selected_bucket {
+        fsutil::check_free_space(bucket.mkdir_root(), &config.mkdir)?;
+        match bucket.make_dir(name, private, names) {
+            Ok(path) => {
+                let dir = bucket.dir_at(&path);
+                let name = dir.name.as_str();
+                if let Some(project_type) = project_type {
+                    scaffold::scaffold(&path, name, project_type)?;
+                    store.set_project_type(&path, &project_type.to_string())?;
+                }
+                if envrc || config.direnv.auto {
+                    direnv::write_envrc(&config.direnv, &path, &bucket.name, name)?;
+                }
+                run_post_create(store, &bucket, &path)?;
+                if config.zoxide.add {
+                    zoxide::add(&path);
+                }
+                if config.links.latest {
+                    if let Err(e) = link::update_latest(Path::new(&bucket.path), &path) {
+                        warn!("Unable to update the latest link: {}", e);
+                    }
+                }
+                println!("{}", path.to_str().unwrap());
+                webhook::notify(&config.webhooks, Event::Created, &bucket.name, &path);
+            }
+            Err(e) => println!("Error: {}", e),
+        };
+    } else {
+        println!("{}", t!("No bucket to choose from"));
+    }
+    Ok(())
+}
+
+// read a directory name from the first non-empty line of stdin
+fn name_from_stdin() -> Result<String, Box<dyn error::Error + 'static>> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    match input.lines().map(str::trim).find(|line| !line.is_empty()) {
+        Some(line) => Ok(line.to_string()),
+        None => Err(Box::new(ZugzugError::new("No directory name on stdin"))),
+    }
+}
+
+// ask which bucket to use when there is no default one
+fn choose_bucket(store: &Store) -> Result<Option<Bucket>, Box<dyn error::Error + 'static>> {
+    let buckets = store.visible_buckets();
+    let items: Vec<String> = buckets
+        .iter()
+        .map(|bucket| format!("{}  {}", bucket.name, bucket.path))
+        .collect();
+    let picked = prompt::select(&t!("There is no default bucket, make it in:"), &items)?;
+    Ok(picked.map(|i| buckets[i].clone()))
+}
+
+// run the post-create hooks of a bucket in a directory just made in it
+//
+// The store is saved first, since hooks may run zz themselves.
+fn run_post_create(
+    store: &mut Store,
+    bucket: &Bucket,
+    path: &Path,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if bucket.hooks.post_create.is_empty() {
+        return Ok(());
+    }
+    let env = envvars::dir_env(store, &bucket.name, path);
+    store.save()?;
+    hooks::run(&bucket.hooks.post_create, path, &env)
+}
+
+/// Make a throwaway directory that expires after a short TTL
+///
+/// The directory is named `scratch-` plus a random suffix and made in the
+/// `scratch.bucket` from the config, or the default bucket. It is removed
+/// by `zz prune` once its TTL (`--ttl`, `scratch.ttl` or 1 day) has passed.
+///
+/// # Example
+///
+/// ```
+/// cd "$(zz scratch)"
+///
+/// # Keep it around for a week
+/// cd "$(zz scratch --ttl 7)"
+/// ```
+fn handle_scratch(
+    store: &mut Store,
+    ttl: Option<i64>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let config = Config::load()?;
+    let bucket = match &config.scratch.bucket {
+        Some(name) => store.resolve_bucket(name)?,
+        None => store
+            .default_bucket()
+            .ok_or_else(|| ZugzugError::new(&t!("No bucket to choose from")))?,
+    }
+    .clone();
+    fsutil::check_free_space(bucket.mkdir_root(), &config.mkdir)?;
+    let path = bucket.make_dir(&format!("scratch-{}", random_suffix()), false, None)?;
+    store.set_dir_ttl(&path, ttl.unwrap_or(config.scratch.ttl))?;
+    run_post_create(store, &bucket, &path)?;
+    println!("{}", path.display());
+    webhook::notify(&config.webhooks, Event::Created, &bucket.name, &path);
+    Ok(())
+}
+
+// short random-looking suffix for unnamed directories
+fn random_suffix() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.subsec_nanos())
+        .unwrap_or(0);
+    let mut seed = nanos ^ std::process::id().rotate_left(16);
+    let alphabet = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    (0..4)
+        .map(|_| {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            alphabet[(seed >> 16) as usize % alphabet.len()] as char
+        })
+        .collect()
+}
+
+/// Print the path of today's directory in a bucket
+///
+/// When there are several directories dated today, the most recently
+/// modified one wins. In a journal bucket, today's directory is named by the
+/// date only and created on first use. With `create`, a new directory of
+/// that name is made instead, like with `zz mkdir`.
+///
+/// # Example
+///
+/// ```
+/// cd "$(zz today)"
+///
+/// # Start the day
+/// cd "$(zz today --create standup)"
+/// ```
+fn handle_today(
+    store: &mut Store,
+    bucket: Option<&str>,
+    create: Option<&str>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(name) = create {
+        return handle_mkdir(store, name, bucket, None, false, false, false);
+    }
+    let bucket = match bucket {
+        Some(name) => store.resolve_bucket(name)?,
+        None => store
+            .default_bucket()
+            .ok_or_else(|| ZugzugError::new(&t!("No bucket to choose from")))?,
+    }
+    .clone();
+    if bucket.journal {
+        let (path, created) = bucket.journal_dir()?;
+        if created {
+            run_post_create(store, &bucket, &path)?;
+            webhook::notify(
+                &Config::load()?.webhooks,
+                Event::Created,
+                &bucket.name,
+                &path,
+            );
+        }
+        store.record_access(&path)?;
+        println!("{}", path.display());
+        return Ok(());
+    }
+    let today = Local::now().date_naive();
+    let newest = bucket
+        .dirs()?
+        .into_iter()
+        .filter(|dir| dir.naive_date() == Some(today))
+        .max_by_key(|dir| fs::metadata(&dir.path).and_then(|m| m.modified()).ok());
+    match newest {
+        Some(dir) => {
+            store.record_access(&dir.path)?;
+            println!("{}", dir.path_str());
+            Ok(())
+        }
+        None => Err(Box::new(ZugzugError::new(
+            "No directory for today yet, use --create NAME",
+        ))),
+    }
+}
+
+/// Print the root of the bucket the working directory is in
+///
+/// With `name`, the bucket's name is printed instead.
+///
+/// # Example
+///
+/// ```
+/// cd "$(zz root)"
+/// echo "Working in $(zz root --name)"
+/// ```
+fn handle_root(store: &Store, name: bool) -> Result<(), Box<dyn error::Error + 'static>> {
+    let (bucket, _) = store
+        .locate(&env::current_dir()?)
+        .ok_or_else(|| ZugzugError::new("Not inside a bucket"))?;
+    if name {
+        println!("{}", bucket.name);
+    } else {
+        println!("{}", bucket.path);
+    }
+    Ok(())
+}
+
+/// Print where the working directory is, for shell prompts
+///
+/// Only the store is read, no bucket is scanned, so this is quick enough to
+/// run on every prompt. Nothing is printed outside of the directories of
+/// buckets. With `starship`, the bucket, the directory name without its
+/// date and its age are printed, for a starship custom module, otherwise the
+/// bucket and the full directory name.
+///
+/// # Example
+///
+/// ```toml
+/// # ~/.config/starship.toml
+/// [custom.zz]
+/// command = "zz prompt --starship"
+/// when = true
+/// format = "[$output]($style) "
+/// ```
+fn handle_prompt(
+    store: &Store,
+    starship: bool,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let (bucket, dir) = match store.locate(&env::current_dir()?) {
+        Some((bucket, Some(dir))) => (bucket, dir),
+        _ => return Ok(()),
+    };
+    if format.is_json() {
+        output::print_json(&json!({
+            "bucket": bucket.name,
+            "name": dir.name,
+            "date": dir.date,
+            "age": dir.age_days(),
+            "path": dir.path,
+        }));
+    } else if starship {
+        match dir.age_days() {
+            Some(age) => println!("{} {} {}d", bucket.name, dir.name, age),
+            None => println!("{} {}", bucket.name, dir.name),
+        }
+    } else {
+        println!("{}/{}", bucket.name, dir.full_name());
+    }
+    Ok(())
+}
+
+/// Print the directory before or after the current one by date
+///
+/// The working directory has to be inside a dated directory of a bucket.
+/// Directories of the same day are ordered by name.
+///
+/// # Example
+///
+/// ```
+/// cd "$(zz prev)"
+/// cd "$(zz next)"
+/// ```
+fn handle_step(store: &mut Store, forward: bool) -> Result<(), Box<dyn error::Error + 'static>> {
+    let (bucket, current) = match store.locate(&env::current_dir()?) {
+        Some((bucket, Some(dir))) if dir.naive_date().is_some() => (bucket, dir),
+        _ => return Err(Box::new(ZugzugError::new("Not inside a dated directory"))),
+    };
+    let mut dirs: Vec<Dir> = bucket
+        .dirs()?
+        .into_iter()
+        .filter(|dir| dir.naive_date().is_some())
+        .collect();
+    dirs.sort_by_key(|dir| (dir.date_key(), dir.full_name()));
+    let position = dirs
+        .iter()
+        .position(|dir| dir.path == current.path)
+        .ok_or_else(|| ZugzugError::new("Not inside a dated directory"))?;
+    let step = match forward {
+        true => dirs.get(position + 1),
+        false => position.checked_sub(1).and_then(|i| dirs.get(i)),
+    };
+    match step {
+        Some(dir) => {
+            store.record_access(&dir.path)?;
+            println!("{}", dir.path_str());
+            Ok(())
+        }
+        None if forward => Err(Box::new(ZugzugError::new("This is the newest directory"))),
+        None => Err(Box::new(ZugzugError::new("This is the oldest directory"))),
+    }
+}
+
+/// Print the path of the newest directory in a bucket
+///
+/// The bucket's `latest` link (or `.latest` pointer file) wins when there
+/// is one, otherwise the directory with the newest date. `--link` points
+/// the `latest` link at it.
+///
+/// # Example
+///
+/// ```
+/// cd "$(zz latest)"
+///
+/// # Keep ~/scratch/latest pointing at the newest dir
+/// zz latest -b scratch --link
+/// ```
+fn handle_latest(
+    store: &Store,
+    bucket: Option<&str>,
+    update: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let bucket = match bucket {
+        Some(name) => store.resolve_bucket(name)?,
+        None => store
+            .default_bucket()
+            .ok_or_else(|| ZugzugError::new(&t!("No bucket to choose from")))?,
+    };
+    let path = match link::read_latest(Path::new(&bucket.path)).filter(|path| path.is_dir()) {
+        Some(path) if !update => path,
+        _ => bucket
+            .dirs()?
+            .into_iter()
+            .max_by_key(|dir| (dir.date_key(), dir.full_name()))
+            .map(|dir| dir.path)
+            .ok_or_else(|| ZugzugError::new("No directories in this bucket"))?,
+    };
+    if update {
+        readonly::check()?;
+        link::update_latest(Path::new(&bucket.path), &path)?;
+    }
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Link to a directory from somewhere else
+///
+/// The link is made in the working directory, named like the directory,
+/// unless a path is given. On Windows a junction is made when directory
+/// symlinks aren't allowed.
+///
+/// # Example
+///
+/// ```
+/// zz link my_dir
+/// zz link my_dir ~/projects/current
+/// ```
+fn handle_link(
+    store: &Store,
+    name: &str,
+    link_path: Option<&str>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    readonly::check()?;
+    let dir = resolve_dir(store, name)?;
+    let target = fs::canonicalize(&dir.path)?;
+    let link_path = match link_path {
+        Some(path) => PathBuf::from(path),
+        None => env::current_dir()?.join(dir.full_name()),
+    };
+    let kind = link::link_dir(&target, &link_path)?;
+    println!("{} -> {} ({})", link_path.display(), target.display(), kind);
+    Ok(())
+}
+
+/// Manage environment variables of a directory
+///
+/// # Example
+///
+/// ```
+/// zz env set my_dir DATABASE_URL=postgres://localhost/db
+/// zz env unset my_dir DATABASE_URL
+/// zz env ls my_dir
+///
+/// # Export the variables into the current shell
+/// eval "$(zz env export my_dir)"
+/// ```
+fn handle_env(
+    store: &mut Store,
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(matches) = matches.subcommand_matches("set") {
+        let dir = resolve_dir(store, matches.value_of("NAME").unwrap())?;
+        let vars = matches
+            .values_of("VARS")
+            .unwrap()
+            .map(envvars::parse_assignment)
+            .collect::<Result<Vec<_>, _>>()?;
+        store.set_dir_env(&dir.path, &vars)?;
+    } else if let Some(matches) = matches.subcommand_matches("unset") {
+        let dir = resolve_dir(store, matches.value_of("NAME").unwrap())?;
+        let keys: Vec<&str> = matches.values_of("KEYS").unwrap().collect();
+        store.unset_dir_env(&dir.path, &keys)?;
+    } else if let Some(matches) = matches.subcommand_matches("ls") {
+        let dir = resolve_dir(store, matches.value_of("NAME").unwrap())?;
+        if let Some(meta) = store.dir_meta(&dir.path) {
+            for (key, value) in meta.env.iter() {
+                println!("{}={}", key, value);
+            }
+        }
+    } else if let Some(matches) = matches.subcommand_matches("export") {
+        let dir = resolve_dir(store, matches.value_of("NAME").unwrap())?;
+        for (key, value) in envvars::dir_env(store, &dir.bucket, &dir.path) {
+            println!("export {}={}", key, envvars::shell_quote(&value));
+        }
+    }
+    Ok(())
+}
+
+/// Manage the tags of a directory
+///
+/// # Example
+///
+/// ```
+/// zz tag add my_dir keep demo
+/// zz tag rm my_dir demo
+/// zz tag ls my_dir
+///
+/// # Mirror the tags of every directory into Finder, see `finder.tags`
+/// zz tag sync
+/// ```
+fn handle_tag(
+    store: &mut Store,
+    matches: &ArgMatches,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(matches) = matches.subcommand_matches("add") {
+        let dir = resolve_dir(store, matches.value_of("NAME").unwrap())?;
+        let tags: Vec<&str> = matches.values_of("TAGS").unwrap().collect();
+        store.add_dir_tags(&dir.path, &tags)?;
+    } else if let Some(matches) = matches.subcommand_matches("rm") {
+        let dir = resolve_dir(store, matches.value_of("NAME").unwrap())?;
+        let tags: Vec<&str> = matches.values_of("TAGS").unwrap().collect();
+        store.remove_dir_tags(&dir.path, &tags)?;
+    } else if let Some(matches) = matches.subcommand_matches("ls") {
+        let dir = resolve_dir(store, matches.value_of("NAME").unwrap())?;
+        if let Some(meta) = store.dir_meta(&dir.path) {
+            for tag in meta.tags.iter() {
+                println!("{}", tag);
+            }
+        }
+    } else if matches.subcommand_matches("sync").is_some() {
+        handle_tag_sync(store)?;
+    }
+    Ok(())
+}
+
+// mirror the tags of all directories with metadata into Finder, e.g.
+// after turning on `finder.tags`
+fn handle_tag_sync(store: &Store) -> Result<(), Box<dyn error::Error + 'static>> {
+    if !finder::supported() {
+        return Err(Box::new(ZugzugError::new(
+            "Finder tags are only available on macOS",
+        )));
+    }
+    let mut config = Config::load()?.finder;
+    config.tags = true;
+    let mut synced = 0;
+    for dir in store.all_dirs() {
+        if let Some(meta) = store.dir_meta(&dir.path) {
+            finder::sync(&config, &dir.path, &meta);
+            synced += 1;
+        }
+    }
+    println!("Synced the Finder tags of {} directories", synced);
+    Ok(())
+}
+
+/// Print or set the description of a directory
+///
+/// # Example
+///
+/// ```
+/// zz describe my_dir "Repro for the login timeout"
+/// zz describe my_dir
+/// zz describe my_dir --unset
+/// ```
+fn handle_describe(
+    store: &mut Store,
+    name: &str,
+    description: Option<&str>,
+    unset: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let dir = resolve_dir(store, name)?;
+    if unset || description.is_some() {
+        return store.set_dir_description(&dir.path, description);
+    }
+    if let Some(description) = store.dir_meta(&dir.path).and_then(|meta| meta.description) {
+        println!("{}", description);
+    }
+    Ok(())
+}
+
+/// Set a directory aside without archiving it
+///
+/// Deprecated directories stay where they are but are hidden from `zz ls`,
+/// and when `zz prune` finds them old enough it removes them before anything
+/// else. `--undo` brings a directory back.
+///
+/// # Example
+///
+/// ```
+/// zz deprecate old-experiment
+/// zz deprecate --undo old-experiment
+/// ```
+fn handle_deprecate(
+    store: &mut Store,
+    name: &str,
+    undo: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    readonly::check()?;
+    let dir = resolve_dir(store, name)?;
+    if !undo && !prompt::confirm_change(&t!("Deprecate {name}?", name = dir.full_name()))? {
+        return Ok(());
+    }
+    store.set_dir_deprecated(&dir.path, !undo)
+}
+
+/// Run a command inside a directory with its environment exported
+///
+/// Exits with the command's exit code.
+///
+/// # Example
+///
+/// ```
+/// zz exec my_dir -- cargo test
+/// ```
+fn handle_exec(
+    store: &mut Store,
+    name: &str,
+    command: &[&str],
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let dir = open_dir(store, name)?;
+    let status = Command::new(command[0])
+        .args(&command[1..])
+        .current_dir(&dir.path)
+        .envs(envvars::dir_env(store, &dir.bucket, &dir.path))
+        .status()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run {}: {}", command[0], e)))?;
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+    Ok(())
+}
+
+/// Run a command in every directory matching a selection
+///
+/// Directories are selected by bucket, tag, age and `--where` expression
+/// like with `zz backup`, and the command runs in each with the directory's
+/// environment. With `--parallel` several run at once. A summary of the exit
+/// statuses is printed at the end, and zz fails if any of them did.
+///
+/// # Example
+///
+/// ```
+/// zz foreach -b work --older-than 30 -- git gc
+///
+/// zz foreach --tag rust --parallel 4 -- cargo clean
+///
+/// zz foreach --where 'type=rust and size>500M' -- cargo clean
+/// ```
+fn handle_foreach(
+    store: &Store,
+    selection: &DirSelection,
+    parallel: usize,
+    command: &[&str],
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let jobs: Vec<foreach::Job> = selection
+        .select(store)?
+        .into_iter()
+        .map(|dir| foreach::Job {
+            env: envvars::dir_env(store, &dir.bucket, &dir.path),
+            dir,
+        })
+        .collect();
+    if jobs.is_empty() {
+        println!("No directories match");
+        return Ok(());
+    }
+    let results = foreach::run(jobs, command, parallel);
+    println!();
+    let mut table = simple_table();
+    let mut failed = 0;
+    for (dir, status) in results.iter() {
+        let status = match status {
+            Ok(status) if status.success() => output::paint("ok", Color::Green),
+            Ok(status) => {
+                failed += 1;
+                let status = match status.code() {
+                    Some(code) => format!("exit {}", code),
+                    None => "killed".to_string(),
+                };
+                output::paint(&status, Color::Red)
+            }
+            Err(e) => {
+                failed += 1;
+                output::paint(&format!("unable to run: {}", e), Color::Red)
+            }
+        };
+        table.add_row(row![dir.bucket, dir.full_name(), status]);
+    }
+    table.printstd();
+    if failed > 0 {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} of {} commands failed",
+            failed,
+            results.len()
+        ))));
+    }
+    Ok(())
+}
+
+/// Open a directory in the OS file manager
+///
+/// # Example
+///
+/// ```
+/// zz reveal my_dir
+/// ```
+fn handle_reveal(store: &mut Store, name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    let dir = open_dir(store, name)?;
+    platform::reveal(&dir.path)
+}
+
+/// Add zz to the context menu of folders in Explorer
+///
+/// Right-clicking a bucket offers "New zz directory here", which asks for a
+/// name and makes a dated directory in it. Right-clicking a directory of a
+/// bucket offers "Open in zz", opening PowerShell in it with its
+/// environment. Both also show when right-clicking inside the folder.
+///
+/// # Example
+///
+/// ```
+/// zz windows install-shell-ext
+/// zz windows uninstall-shell-ext
+/// ```
+fn handle_install_shell_ext() -> Result<(), Box<dyn error::Error + 'static>> {
+    shellext::install(&env::current_exe()?)?;
+    println!("Added zz to the Explorer context menu");
+    Ok(())
+}
+
+/// Remove zz from the context menu of folders in Explorer
+fn handle_uninstall_shell_ext() -> Result<(), Box<dyn error::Error + 'static>> {
+    match shellext::uninstall()? {
+        0 => println!("zz is not in the Explorer context menu"),
+        _ => println!("Removed zz from the Explorer context menu"),
+    }
+    Ok(())
+}
+
+// "New zz directory here": make a directory in the bucket at a path,
+// asking for its name
+fn handle_new_here(store: &mut Store, path: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    let bucket = match store.locate(Path::new(path)) {
+        Some((bucket, None)) => bucket,
+        _ => {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "{} is not a bucket, add it with zz bucket add",
+                path
+            ))))
+        }
+    };
+    let name = prompt::input(&t!("Name of the new directory:"), None)?
+        .ok_or_else(|| ZugzugError::new(&t!("No name given")))?;
+    handle_mkdir(store, &name, Some(&bucket.name), None, false, false, false)
+}
+
+// "Open in zz": open a shell in the directory a path is in, with the
+// directory's environment, recording the access like `zz exec`
+fn handle_open_here(store: &mut Store, path: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    let dir = match store.locate(Path::new(path)) {
+        Some((_, Some(dir))) => dir,
+        _ => {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "{} is not inside a directory of a bucket",
+                path
+            ))))
+        }
+    };
+    store.record_access(&dir.path)?;
+    store.save()?;
+    let shell = match cfg!(windows) {
+        true => "powershell".to_string(),
+        false => env::var("SHELL").unwrap_or_else(|_| "sh".to_string()),
+    };
+    Command::new(&shell)
+        .current_dir(&dir.path)
+        .envs(envvars::dir_env(store, &dir.bucket, &dir.path))
+        .status()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run {}: {}", shell, e)))?;
+    Ok(())
+}
+
+/// Copy the absolute path of a directory to the clipboard
+///
+/// # Example
+///
+/// ```
+/// zz copy-path my_dir
+/// ```
+fn handle_copy_path(store: &mut Store, name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    let dir = open_dir(store, name)?;
+    let path = fs::canonicalize(&dir.path)?;
+    platform::copy_to_clipboard(&path.to_string_lossy())?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Print a PowerShell module with cmdlets that return objects
+///
+/// `Get-ZzDir`, `Get-ZzBucket` and `New-ZzDir` wrap `zz ls`, `zz bucket ls`
+/// and `zz mkdir`, reading `--format jsonl` output.
+///
+/// # Example
+///
+/// ```
+/// zz powershell-module | Out-File zz.psm1
+/// Import-Module ./zz.psm1
+/// Get-ZzDir -Bucket work | Sort-Object date
+/// ```
+fn handle_powershell_module() {
+    print!("{}", powershell::MODULE);
+}
+
+/// Run zz commands read from stdin, one per line
+///
+/// Each line is a zz command line without the leading `zz`, e.g. `mkdir x`
+/// or `tag add x foo`, split into words like a shell would. Blank lines and
+/// lines starting with `#` are skipped. All commands share one store, which
+/// is written once at the end instead of after every command. Stops at the
+/// first line that fails, keeping the changes made before it.
+///
+/// Stdin is read completely first, so commands asking for confirmation get
+/// no answer and decline.
+///
+/// # Example
+///
+/// ```
+/// printf 'mkdir api\ntag add api work\n' | zz batch
+/// ```
+fn handle_batch(stores: &mut LazyStore) -> Result<(), Box<dyn error::Error + 'static>> {
+    let mut input = String::new();
+    io::stdin().read_to_string(&mut input)?;
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fail = |message: &str| ZugzugError::new(&format!("Line {}: {}", i + 1, message));
+        let words = batch::split(line).map_err(|e| fail(&e.to_string()))?;
+        let matches = app()
+            .get_matches_from_safe(iter::once("zz".to_string()).chain(words))
+            .map_err(|e| fail(&e.message))?;
+        if matches.subcommand_matches("batch").is_some() {
+            return Err(Box::new(fail("batch can't run another batch")));
+        }
+        let format = output_format(&matches).map_err(|e| fail(&e.to_string()))?;
+        dispatch(&matches, format, stores).map_err(|e| fail(&e.to_string()))?;
+    }
+    Ok(())
+}
+
+/// Answer JSON-RPC requests from editor plugins
+///
+/// Requests and responses are JSON-RPC 2.0 objects, one per line, on stdin
+/// and stdout or on a unix socket with `--socket`. Methods are `buckets`,
+/// `list`, `resolve`, `meta` and `mkdir`. With `watch_interval` the buckets
+/// are also watched for changes made outside zz meanwhile, see
+/// `watch::run`.
+///
+/// # Example
+///
+/// ```
+/// echo '{"jsonrpc":"2.0","id":1,"method":"resolve","params":{"name":"foo"}}' | zz serve
+///
+/// zz serve --socket /tmp/zz.sock --watch
+/// ```
+fn handle_serve(
+    socket: Option<&str>,
+    watch_interval: Option<u64>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(seconds) = watch_interval {
+        let interval = Duration::from_secs(seconds.max(1));
+        thread::spawn(move || {
+            if let Err(e) = watch::run(interval) {
+                error!("Stopped watching buckets: {}", e);
+            }
+        });
+    }
+    match socket {
+        Some(path) => serve::socket(Path::new(path)),
+        None => serve::stdio(),
+    }
+}
+
+/// Pack a directory into an archive for sending
+///
+/// The archive is named after the directory, including its date prefix. It
+/// is written to `share.output_dir` from the config, or the working
+/// directory, unless `-o` says otherwise. `-o -` writes to stdout.
+///
+/// # Example
+///
+/// ```
+/// zz share my_dir
+///
+/// zz share --type tar.gz -o - my_dir | ssh host 'tar -xzf -'
+/// ```
+fn handle_share(
+    store: &Store,
+    name: &str,
+    format: Option<&str>,
+    output: Option<&str>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let config = Config::load()?;
+    let dir = resolve_dir(store, name)?;
+    let format: ArchiveFormat = format
+        .or(config.share.format.as_deref())
+        .unwrap_or("zip")
+        .parse()?;
+
+    let destination = match output.or(config.share.output_dir.as_deref()) {
+        Some("-") => Destination::Stdout,
+        Some(output) => {
+            let output = Path::new(output);
+            if output.is_dir() {
+                Destination::File(output.join(format!("{}{}", dir.full_name(), format.extension())))
+            } else {
+                Destination::File(output.to_path_buf())
+            }
+        }
+        None => Destination::File(std::env::current_dir()?.join(format!(
+            "{}{}",
+            dir.full_name(),
+            format.extension()
+        ))),
+    };
+    if let Destination::File(path) = &destination {
+        if path.exists() {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "{} already exists",
+                path.display()
+            ))));
+        }
+    }
+    archive::create(&dir.path, format, &destination)?;
+    if let Destination::File(path) = destination {
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// Upload a directory to the configured publish target
+///
+/// Uses the target configured for the directory's bucket in
+/// `[publish.buckets.<name>]`, falling back to the global `[publish]` target.
+///
+/// # Example
+///
+/// ```
+/// zz publish my_dir
+/// ```
+fn handle_publish(store: &Store, name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    let config = Config::load()?;
+    let dir = resolve_dir(store, name)?;
+    let target = config.publish.target_for(&dir.bucket).ok_or_else(|| {
+        ZugzugError::new(&format!(
+            "No publish target configured for bucket '{}'",
+            dir.bucket
+        ))
+    })?;
+    let location =
+        publish::publisher(&target, &config.secrets)?.publish(&dir.path, &dir.full_name())?;
+    match target.url {
+        Some(url) => println!("{}/{}/", url.trim_end_matches('/'), dir.full_name()),
+        None => println!("{}", location),
+    }
+    Ok(())
+}
+
+/// List directories that were archived before removal
+///
+/// # Example
+///
+/// ```
+/// zz archive ls
+///
+/// # Only archives from a specific bucket
+/// zz archive ls -b my_bucket
+///
+/// # Only large archives from last year
+/// zz archive ls --where 'size>1G and date<2024-01-01'
+/// ```
+fn handle_archive_ls(
+    store: &Store,
+    filter_bucket_name: Option<&str>,
+    filter: Option<&Filter>,
+    format: Format,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let archives: Vec<&ArchivedDir> = store
+        .archives()
+        .iter()
+        .filter(|a| filter_bucket_name.is_none() || filter_bucket_name == Some(a.bucket.as_str()))
+        .filter(|a| filter.is_none_or(|filter| filter.matches(*a)))
+        .collect();
+    if format.is_json() {
+        let entries = archives.iter().map(|a| json!(a)).collect();
+        output::print_json_records(entries, format);
+        return Ok(());
+    }
+    let mut table = simple_table();
+    for archived in archives {
+        let (date, name) = archived.date_and_name();
+        if format == Format::Porcelain {
+            output::print_porcelain(
+                "archive",
+                &[
+                    &archived.bucket,
+                    date,
+                    name,
+                    &archived.size.to_string(),
+                    &archived.archive,
+                    &archived.format,
+                ],
+            );
+            continue;
+        }
+        table.add_row(row![
+            archived.bucket,
+            date,
+            name,
+            r->fsutil::human_size(archived.size),
+            archived.archive
+        ]);
+    }
+    match format {
+        Format::Markdown => {
+            output::print_markdown(&["bucket", "date", "name", "size", "archive"], &table)
+        }
+        Format::Table => table.printstd(),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Unpack an archived directory back into its bucket
+///
+/// The archive is matched by the directory name, with or without its date
+/// prefix. Once restored, the archive file is deleted unless `keep` is set.
+///
+/// # Example
+///
+/// ```
+/// zz archive restore 20190301_run
+/// ```
+fn handle_archive_restore(
+    store: &mut Store,
+    name: &str,
+    keep: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    readonly::check()?;
+    let archived = find_archive(store, name)?;
+    let bucket = store
+        .find_bucket(&archived.bucket)
+        .cloned()
+        .ok_or_else(|| {
+            ZugzugError::new(&format!("Bucket '{}' no longer exists", archived.bucket))
+        })?;
+    let root = bucket.mkdir_root();
+    let path = root.join(&archived.name);
+    if path.exists() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} already exists",
+            path.display()
+        ))));
+    }
+    let browse_root = archive_browse_root(&archived)?;
+    if archive::unbrowse(&browse_root)? {
+        info!("Removed {} before restoring", browse_root.display());
+    }
+    archive::extract(Path::new(&archived.archive), archived.format.parse()?, root)?;
+    store.remove_archived(&archived.archive)?;
+    if !keep {
+        fs::remove_file(&archived.archive)?;
+    }
+    println!("{}", path.display());
+    webhook::notify(
+        &Config::load()?.webhooks,
+        Event::Created,
+        &bucket.name,
+        &path,
+    );
+    Ok(())
+}
+
+/// Browse an archived directory without restoring it
+///
+/// The archive is mounted read-only with `archivemount` (FUSE) when it is
+/// installed, otherwise it is extracted into a cache that is reused until
+/// the archive changes. `extract` always extracts. The path of the archived
+/// directory is printed, `zz archive unmount` cleans up again.
+///
+/// # Example
+///
+/// ```
+/// cd "$(zz archive mount 20190301_run)"
+/// zz archive unmount 20190301_run
+/// ```
+fn handle_archive_mount(
+    store: &Store,
+    name: &str,
+    extract: bool,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let archived = find_archive(store, name)?;
+    let (path, browsing) = archive::browse(&archived, &archive_browse_root(&archived)?, extract)?;
+    match browsing {
+        archive::Browsing::Mounted => info!("Mounted {} read-only", archived.archive),
+        archive::Browsing::Extracted => info!(
+            "Extracted {}, changes are not saved to the archive",
+            archived.archive
+        ),
+    }
+    println!("{}", path.display());
+    Ok(())
+}
+
+/// Unmount an archive mounted with `zz archive mount`, or remove its copy
+fn handle_archive_unmount(
+    store: &Store,
+    name: &str,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let archived = find_archive(store, name)?;
+    if !archive::unbrowse(&archive_browse_root(&archived)?)? {
+        println!("{} is not mounted", archived.name);
+    }
+    Ok(())
+}
+
+// find an archive by the directory name, with or without its date prefix
+fn find_archive(store: &Store, name: &str) -> Result<ArchivedDir, Box<dyn error::Error + 'static>> {
+    let matches: Vec<&ArchivedDir> = store
+        .archives()
+        .iter()
+        .filter(|a| a.name == name || a.date_and_name().1 == name)
+        .collect();
+    match matches.len() {
+        0 => Err(Box::new(ZugzugError::new(&format!(
+            "No archive named '{}'",
+            name
+        )))),
+        1 => Ok(matches[0].clone()),
+        _ => {
+            let names: Vec<String> = matches
+                .iter()
+                .map(|a| format!("{}/{}", a.bucket, a.name))
+                .collect();
+            Err(Box::new(ZugzugError::new(&format!(
+                "'{}' matches several archives: {}",
+                name,
+                names.join(", ")
+            ))))
+        }
+    }
+}
+
+// where an archive is mounted or extracted for browsing
+fn archive_browse_root(archived: &ArchivedDir) -> Result<PathBuf, Box<dyn error::Error + 'static>> {
+    Ok(Config::load()?
+        .archive_browse_dir()?
+        .join(&archived.bucket)
+        .join(&archived.name))
+}
+
+/// Store a secret for a publish target, or remove one
+///
+/// The secret is read from the first line of stdin. With the keyring
+/// backend it is stored right away, with age the encrypted value is printed
+/// for `[secrets.values]`.
+///
+/// # Example
+///
+/// ```
+/// zz secret set dav
+/// pass show dav | zz secret set dav
+/// zz secret rm dav
+/// ```
+fn handle_secret(matches: &ArgMatches) -> Result<(), Box<dyn error::Error + 'static>> {
+    let config = Config::load()?;
+    if let Some(matches) = matches.subcommand_matches("set") {
+        let name = matches.value_of("NAME").unwrap();
+        let mut secret = String::new();
+        io::stdin().read_line(&mut secret)?;
+        let secret = secret.trim_end_matches(['\r', '\n']);
+        if secret.is_empty() {
+            return Err(Box::new(ZugzugError::new("No secret on stdin")));
+        }
+        if let Some(encrypted) = secrets::set(&config.secrets, name, secret)? {
+            println!("[secrets.values]");
+            println!("{} = \"\"\"\n{}\"\"\"", name, encrypted);
+        }
+    } else if let Some(matches) = matches.subcommand_matches("rm") {
+        secrets::remove(&config.secrets, matches.value_of("NAME").unwrap())?;
+    }
+    Ok(())
+}
+
+/// Restore the store from its newest readable backup
+///
+/// Reports which backup was used, and so which changes were lost.
+///
+/// # Example
+///
+/// ```
+/// zz store recover
+/// ```
+fn handle_store_recover() -> Result<(), Box<dyn error::Error + 'static>> {
+    let recovery = Store::recover()?;
+    for skipped in recovery.skipped.iter() {
+        println!("Skipped unreadable backup {}", skipped.display());
+    }
+    if let Some(corrupt) = recovery.corrupt {
+        println!("Moved the broken store to {}", corrupt.display());
+    }
+    match recovery.written {
+        Some(written) => println!(
+            "Restored {} written {}. Changes made after that are lost.",
+            recovery.restored.display(),
+            written.format("%Y-%m-%d %H:%M:%S")
+        ),
+        None => println!("Restored {}", recovery.restored.display()),
+    }
+    println!("Buckets: {}", recovery.buckets.join(", "));
+    Ok(())
+}
+
+/// Open a directory with an editor configured in the `[editors]` table
+///
+/// Every configured editor becomes a subcommand of its own.
+///
+/// # Example
+///
+/// ```
+/// # With `code = "code"` in ~/.zz.toml
+/// zz code my_dir
+/// ```
+fn handle_editor(
+    store: &mut Store,
+    editor: &str,
+    args: &[&str],
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let config = Config::load()?;
+    let command = match config.editor_command(editor) {
+        Some(command) => command,
+        None => {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "Unknown command '{}', and no editor with that name is configured",
+                editor
+            ))))
+        }
+    };
+    let name = match args {
+        [name] => name,
+        _ => {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "Usage: zz {} <NAME>",
+                editor
+            ))))
+        }
+    };
+    let dir = open_dir(store, name)?;
+    let status = Command::new(&command[0])
+        .args(&command[1..])
+        .arg(&dir.path)
+        .status()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run {}: {}", command[0], e)))?;
+    if !status.success() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} exited with {}",
+            command[0], status
+        ))));
+    }
+    Ok(())
+}
+
+/// The `default` subcommand, available as `zz default` and `zz bucket default`
+fn default_subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("default")
+        .about("Get or set the default bucket")
+        .arg(
+            Arg::with_name("NAME")
+                .help("Set the default bucket to this bucket")
+                .required(false),
+        )
+        .arg(
+            Arg::with_name("unset")
+                .help("Unset the default bucket")
+                .long("unset")
+                .conflicts_with("NAME"),
+        )
+}
+
+/// Parse CLI arguments
+pub fn parse_args<'a>() -> Result<ArgMatches<'a>, Box<dyn error::Error + 'static>> {
+    Ok(app().get_matches())
+}
+
+// the command line interface, shared by `parse_args` and `zz batch`
+fn app() -> App<'static, 'static> {
+    App::new("zz")
+        .version("0.1.0")
+        .author("Erik R. <eronshagen@gmail.com>")
+        .about("Manage temporary working directories")
+        .setting(AppSettings::AllowExternalSubcommands)
+        .setting(match output::no_color_env() {
+            true => AppSettings::ColorNever,
+            false => AppSettings::ColorAuto,
+        })
+        .arg(
+            Arg::with_name("read-only")
+                .help("Refuse to change the store or any directories [env: ZZ_READONLY=1]")
+                .long("read-only")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("yes")
+                .help("Go ahead without asking for confirmation, like confirm = \"never\"")
+                .short("y")
+                .long("yes")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("log-level")
+                .help("How much to report on stderr, debug includes timings [env: RUST_LOG] [default: warn]")
+                .long("log-level")
+                .value_name("LEVEL")
+                .possible_values(log::LEVELS)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("format")
+                .help("Output format")
+                .long("format")
+                .value_name("FORMAT")
+                .possible_values(output::FORMATS)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("color")
+                .help("When to color output, auto colors terminals unless NO_COLOR is set")
+                .long("color")
+                .value_name("WHEN")
+                .possible_values(output::COLOR_CHOICES)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("style")
+                .help("How tables are drawn")
+                .long("style")
+                .value_name("STYLE")
+                .possible_values(output::STYLES)
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("json")
+                .help("Shorthand for --format json")
+                .long("json")
+                .global(true),
+        )
+        .arg(
+            Arg::with_name("porcelain")
+                .help("Shorthand for --format porcelain, stable output for scripts")
+                .long("porcelain")
+                .global(true),
+        )
+        .subcommand(
+            SubCommand::with_name("bucket")
+                .about("Manage buckets")
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the bucket")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("DIR")
+                                .help("Path to the bucket")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("relative-to-home")
+                                .help(
+                                    "Store the path as ~/..., for stores used on several machines",
+                                )
+                                .long("relative-to-home"),
+                        ),
+                )
+                .subcommand(default_subcommand())
+                .subcommand(
+                    SubCommand::with_name("forget")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the bucket")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("force")
+                                .help("Forget the bucket even if another user owns it")
+                                .long("force"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("import")
+                        .about("Register an existing directory tree as a bucket")
+                        .arg(
+                            Arg::with_name("DIR")
+                                .help("Path to the directory tree")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("name")
+                                .help("Name of the bucket, the directory name by default")
+                                .long("name")
+                                .value_name("NAME"),
+                        )
+                        .arg(
+                            Arg::with_name("adopt")
+                                .help("Rename dated directories not in the zz format")
+                                .long("adopt"),
+                        )
+                        .arg(
+                            Arg::with_name("dry-run")
+                                .help("Only show what would be imported")
+                                .long("dry-run"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("date-position")
+                        .about("Show or set where new directories get their date")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the bucket")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("POSITION")
+                                .help("Before the name, after it, or nowhere")
+                                .possible_values(&["prefix", "suffix", "none"]),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("journal")
+                        .about("Keep one directory per day in a bucket")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the bucket")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("off")
+                                .help("Turn journal mode off again")
+                                .long("off"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("mv")
+                        .about("Move a bucket's directory, also onto another filesystem")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the bucket")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("DIR")
+                                .help("New path of the bucket")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("order")
+                        .about("Show or set the order of buckets")
+                        .arg(
+                            Arg::with_name("NAMES")
+                                .help("Buckets in order, the first one wins ambiguous names")
+                                .multiple(true),
+                        )
+                        .arg(
+                            Arg::with_name("reset")
+                                .help("Use the order buckets were added in again")
+                                .long("reset")
+                                .conflicts_with("NAMES"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("path")
+                        .about("Manage the paths a bucket spans")
+                        .subcommand(
+                            SubCommand::with_name("add")
+                                .about("Add a path")
+                                .arg(
+                                    Arg::with_name("NAME")
+                                        .help("Name of the bucket")
+                                        .required(true),
+                                )
+                                .arg(
+                                    Arg::with_name("DIR")
+                                        .help("Directory to add")
+                                        .required(true),
+                                )
+                                .arg(
+                                    Arg::with_name("primary")
+                                        .help("Make it the path new directories go into")
+                                        .long("primary"),
+                                ),
+                        )
+                        .subcommand(
+                            SubCommand::with_name("rm")
+                                .about("Remove a path, leaving its directories on disk")
+                                .arg(
+                                    Arg::with_name("NAME")
+                                        .help("Name of the bucket")
+                                        .required(true),
+                                )
+                                .arg(
+                                    Arg::with_name("DIR")
+                                        .help("Directory to remove")
+                                        .required(true),
+                                ),
+                        )
+                        .subcommand(
+                            SubCommand::with_name("ls")
+                                .about("List paths, the primary one first")
+                                .arg(
+                                    Arg::with_name("NAME")
+                                        .help("Name of the bucket")
+                                        .required(true),
+                                ),
+                        )
+                        .subcommand(
+                            SubCommand::with_name("placement")
+                                .about("Show or set where new directories go")
+                                .arg(
+                                    Arg::with_name("NAME")
+                                        .help("Name of the bucket")
+                                        .required(true),
+                                )
+                                .arg(
+                                    Arg::with_name("PLACEMENT")
+                                        .help(
+                                            "The primary path, or the one with the most free space",
+                                        )
+                                        .possible_values(&["primary", "most-free"]),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("mode")
+                        .about("Show or set the permission mode of new directories")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the bucket")
+                                .required(true),
+                        )
+                        .arg(Arg::with_name("MODE").help("Octal mode, e.g. 0700"))
+                        .arg(
+                            Arg::with_name("unset")
+                                .help("Use the default permissions again")
+                                .long("unset")
+                                .conflicts_with("MODE"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("env")
+                        .about("Manage environment variables of a bucket")
+                        .subcommand(
+                            SubCommand::with_name("set")
+                                .about("Set variables")
+                                .arg(
+                                    Arg::with_name("NAME")
+                                        .help("Name of the bucket")
+                                        .required(true),
+                                )
+                                .arg(
+                                    Arg::with_name("VARS")
+                                        .help("Variables as KEY=VALUE")
+                                        .required(true)
+                                        .multiple(true),
+                                ),
+                        )
+                        .subcommand(
+                            SubCommand::with_name("unset")
+                                .about("Remove variables")
+                                .arg(
+                                    Arg::with_name("NAME")
+                                        .help("Name of the bucket")
+                                        .required(true),
+                                )
+                                .arg(
+                                    Arg::with_name("KEYS")
+                                        .help("Variable names")
+                                        .required(true)
+                                        .multiple(true),
+                                ),
+                        )
+                        .subcommand(
+                            SubCommand::with_name("ls").about("List variables").arg(
+                                Arg::with_name("NAME")
+                                    .help("Name of the bucket")
+                                    .required(true),
+                            ),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("hook")
+                        .about("Manage post-create hooks of a bucket")
+                        .subcommand(
+                            SubCommand::with_name("add")
+                                .about("Add a hook")
+                                .arg(
+                                    Arg::with_name("NAME")
+                                        .help("Name of the bucket")
+                                        .required(true),
+                                )
+                                .arg(
+                                    Arg::with_name("COMMAND")
+                                        .help("Shell command to run in new directories")
+                                        .required(true),
+                                ),
+                        )
+                        .subcommand(
+                            SubCommand::with_name("ls").about("List hooks").arg(
+                                Arg::with_name("NAME")
+                                    .help("Name of the bucket")
+                                    .required(true),
+                            ),
+                        )
+                        .subcommand(
+                            SubCommand::with_name("clear")
+                                .about("Remove all hooks")
+                                .arg(
+                                    Arg::with_name("NAME")
+                                        .help("Name of the bucket")
+                                        .required(true),
+                                ),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("ls").about("List buckets").arg(
+                        Arg::with_name("size")
+                            .help("Show the total size of each bucket")
+                            .long("size"),
+                    ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("archive")
+                .about("Browse and restore archived directories")
+                .subcommand(
+                    SubCommand::with_name("ls")
+                        .about("List archives")
+                        .arg(
+                            Arg::with_name("bucket")
+                                .help("Only list archives from this bucket")
+                                .short("b")
+                                .long("bucket")
+                                .value_name("BUCKET_NAME"),
+                        )
+                        .arg(
+                            Arg::with_name("where")
+                                .help("Only list archives matching this filter expression")
+                                .long("where")
+                                .value_name("EXPR"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("restore")
+                        .about("Restore an archived directory into its bucket")
+                        .arg(
+                            Arg::with_name("keep")
+                                .help("Keep the archive file after restoring")
+                                .long("keep"),
+                        )
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the archived dir")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("mount")
+                        .about("Browse an archived directory read-only without restoring it")
+                        .arg(
+                            Arg::with_name("extract")
+                                .help("Extract a copy even if the archive could be mounted")
+                                .long("extract"),
+                        )
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the archived dir")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("unmount")
+                        .about("Unmount an archive, or remove its extracted copy")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the archived dir")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("backup")
+                .about("Back up directories into a hard-linked snapshot")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Only back up directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .help("Only back up directories with this tag")
+                        .long("tag")
+                        .value_name("TAG"),
+                )
+                .arg(
+                    Arg::with_name("older-than")
+                        .help("Only back up directories older than this many days")
+                        .long("older-than")
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::with_name("newer-than")
+                        .help("Only back up directories at most this many days old")
+                        .long("newer-than")
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::with_name("where")
+                        .help("Only back up directories matching this filter expression")
+                        .long("where")
+                        .value_name("EXPR"),
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .help("Backup root, instead of backup.root from the config")
+                        .long("to")
+                        .value_name("PATH"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("batch")
+                .about("Run zz commands read from stdin, one per line, saving the store once"),
+        )
+        .subcommand(
+            SubCommand::with_name("biggest")
+                .about("Show the largest directories")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Only consider directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .help("Number of directories to show")
+                        .short("n")
+                        .long("count")
+                        .value_name("N")
+                        .default_value("10"),
+                ),
+        )
+        .subcommand(default_subcommand())
+        .subcommand(
+            SubCommand::with_name("context")
+                .about("Switch between contexts with their own default bucket")
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("Add a context, or replace one")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the context")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("default")
+                                .help("Default bucket in the context")
+                                .long("default")
+                                .value_name("BUCKET_NAME"),
+                        )
+                        .arg(
+                            Arg::with_name("bucket")
+                                .help("Only show this bucket in the context, may be repeated")
+                                .short("b")
+                                .long("bucket")
+                                .value_name("BUCKET_NAME")
+                                .multiple(true)
+                                .number_of_values(1),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("use")
+                        .about("Switch to a context")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the context")
+                                .required_unless("none"),
+                        )
+                        .arg(
+                            Arg::with_name("none")
+                                .help("Stop using a context")
+                                .long("none")
+                                .conflicts_with("NAME"),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rm").about("Remove a context").arg(
+                        Arg::with_name("NAME")
+                            .help("Name of the context")
+                            .required(true),
+                    ),
+                )
+                .subcommand(SubCommand::with_name("ls").about("List contexts")),
+        )
+        .subcommand(
+            SubCommand::with_name("diff")
+                .about("Compare two directories")
+                .arg(
+                    Arg::with_name("content")
+                        .help("Show content diffs of changed files")
+                        .long("content"),
+                )
+                .arg(
+                    Arg::with_name("A")
+                        .help("Name of the first dir")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("B")
+                        .help("Name of the second dir")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("empty")
+                .about("Find directories that contain no files")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Only consider directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("delete")
+                        .help("Remove the empty directories after confirmation")
+                        .long("delete"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rmdir")
+                .about("Remove directories, but only if they contain no files")
+                .arg(
+                    Arg::with_name("NAMES")
+                        .help("Exact names of the dirs")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("env")
+                .about("Manage environment variables of a directory")
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("Set variables")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the dir")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("VARS")
+                                .help("Variables as KEY=VALUE")
+                                .required(true)
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("unset")
+                        .about("Remove variables")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the dir")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("KEYS")
+                                .help("Variable names")
+                                .required(true)
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("ls").about("List variables").arg(
+                        Arg::with_name("NAME")
+                            .help("Name of the dir")
+                            .required(true),
+                    ),
+                )
+                .subcommand(
+                    SubCommand::with_name("export")
+                        .about("Print shell export statements")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the dir")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("exec")
+                .about("Run a command inside a directory with its environment")
+                .setting(AppSettings::TrailingVarArg)
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("COMMAND")
+                        .help("Command to run")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("count")
+                .about("Print how many directories match, for scripts")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Only count directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .help("Only count directories with this tag")
+                        .long("tag")
+                        .value_name("TAG"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .help("Only count directories whose name matches this glob")
+                        .long("name")
+                        .value_name("GLOB"),
+                )
+                .arg(
+                    Arg::with_name("older-than")
+                        .help("Only count directories older than this many days")
+                        .long("older-than")
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::with_name("newer-than")
+                        .help("Only count directories at most this many days old")
+                        .long("newer-than")
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::with_name("where")
+                        .help("Only count directories matching this filter expression")
+                        .long("where")
+                        .value_name("EXPR"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dupes")
+                .about("Report directories that are likely copies of each other")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Only look in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("any-name")
+                        .help("Also report copies named differently")
+                        .long("any-name"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("dedup")
+                .about("Hard-link identical files across directories")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Only look in directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .help("Only look in directories with this tag")
+                        .long("tag")
+                        .value_name("TAG"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .help("Only look in directories whose name matches this glob")
+                        .long("name")
+                        .value_name("GLOB"),
+                )
+                .arg(
+                    Arg::with_name("older-than")
+                        .help("Only look in directories older than this many days")
+                        .long("older-than")
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::with_name("newer-than")
+                        .help("Only look in directories at most this many days old")
+                        .long("newer-than")
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::with_name("where")
+                        .help("Only look in directories matching this filter expression")
+                        .long("where")
+                        .value_name("EXPR"),
+                )
+                .arg(
+                    Arg::with_name("min-size")
+                        .help("Skip files smaller than this, e.g. 10M")
+                        .long("min-size")
+                        .value_name("SIZE")
+                        .default_value("1M"),
+                )
+                .arg(
+                    Arg::with_name("apply")
+                        .help("Link the copies instead of only showing them")
+                        .long("apply"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("foreach")
+                .about("Run a command in every matching directory")
+                .setting(AppSettings::TrailingVarArg)
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Only directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("tag")
+                        .help("Only directories with this tag")
+                        .long("tag")
+                        .value_name("TAG"),
+                )
+                .arg(
+                    Arg::with_name("older-than")
+                        .help("Only directories older than this many days")
+                        .long("older-than")
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::with_name("newer-than")
+                        .help("Only directories at most this many days old")
+                        .long("newer-than")
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::with_name("where")
+                        .help("Only directories matching this filter expression")
+                        .long("where")
+                        .value_name("EXPR"),
+                )
+                .arg(
+                    Arg::with_name("parallel")
+                        .help("Number of directories to run the command in at once")
+                        .short("j")
+                        .long("parallel")
+                        .value_name("N")
+                        .default_value("1"),
+                )
+                .arg(
+                    Arg::with_name("COMMAND")
+                        .help("Command to run")
+                        .required(true)
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("ls")
+                .about("List directories")
+                .arg(
+                    Arg::with_name("bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME")
+                        // .takes_value(true) ???
+                        .help("List directories in this bucket"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .long("type")
+                        .value_name("TYPE")
+                        .possible_values(scaffold::PROJECT_TYPES)
+                        .help("List directories of this project type"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .value_name("GLOB")
+                        .help("List directories whose name matches this glob"),
+                )
+                .arg(
+                    Arg::with_name("regex")
+                        .long("regex")
+                        .value_name("REGEX")
+                        .help("List directories matching this regex"),
+                )
+                .arg(
+                    Arg::with_name("match-on")
+                        .long("match-on")
+                        .value_name("FIELD")
+                        .possible_values(pattern::MATCH_ON)
+                        .default_value("name")
+                        .help("Field the regex is matched against"),
+                )
+                .arg(
+                    Arg::with_name("where")
+                        .long("where")
+                        .value_name("EXPR")
+                        .help("List directories matching this filter expression"),
+                )
+                .arg(
+                    Arg::with_name("journal")
+                        .long("journal")
+                        .help("Show journal buckets as calendars"),
+                )
+                .arg(
+                    Arg::with_name("deprecated")
+                        .long("deprecated")
+                        .help("Also list directories set aside with zz deprecate"),
+                )
+                .arg(
+                    Arg::with_name("sort")
+                        .help(
+                            "Sort by date (newest first), name, last access, size or bucket order",
+                        )
+                        .long("sort")
+                        .value_name("KEY")
+                        .possible_values(SORT_KEYS),
+                )
+                .arg(
+                    Arg::with_name("columns")
+                        .help("Comma separated columns to print, e.g. bucket,date,name,size,tags")
+                        .long("columns")
+                        .value_name("COLUMNS"),
+                )
+                .arg(
+                    Arg::with_name("follow-symlinks")
+                        .help("List symlinked directories with their target's path and size")
+                        .long("follow-symlinks"),
+                )
+                .arg(
+                    Arg::with_name("no-symlinks")
+                        .help("Leave out symlinked directories")
+                        .long("no-symlinks")
+                        .conflicts_with("follow-symlinks"),
+                )
+                .arg(
+                    Arg::with_name("view")
+                        .help("Apply a view saved under [views] in the config")
+                        .long("view")
+                        .value_name("NAME"),
+                )
+                .arg(
+                    Arg::with_name("group-by")
+                        .help("List directories under a heading per bucket or month")
+                        .long("group-by")
+                        .value_name("KEY")
+                        .possible_values(GROUP_KEYS),
+                )
+                .arg(
+                    Arg::with_name("git")
+                        .long("git")
+                        .help("Show the git status of repositories (slower)"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("clone")
+                .about("Make a new directory holding a copy of an existing one")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir to copy")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("NEW_NAME")
+                        .help("Name of the copy, the original's if not given"),
+                )
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Bucket to make the copy in, the original's if not given")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("merge")
+                .about("Move the contents of a directory into another, then remove it")
+                .arg(
+                    Arg::with_name("SOURCE")
+                        .help("Directory to merge and remove")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("TARGET")
+                        .help("Directory to merge into")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("on-conflict")
+                        .help("What to do with files both have with other contents")
+                        .long("on-conflict")
+                        .value_name("HOW")
+                        .possible_values(&["skip", "overwrite", "rename"])
+                        .default_value("rename"),
+                )
+                .arg(
+                    Arg::with_name("deprecate")
+                        .help("Deprecate the source instead of removing it")
+                        .long("deprecate"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("mv")
+                .about("Move a directory into another bucket, also onto another filesystem")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("BUCKET")
+                        .help("Bucket to move the dir into")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("snapshot")
+                .about("Take, list and restore snapshots of a directory")
+                // keeps clap from mistaking names like `report` for typos
+                // of `restore`
+                .setting(AppSettings::AllowExternalSubcommands)
+                .arg(Arg::with_name("NAME").help("Name of the dir to snapshot"))
+                .subcommand(
+                    SubCommand::with_name("ls")
+                        .about("List the snapshots of a directory")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the dir")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("restore")
+                        .about("Replace the contents of a directory with a snapshot")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the dir")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("SNAPSHOT")
+                                .help("Snapshot to restore, the newest if not given"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bump")
+                .about("Re-date a directory to today, keeping its name")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("publish")
+                .about("Upload a directory to the configured remote")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("copy-path")
+                .about("Copy the path of a directory to the clipboard")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("reveal")
+                .about("Open a directory in the file manager")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("share")
+                .about("Archive a directory for sending")
+                .arg(
+                    Arg::with_name("type")
+                        .help("Archive format")
+                        .short("t")
+                        .long("type")
+                        .possible_values(&["zip", "tar.gz", "tar.xz", "tar.zst"])
+                        .value_name("FORMAT"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .help("Output file or directory, '-' for stdout")
+                        .short("o")
+                        .long("output")
+                        .value_name("PATH"),
+                )
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("prune")
+                .about("Remove directories past the retention age")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Only prune directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("older-than")
+                        .help("Prune directories older than this many days")
+                        .long("older-than")
+                        .value_name("DAYS"),
+                )
+                .arg(
+                    Arg::with_name("where")
+                        .help("Prune directories matching this filter expression")
+                        .long("where")
+                        .value_name("EXPR"),
+                )
+                .arg(
+                    Arg::with_name("keep-tag")
+                        .help("Spare directories with this tag")
+                        .long("keep-tag")
+                        .value_name("TAG")
+                        .multiple(true)
+                        .number_of_values(1),
+                )
+                .arg(
+                    Arg::with_name("archive-first")
+                        .help("Archive directories before removing them")
+                        .long("archive-first"),
+                )
+                .arg(
+                    Arg::with_name("archive-format")
+                        .help("Compression of the archives")
+                        .long("archive-format")
+                        .value_name("FORMAT")
+                        .possible_values(&["gzip", "xz", "zstd"]),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .help("Also remove git repositories with unsaved work")
+                        .short("f")
+                        .long("force"),
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .help("Only report what would be removed")
+                        .short("n")
+                        .long("dry-run"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("rm")
+                .about("Remove directories")
+                .alias("clean")
+                .arg(
+                    Arg::with_name("interactive")
+                        .help("Pick the directories to remove from a checklist")
+                        .short("i")
+                        .long("interactive"),
+                )
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Only offer directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME")
+                        .conflicts_with("NAME"),
+                )
+                .arg(
+                    Arg::with_name("older-than")
+                        .help("Only offer directories older than this many days")
+                        .long("older-than")
+                        .value_name("DAYS")
+                        .conflicts_with("NAME"),
+                )
+                .arg(
+                    Arg::with_name("where")
+                        .help("Only offer directories matching this filter expression")
+                        .long("where")
+                        .value_name("EXPR")
+                        .conflicts_with("NAME"),
+                )
+                .arg(
+                    Arg::with_name("archive-first")
+                        .help("Archive directories before removing them")
+                        .long("archive-first"),
+                )
+                .arg(
+                    Arg::with_name("archive-format")
+                        .help("Compression of the archives")
+                        .long("archive-format")
+                        .value_name("FORMAT")
+                        .possible_values(&["gzip", "xz", "zstd"]),
+                )
+                .arg(
+                    Arg::with_name("force")
+                        .help("Also remove git repositories with unsaved work")
+                        .short("f")
+                        .long("force"),
+                )
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Names of the dirs to remove")
+                        .multiple(true),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("secret")
+                .about("Manage credentials of publish targets")
+                .subcommand(
+                    SubCommand::with_name("set")
+                        .about("Store a secret read from stdin")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the secret")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rm")
+                        .about("Remove a secret from the keyring")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the secret")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("store")
+                .about("Maintain the store file")
+                .subcommand(
+                    SubCommand::with_name("recover")
+                        .about("Restore the store from the newest readable backup"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("powershell-module")
+                .about("Print a PowerShell module wrapping zz in cmdlets"),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Answer JSON-RPC requests from editor plugins")
+                .arg(
+                    Arg::with_name("socket")
+                        .help("Listen on a unix socket instead of stdin")
+                        .long("socket")
+                        .value_name("PATH"),
+                )
+                .arg(
+                    Arg::with_name("watch")
+                        .help("Keep the store in step with changes made to buckets by hand")
+                        .long("watch"),
+                )
+                .arg(
+                    Arg::with_name("interval")
+                        .help("Seconds between checks for changes")
+                        .long("interval")
+                        .value_name("SECONDS")
+                        .default_value("2"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("scratch")
+                .about("Make a throwaway directory that expires soon")
+                .arg(
+                    Arg::with_name("ttl")
+                        .help("Days until the directory expires")
+                        .long("ttl")
+                        .value_name("DAYS"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("describe")
+                .about("Print or set the description of a directory")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                )
+                .arg(Arg::with_name("DESCRIPTION").help("New description"))
+                .arg(
+                    Arg::with_name("unset")
+                        .help("Remove the description")
+                        .long("unset")
+                        .conflicts_with("DESCRIPTION"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("deprecate")
+                .about("Hide a directory from listings and prune it first")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("undo")
+                        .help("Bring a deprecated directory back")
+                        .long("undo"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Import history from other tools")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("zoxide")
+                        .about("Rank directories zoxide knows about by its scores")
+                        .arg(
+                            Arg::with_name("bucket")
+                                .help("Only import directories in this bucket")
+                                .short("b")
+                                .long("bucket")
+                                .value_name("BUCKET_NAME"),
+                        )
+                        .arg(
+                            Arg::with_name("min-score")
+                                .help("Skip paths zoxide scores lower")
+                                .long("min-score")
+                                .value_name("SCORE")
+                                .default_value("1"),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("discover")
+                .about("Find directories that look like buckets and offer to add them")
+                .arg(
+                    Arg::with_name("ROOT")
+                        .help("Directory to search below")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("depth")
+                        .help("How many levels deep to search")
+                        .long("depth")
+                        .value_name("LEVELS")
+                        .default_value("3"),
+                )
+                .arg(
+                    Arg::with_name("min")
+                        .help("Dated directories needed to count as a bucket")
+                        .long("min")
+                        .value_name("COUNT")
+                        .default_value("3"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("prev")
+                .about("Print the directory before the current one by date"),
+        )
+        .subcommand(
+            SubCommand::with_name("next")
+                .about("Print the directory after the current one by date"),
+        )
+        .subcommand(
+            SubCommand::with_name("recent")
+                .about("List the most recent directories")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("List directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .help("How many directories to list")
+                        .short("n")
+                        .value_name("COUNT")
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("accessed")
+                        .help("Order by when directories were last opened through zz")
+                        .long("accessed"),
+                )
+                .arg(
+                    Arg::with_name("by-date")
+                        .help("Order by date only, ignoring frecency")
+                        .long("by-date")
+                        .conflicts_with("accessed"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("stale")
+                .about("List the directories untouched the longest, by date and contents")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("List directories in this bucket")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .help("How many directories to list")
+                        .short("n")
+                        .value_name("COUNT")
+                        .default_value("10"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("find")
+                .about("Find directories by fuzzy name, most used first")
+                .arg(
+                    Arg::with_name("QUERY")
+                        .help("Part of the directory name")
+                        .required(true),
+                )
+                .arg(
+                    Arg::with_name("count")
+                        .help("How many directories to list")
+                        .short("n")
+                        .value_name("COUNT")
+                        .default_value("10"),
+                )
+                .arg(
+                    Arg::with_name("first")
+                        .help("Only print the path of the best match")
+                        .long("first"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("report")
+                .about("Summarize activity during a week or month")
+                .arg(
+                    Arg::with_name("period")
+                        .help("Length of the period to report on")
+                        .long("period")
+                        .value_name("PERIOD")
+                        .possible_values(report::PERIODS)
+                        .default_value("week"),
+                )
+                .arg(
+                    Arg::with_name("previous")
+                        .help("Report on the last full period instead of the current one")
+                        .long("previous"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("root")
+                .about("Print the root of the bucket the working directory is in")
+                .arg(
+                    Arg::with_name("name")
+                        .help("Print the name of the bucket instead")
+                        .long("name"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("prompt")
+                .about("Print the bucket and directory the working directory is in, for prompts")
+                .arg(
+                    Arg::with_name("starship")
+                        .help("Print the bucket, name and age for a starship custom module")
+                        .long("starship"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("latest")
+                .about("Print the path of the newest directory in a bucket")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Look in this bucket instead of the default")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("link")
+                        .help("Point the bucket's latest link at the newest directory")
+                        .long("link"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("link")
+                .about("Link to a directory from somewhere else")
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir")
+                        .required(true),
+                )
+                .arg(Arg::with_name("LINK").help("Path of the link, ./<dir name> by default")),
+        )
+        .subcommand(
+            SubCommand::with_name("today")
+                .about("Print the path of today's directory")
+                .alias("journal")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Look in this bucket instead of the default")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("create")
+                        .help("Create a directory for today with this name")
+                        .long("create")
+                        .value_name("NAME"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("tag")
+                .about("Manage tags of a directory")
+                .subcommand(
+                    SubCommand::with_name("add")
+                        .about("Add tags")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the dir")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("TAGS")
+                                .help("Tags to add")
+                                .required(true)
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("rm")
+                        .about("Remove tags")
+                        .arg(
+                            Arg::with_name("NAME")
+                                .help("Name of the dir")
+                                .required(true),
+                        )
+                        .arg(
+                            Arg::with_name("TAGS")
+                                .help("Tags to remove")
+                                .required(true)
+                                .multiple(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("ls").about("List tags").arg(
+                        Arg::with_name("NAME")
+                            .help("Name of the dir")
+                            .required(true),
+                    ),
+                )
+                .subcommand(
+                    SubCommand::with_name("sync")
+                        .about("Mirror the tags of all directories into Finder, on macOS"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("windows")
+                .about("Integrate zz with Windows")
+                .subcommand(
+                    SubCommand::with_name("install-shell-ext").about(
+                        "Add \"New zz directory here\" and \"Open in zz\" to the Explorer context menu",
+                    ),
+                )
+                .subcommand(
+                    SubCommand::with_name("uninstall-shell-ext")
+                        .about("Remove the zz entries from the Explorer context menu"),
+                )
+                .subcommand(
+                    SubCommand::with_name("new-here")
+                        .about("Make a directory in the bucket at PATH, asking for its name")
+                        .setting(AppSettings::Hidden)
+                        .arg(
+                            Arg::with_name("PATH")
+                                .help("Root of the bucket")
+                                .required(true),
+                        ),
+                )
+                .subcommand(
+                    SubCommand::with_name("open")
+                        .about("Open a shell with its environment in the directory at PATH")
+                        .setting(AppSettings::Hidden)
+                        .arg(
+                            Arg::with_name("PATH")
+                                .help("Path inside a directory of a bucket")
+                                .required(true),
+                        ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("mkdir")
+                .about("Make a new directory")
+                .arg(
+                    Arg::with_name("bucket")
+                        .help("Select bucket to create the directory in")
+                        .short("b")
+                        .long("bucket")
+                        .value_name("BUCKET_NAME"),
+                )
+                .arg(
+                    Arg::with_name("type")
+                        .help("Scaffold the directory as this type of project")
+                        .long("type")
+                        .value_name("TYPE")
+                        .possible_values(scaffold::PROJECT_TYPES),
+                )
+                .arg(
+                    Arg::with_name("envrc")
+                        .help("Write a .envrc for direnv")
+                        .long("envrc"),
+                )
+                .arg(
+                    Arg::with_name("private")
+                        .help("Make the directory accessible only by you")
+                        .long("private"),
+                )
+                .arg(
+                    Arg::with_name("raw")
+                        .help("Use the name as given, without cleaning it up")
+                        .long("raw"),
+                )
+                .arg(
+                    Arg::with_name("NAME")
+                        .help("Name of the dir, - to read it from stdin")
+                        .required(true),
+                ),
+        )
+}
+
+/// Check a global flag, which is recorded on the subcommand it was given to
+fn global_flag(matches: &ArgMatches, name: &str) -> bool {
+    let mut matches = matches;
+    loop {
+        if matches.is_present(name) {
+            return true;
+        }
+        match matches.subcommand() {
+            (_, Some(sub)) => matches = sub,
+            _ => return false,
+        }
+    }
+}
+
+/// Value of a global option, from the innermost subcommand that has one
+fn global_value<'a>(matches: &'a ArgMatches, name: &str) -> Option<&'a str> {
+    let mut matches = matches;
+    let mut value = matches.value_of(name);
+    while let (_, Some(sub)) = matches.subcommand() {
+        matches = sub;
+        value = matches.value_of(name).or(value);
+    }
+    value
+}
+
+/// Parse an age option of a subcommand, given in days, e.g. `30` or `30d`
+fn days(matches: &ArgMatches, name: &str) -> Result<Option<i64>, Box<dyn error::Error + 'static>> {
+    Ok(matches
+        .value_of(name)
+        .map(|days| days.strip_suffix('d').unwrap_or(days).parse::<i64>())
+        .transpose()
+        .map_err(|_| ZugzugError::new("Age must be a number of days"))?)
+}
+
+/// Parse the `--where` filter expression of a subcommand
+fn where_filter(matches: &ArgMatches) -> Result<Option<Filter>, Box<dyn error::Error + 'static>> {
+    Ok(matches.value_of("where").map(str::parse).transpose()?)
+}
+
+// parse the `-n` option of listing commands
+fn count(matches: &ArgMatches) -> Result<usize, Box<dyn error::Error + 'static>> {
+    Ok(matches
+        .value_of("count")
+        .unwrap()
+        .parse()
+        .map_err(|_| ZugzugError::new("-n must be a number"))?)
+}
+
+/// Dispatch sub-command handlers based on the parsed args
+pub fn handle_parsed_args(matches: ArgMatches) -> Result<(), Box<dyn error::Error + 'static>> {
+    match global_value(&matches, "log-level") {
+        Some(level) => log::set_level(level.parse()?),
+        None => {
+            if let Some(level) = log::env_level() {
+                log::set_level(level);
+            }
+        }
+    }
+    if global_flag(&matches, "read-only") || env::var("ZZ_READONLY").as_deref() == Ok("1") {
+        readonly::enable();
+    }
+    let format = output_format(&matches)?;
+    if let Some(color) = global_value(&matches, "color") {
+        output::set_color(color.parse()?);
+    }
+    match global_value(&matches, "style") {
+        Some(style) => output::set_style(style.parse()?),
+        None => {
+            if let Some(style) = Config::load()?.output.style {
+                output::set_style(style.parse()?);
+            }
+        }
+    }
+    i18n::init(&Config::load()?.locale);
+    dates::init(&Config::load()?.dates)?;
+    if global_flag(&matches, "yes") {
+        prompt::set_policy(prompt::Policy::Never);
+    } else if let Some(policy) = Config::load()?.confirm {
+        prompt::set_policy(policy.parse()?);
+    }
+    let mut stores = LazyStore::default();
+    let _span = span!("zz {}", command_name(&matches));
+    let result = dispatch(&matches, format, &mut stores);
+    // changes made before an error are kept, like the files they describe
+    let saved = stores.save();
+    result.and(saved)
+}
+
+// the sub-command being run, e.g. `bucket ls`
+fn command_name(matches: &ArgMatches) -> String {
+    let mut names = vec![];
+    let mut matches = matches;
+    while let (name, Some(sub)) = matches.subcommand() {
+        names.push(name);
+        matches = sub;
+    }
+    names.join(" ")
+}
+
+// the output format selected with `--format`, `--json` or `--porcelain`
+fn output_format(matches: &ArgMatches) -> Result<Format, Box<dyn error::Error + 'static>> {
+    Ok(if global_flag(matches, "json") {
+        Format::Json
+    } else if global_flag(matches, "porcelain") {
+        Format::Porcelain
+    } else {
+        global_value(matches, "format").unwrap_or("table").parse()?
+    })
+}
+
+// run the handler of the sub-command, loading the store when it needs it
+fn dispatch(
+    matches: &ArgMatches,
+    format: Format,
+    stores: &mut LazyStore,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    if let Some(matches) = matches.subcommand_matches("bucket") {
+        if let Some(matches) = matches.subcommand_matches("add") {
+            handle_bucket_add(
+                stores.get()?,
+                matches.value_of("NAME").unwrap(),
+                matches.value_of("DIR").unwrap(),
+                matches.is_present("relative-to-home"),
+                format,
+            )?;
+        } else if let Some(matches) = matches.subcommand_matches("default") {
+            handle_bucket_default(
+                stores.get()?,
+                matches.value_of("NAME"),
+                matches.is_present("unset"),
+                format,
+            )?;
+        } else if let Some(matches) = matches.subcommand_matches("forget") {
+            handle_bucket_forget(
+                stores.get()?,
+                matches.value_of("NAME").unwrap(),
+                matches.is_present("force"),
+                format,
+            )?
+        } else if let Some(matches) = matches.subcommand_matches("env") {
+            handle_bucket_env(stores.get()?, matches)?
+        } else if let Some(matches) = matches.subcommand_matches("hook") {
+            handle_bucket_hook(stores.get()?, matches)?
+        } else if let Some(matches) = matches.subcommand_matches("path") {
+            handle_bucket_path(stores.get()?, matches)?
+        } else if let Some(matches) = matches.subcommand_matches("order") {
+            let names: Vec<&str> = matches.values_of("NAMES").unwrap_or_default().collect();
+            handle_bucket_order(stores.get()?, &names, matches.is_present("reset"))?
+        } else if let Some(matches) = matches.subcommand_matches("import") {
+            handle_bucket_import(
+                stores.get()?,
+                matches.value_of("DIR").unwrap(),
+                matches.value_of("name"),
+                matches.is_present("adopt"),
+                matches.is_present("dry-run"),
+            )?
+        } else if let Some(matches) = matches.subcommand_matches("journal") {
+            handle_bucket_journal(
+                stores.get()?,
+                matches.value_of("NAME").unwrap(),
+                matches.is_present("off"),
+            )?
+        } else if let Some(matches) = matches.subcommand_matches("date-position") {
+            handle_bucket_date_position(
+                stores.get()?,
+                matches.value_of("NAME").unwrap(),
+                matches.value_of("POSITION"),
+            )?
+        } else if let Some(matches) = matches.subcommand_matches("mode") {
+            handle_bucket_mode(
+                stores.get()?,
+                matches.value_of("NAME").unwrap(),
+                matches.value_of("MODE"),
+                matches.is_present("unset"),
+            )?
+        } else if let Some(matches) = matches.subcommand_matches("mv") {
+            handle_bucket_mv(
+                stores.get()?,
+                matches.value_of("NAME").unwrap(),
+                matches.value_of("DIR").unwrap(),
+            )?
+        } else if let Some(matches) = matches.subcommand_matches("ls") {
+            handle_bucket_ls(stores.get()?, matches.is_present("size"), format)
+        }
+    } else if let Some(matches) = matches.subcommand_matches("archive") {
+        if let Some(matches) = matches.subcommand_matches("ls") {
+            handle_archive_ls(
+                stores.get()?,
+                matches.value_of("bucket"),
+                where_filter(matches)?.as_ref(),
+                format,
+            )?;
+        } else if let Some(matches) = matches.subcommand_matches("restore") {
+            handle_archive_restore(
+                stores.get()?,
+                matches.value_of("NAME").unwrap(),
+                matches.is_present("keep"),
+            )?;
+        } else if let Some(matches) = matches.subcommand_matches("mount") {
+            handle_archive_mount(
+                stores.get()?,
+                matches.value_of("NAME").unwrap(),
+                matches.is_present("extract"),
+            )?;
+        } else if let Some(matches) = matches.subcommand_matches("unmount") {
+            handle_archive_unmount(stores.get()?, matches.value_of("NAME").unwrap())?;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("count") {
+        handle_count(stores.get()?, &DirSelection::from_matches(matches)?, format)?;
+    } else if let Some(matches) = matches.subcommand_matches("dupes") {
+        handle_dupes(
+            stores.get()?,
+            matches.value_of("bucket"),
+            matches.is_present("any-name"),
+            format,
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("dedup") {
+        handle_dedup(
+            stores.get()?,
+            &DirSelection::from_matches(matches)?,
+            matches.value_of("min-size").unwrap(),
+            matches.is_present("apply"),
+            format,
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("backup") {
+        handle_backup(
+            stores.get()?,
+            &DirSelection::from_matches(matches)?,
+            matches.value_of("to"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("biggest") {
+        let count = matches
+            .value_of("count")
+            .unwrap()
+            .parse::<usize>()
+            .map_err(|_| ZugzugError::new("Count must be a positive number"))?;
+        handle_biggest(stores.get()?, count, matches.value_of("bucket"))?;
+    } else if let Some(matches) = matches.subcommand_matches("default") {
+        handle_bucket_default(
+            stores.get()?,
+            matches.value_of("NAME"),
+            matches.is_present("unset"),
+            format,
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("context") {
+        handle_context(stores.get()?, matches, format)?;
+    } else if let Some(matches) = matches.subcommand_matches("diff") {
+        handle_diff(
+            stores.get()?,
+            matches.value_of("A").unwrap(),
+            matches.value_of("B").unwrap(),
+            matches.is_present("content"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("empty") {
+        handle_empty(
+            stores.get()?,
+            matches.value_of("bucket"),
+            matches.is_present("delete"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("rmdir") {
+        let names: Vec<&str> = matches.values_of("NAMES").unwrap().collect();
+        handle_rmdir(stores.get()?, &names)?;
+    } else if let Some(matches) = matches.subcommand_matches("env") {
+        handle_env(stores.get()?, matches)?;
+    } else if let Some(matches) = matches.subcommand_matches("exec") {
+        let command: Vec<&str> = matches.values_of("COMMAND").unwrap().collect();
+        handle_exec(stores.get()?, matches.value_of("NAME").unwrap(), &command)?;
+    } else if let Some(matches) = matches.subcommand_matches("ls") {
+        let regex = match matches.value_of("regex") {
+            Some(regex) => Some(DirRegex::new(
+                regex,
+                matches.value_of("match-on").unwrap().parse()?,
+            )?),
+            None => None,
+        };
+        let view = match matches.value_of("view") {
+            Some(name) => SavedView::load(name)?,
+            None => SavedView::default(),
+        };
+        let filter = ListFilter {
+            bucket: matches.value_of("bucket"),
+            project_type: matches.value_of("type"),
+            name: matches.value_of("name"),
+            regex,
+            filter: view.filter(matches.value_of("where"))?,
+            deprecated: matches.is_present("deprecated"),
+        };
+        handle_ls(
+            stores.get()?,
+            &filter,
+            &ListView {
+                git: matches.is_present("git"),
+                journal: matches.is_present("journal"),
+                sort: matches.value_of("sort").or(view.sort.as_deref()),
+                group_by: matches.value_of("group-by").or(view.group_by.as_deref()),
+                columns: list_columns(matches.value_of("columns").or(view.columns.as_deref()))?,
+                symlinks: Symlinks::from_matches(matches)?,
+            },
+            format,
+        );
+    } else if let Some(matches) = matches.subcommand_matches("mkdir") {
+        handle_mkdir(
+            stores.get()?,
+            matches.value_of("NAME").unwrap(),
+            matches.value_of("bucket"),
+            matches.value_of("type").map(|t| t.parse()).transpose()?,
+            matches.is_present("envrc"),
+            matches.is_present("private"),
+            matches.is_present("raw"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("clone") {
+        handle_clone(
+            stores.get()?,
+            matches.value_of("NAME").unwrap(),
+            matches.value_of("NEW_NAME"),
+            matches.value_of("bucket"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("mv") {
+        handle_mv(
+            stores.get()?,
+            matches.value_of("NAME").unwrap(),
+            matches.value_of("BUCKET").unwrap(),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("merge") {
+        handle_merge(
+            stores.get()?,
+            matches.value_of("SOURCE").unwrap(),
+            matches.value_of("TARGET").unwrap(),
+            matches.value_of("on-conflict").unwrap(),
+            matches.is_present("deprecate"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("snapshot") {
+        handle_snapshot(stores.get()?, matches)?;
+    } else if let Some(matches) = matches.subcommand_matches("bump") {
+        handle_bump(stores.get()?, matches.value_of("NAME").unwrap())?;
+    } else if let Some(matches) = matches.subcommand_matches("publish") {
+        handle_publish(stores.get()?, matches.value_of("NAME").unwrap())?;
+    } else if let Some(matches) = matches.subcommand_matches("prune") {
+        handle_prune(
+            stores.get()?,
+            matches.value_of("bucket"),
+            days(matches, "older-than")?,
+            &matches
+                .values_of("keep-tag")
+                .map(|v| v.collect::<Vec<_>>())
+                .unwrap_or_default(),
+            where_filter(matches)?.as_ref(),
+            &Removal::from_matches(matches),
+            matches.is_present("dry-run"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("rm") {
+        let names: Vec<&str> = matches
+            .values_of("NAME")
+            .map(|v| v.collect())
+            .unwrap_or_default();
+        let older_than = days(matches, "older-than")?;
+        handle_rm(
+            stores.get()?,
+            &names,
+            matches.is_present("interactive"),
+            matches.value_of("bucket"),
+            older_than,
+            where_filter(matches)?.as_ref(),
+            &Removal::from_matches(matches),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("secret") {
+        handle_secret(matches)?;
+    } else if let Some(matches) = matches.subcommand_matches("store") {
+        if matches.subcommand_matches("recover").is_some() {
+            handle_store_recover()?;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("copy-path") {
+        handle_copy_path(stores.get()?, matches.value_of("NAME").unwrap())?;
+    } else if let Some(matches) = matches.subcommand_matches("reveal") {
+        handle_reveal(stores.get()?, matches.value_of("NAME").unwrap())?;
+    } else if let Some(matches) = matches.subcommand_matches("share") {
+        handle_share(
+            stores.get()?,
+            matches.value_of("NAME").unwrap(),
+            matches.value_of("type"),
+            matches.value_of("output"),
+        )?;
+    } else if matches.subcommand_matches("powershell-module").is_some() {
+        handle_powershell_module();
+    } else if let Some(matches) = matches.subcommand_matches("serve") {
+        let watch_interval = match matches.is_present("watch") {
+            true => Some(
+                matches
+                    .value_of("interval")
+                    .unwrap()
+                    .parse()
+                    .map_err(|_| ZugzugError::new("--interval takes a number of seconds"))?,
+            ),
+            false => None,
+        };
+        handle_serve(matches.value_of("socket"), watch_interval)?;
+    } else if let Some(matches) = matches.subcommand_matches("scratch") {
+        handle_scratch(stores.get()?, days(matches, "ttl")?)?;
+    } else if let Some(matches) = matches.subcommand_matches("describe") {
+        handle_describe(
+            stores.get()?,
+            matches.value_of("NAME").unwrap(),
+            matches.value_of("DESCRIPTION"),
+            matches.is_present("unset"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("deprecate") {
+        handle_deprecate(
+            stores.get()?,
+            matches.value_of("NAME").unwrap(),
+            matches.is_present("undo"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("import") {
+        if let Some(matches) = matches.subcommand_matches("zoxide") {
+            let min_score = matches
+                .value_of("min-score")
+                .unwrap()
+                .parse()
+                .map_err(|_| ZugzugError::new("--min-score must be a number"))?;
+            handle_import_zoxide(stores.get()?, matches.value_of("bucket"), min_score)?;
+        }
+    } else if let Some(matches) = matches.subcommand_matches("discover") {
+        let number = |name| {
+            matches
+                .value_of(name)
+                .unwrap()
+                .parse::<usize>()
+                .map_err(|_| ZugzugError::new(&format!("--{} must be a number", name)))
+        };
+        handle_discover(
+            stores.get()?,
+            matches.value_of("ROOT").unwrap(),
+            number("depth")?,
+            number("min")?,
+        )?;
+    } else if matches.subcommand_matches("prev").is_some() {
+        handle_step(stores.get()?, false)?;
+    } else if matches.subcommand_matches("next").is_some() {
+        handle_step(stores.get()?, true)?;
+    } else if let Some(matches) = matches.subcommand_matches("recent") {
+        handle_recent(
+            stores.get()?,
+            matches.value_of("bucket"),
+            count(matches)?,
+            matches.is_present("accessed"),
+            matches.is_present("by-date"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("stale") {
+        handle_stale(stores.get()?, matches.value_of("bucket"), count(matches)?)?;
+    } else if let Some(matches) = matches.subcommand_matches("find") {
+        handle_find(
+            stores.get()?,
+            matches.value_of("QUERY").unwrap(),
+            count(matches)?,
+            matches.is_present("first"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("foreach") {
+        let command: Vec<&str> = matches.values_of("COMMAND").unwrap().collect();
+        let parallel = matches
+            .value_of("parallel")
+            .unwrap()
+            .parse()
+            .map_err(|_| ZugzugError::new("--parallel must be a number"))?;
+        handle_foreach(
+            stores.get()?,
+            &DirSelection::from_matches(matches)?,
+            parallel,
+            &command,
+        )?;
+    } else if matches.subcommand_matches("batch").is_some() {
+        handle_batch(stores)?;
+    } else if let Some(matches) = matches.subcommand_matches("report") {
+        handle_report(
+            stores.get()?,
+            matches.value_of("period").unwrap().parse()?,
+            matches.is_present("previous"),
+            format,
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("root") {
+        handle_root(stores.get()?, matches.is_present("name"))?;
+    } else if let Some(matches) = matches.subcommand_matches("prompt") {
+        handle_prompt(stores.get()?, matches.is_present("starship"), format)?;
+    } else if let Some(matches) = matches.subcommand_matches("latest") {
+        handle_latest(
+            stores.get()?,
+            matches.value_of("bucket"),
+            matches.is_present("link"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("link") {
+        handle_link(
+            stores.get()?,
+            matches.value_of("NAME").unwrap(),
+            matches.value_of("LINK"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("today") {
+        handle_today(
+            stores.get()?,
+            matches.value_of("bucket"),
+            matches.value_of("create"),
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("tag") {
+        handle_tag(stores.get()?, matches)?;
+    } else if let Some(matches) = matches.subcommand_matches("windows") {
+        if matches.subcommand_matches("install-shell-ext").is_some() {
+            handle_install_shell_ext()?;
+        } else if matches.subcommand_matches("uninstall-shell-ext").is_some() {
+            handle_uninstall_shell_ext()?;
+        } else if let Some(matches) = matches.subcommand_matches("new-here") {
+            handle_new_here(stores.get()?, matches.value_of("PATH").unwrap())?;
+        } else if let Some(matches) = matches.subcommand_matches("open") {
+            handle_open_here(stores.get()?, matches.value_of("PATH").unwrap())?;
+        }
+    } else if let (editor, Some(matches)) = matches.subcommand() {
+        let args: Vec<&str> = matches
+            .values_of("")
+            .map(|v| v.collect())
+            .unwrap_or_default();
+        handle_editor(stores.get()?, editor, &args)?;
     }
     Ok(())
 }
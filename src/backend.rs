@@ -0,0 +1,173 @@
+use crate::errors::ZugzugError;
+use crate::lock;
+use crate::sqlite;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where the contents of a store are kept
+///
+/// Contents are passed around as the store's JSON, whatever a backend
+/// turns them into.
+pub trait StoreBackend {
+    /// File the store is kept in, next to which its lock and backups go,
+    /// or `None` when it isn't kept on disk
+    fn path(&self) -> Option<&Path>;
+
+    /// Whether the store was created already
+    fn exists(&self) -> bool;
+
+    /// Read the contents of the store
+    fn read(&self) -> Result<Vec<u8>, Box<dyn error::Error + 'static>>;
+
+    /// Read the contents of one of the store's backups
+    fn read_backup(&self, backup: &Path) -> Result<Vec<u8>, Box<dyn error::Error + 'static>>;
+
+    /// Create the store with its first contents
+    fn create(&mut self, contents: &[u8]) -> Result<(), Box<dyn error::Error + 'static>>;
+
+    /// Replace contents `old`, as last read, by `new`
+    ///
+    /// Returns the contents as `read` would now return them.
+    fn write(&mut self, old: &[u8], new: &[u8])
+        -> Result<Vec<u8>, Box<dyn error::Error + 'static>>;
+}
+
+/// Construct a path next to a store file, e.g. `.zz.json.lock`
+pub fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// A single JSON file, rewritten on every save
+pub struct JsonFile {
+    pub path: PathBuf,
+}
+
+impl StoreBackend for JsonFile {
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+        Ok(fs::read(&self.path)?)
+    }
+
+    fn read_backup(&self, backup: &Path) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+        Ok(fs::read(backup)?)
+    }
+
+    fn create(&mut self, contents: &[u8]) -> Result<(), Box<dyn error::Error + 'static>> {
+        Ok(fs::write(&self.path, contents)?)
+    }
+
+    // the new contents are renamed into place so readers never see a
+    // partial file
+    fn write(
+        &mut self,
+        _old: &[u8],
+        new: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+        let tmp = sibling(
+            &self.path,
+            &format!("tmp.{}.{}", lock::hostname(), std::process::id()),
+        );
+        fs::write(&tmp, new)?;
+        fs::rename(&tmp, &self.path)?;
+        Ok(new.to_vec())
+    }
+}
+
+/// An SQLite database, see `sqlite`
+pub struct Sqlite {
+    pub path: PathBuf,
+    /// JSON store whose contents a new database starts out with, if it
+    /// exists
+    pub import: Option<PathBuf>,
+}
+
+impl StoreBackend for Sqlite {
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+
+    fn exists(&self) -> bool {
+        self.path.exists()
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+        sqlite::read(&self.path)
+    }
+
+    fn read_backup(&self, backup: &Path) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+        sqlite::read(backup)
+    }
+
+    fn create(&mut self, contents: &[u8]) -> Result<(), Box<dyn error::Error + 'static>> {
+        match self.import.as_ref().filter(|json| json.exists()) {
+            Some(json) => {
+                info!("Importing {}", json.display());
+                sqlite::init(&self.path, &fs::read(json)?)
+            }
+            None => sqlite::init(&self.path, contents),
+        }
+    }
+
+    // only the rows that changed are written
+    fn write(
+        &mut self,
+        old: &[u8],
+        new: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+        sqlite::write(&self.path, old, new)
+    }
+}
+
+/// Contents kept in memory only, gone when the store is dropped
+#[derive(Default)]
+pub struct Memory {
+    contents: Option<Vec<u8>>,
+}
+
+impl StoreBackend for Memory {
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    fn exists(&self) -> bool {
+        self.contents.is_some()
+    }
+
+    fn read(&self) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+        match &self.contents {
+            Some(contents) => Ok(contents.clone()),
+            None => Err(Box::new(ZugzugError::new("Store was not created yet"))),
+        }
+    }
+
+    fn read_backup(&self, _backup: &Path) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+        Err(Box::new(ZugzugError::new(
+            "Stores kept in memory have no backups",
+        )))
+    }
+
+    fn create(&mut self, contents: &[u8]) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.contents = Some(contents.to_vec());
+        Ok(())
+    }
+
+    fn write(
+        &mut self,
+        _old: &[u8],
+        new: &[u8],
+    ) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+        self.contents = Some(new.to_vec());
+        Ok(new.to_vec())
+    }
+}
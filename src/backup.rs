@@ -0,0 +1,106 @@
+use crate::fsutil;
+use crate::store::Dir;
+use chrono::prelude::*;
+use std::error;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What a backup run did
+#[derive(Debug, Default)]
+pub struct BackupSummary {
+    pub snapshot: PathBuf,
+    /// Files copied because they are new or changed
+    pub copied: usize,
+    /// Files hard-linked from the previous snapshot
+    pub linked: usize,
+    /// Bytes copied
+    pub bytes: u64,
+}
+
+/// Most recent snapshot below a backup root
+fn latest_snapshot(root: &Path) -> io::Result<Option<PathBuf>> {
+    if !root.exists() {
+        return Ok(None);
+    }
+    let mut snapshots = vec![];
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            snapshots.push(entry.path());
+        }
+    }
+    snapshots.sort();
+    Ok(snapshots.pop())
+}
+
+/// Check whether a file is unchanged since it was put into a snapshot
+fn unchanged(source: &fs::Metadata, previous: &Path) -> bool {
+    match fs::symlink_metadata(previous) {
+        Ok(previous) => {
+            previous.is_file()
+                && previous.len() == source.len()
+                && previous.modified().ok() == source.modified().ok()
+        }
+        Err(_) => false,
+    }
+}
+
+/// Copy a file, keeping its modification time so the next run can tell
+/// whether it changed
+///
+/// The copy gets the source's permissions, so it is only opened for reading
+/// to set the time: read-only files like those in `.git/objects` can't be
+/// opened for writing.
+fn copy_file(from: &Path, to: &Path, metadata: &fs::Metadata) -> io::Result<u64> {
+    let bytes = fs::copy(from, to)?;
+    File::open(to)?.set_modified(metadata.modified()?)?;
+    Ok(bytes)
+}
+
+/// Back up directories into a new snapshot below `root`
+///
+/// Each run creates `<root>/<timestamp>/<bucket>/<dir name>`. Files that are
+/// unchanged since the previous snapshot (same size and modification time)
+/// are hard-linked to it instead of copied, so every snapshot is complete
+/// but only changes take up space. Symlinks are skipped.
+pub fn backup(dirs: &[Dir], root: &Path) -> Result<BackupSummary, Box<dyn error::Error + 'static>> {
+    let previous = latest_snapshot(root)?;
+    let snapshot = root.join(Local::now().format("%Y%m%dT%H%M%S").to_string());
+    fs::create_dir_all(root)?;
+    fs::create_dir(&snapshot)?;
+
+    let mut summary = BackupSummary {
+        snapshot: snapshot.clone(),
+        ..Default::default()
+    };
+    for dir in dirs {
+        let relative = Path::new(&dir.bucket).join(dir.full_name());
+        let target = snapshot.join(&relative);
+        fs::create_dir_all(&target)?;
+        for file in fsutil::walk_files(&dir.path)? {
+            let source = dir.path.join(&file);
+            let metadata = fs::symlink_metadata(&source)?;
+            if !metadata.is_file() {
+                continue;
+            }
+            let destination = target.join(&file);
+            if let Some(parent) = destination.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let old = previous.as_ref().map(|p| p.join(&relative).join(&file));
+            match old {
+                Some(old)
+                    if unchanged(&metadata, &old) && fs::hard_link(&old, &destination).is_ok() =>
+                {
+                    summary.linked += 1;
+                }
+                _ => {
+                    summary.bytes += copy_file(&source, &destination, &metadata)?;
+                    summary.copied += 1;
+                }
+            }
+        }
+    }
+    Ok(summary)
+}
@@ -0,0 +1,53 @@
+use crate::errors::ZugzugError;
+
+/// Split a command line into words like a POSIX shell
+///
+/// Words are separated by whitespace. Single quotes keep everything up to the
+/// next single quote, double quotes keep everything but `\"` and `\\`
+/// escapes, and a backslash outside quotes escapes the next character. No
+/// expansion of any kind happens.
+pub fn split(line: &str) -> Result<Vec<String>, ZugzugError> {
+    let mut words = vec![];
+    let mut word: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\'' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(ZugzugError::new("Unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\')) => word.push(c),
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(ZugzugError::new("Unterminated double quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(ZugzugError::new("Unterminated double quote")),
+                    }
+                }
+            }
+            '\\' => match chars.next() {
+                Some(c) => word.get_or_insert_with(String::new).push(c),
+                None => return Err(ZugzugError::new("Trailing backslash")),
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
+}
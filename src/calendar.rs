@@ -0,0 +1,42 @@
+use chrono::prelude::*;
+use std::collections::BTreeSet;
+
+/// Render month calendars covering the given dates
+///
+/// Only months containing at least one date are shown. Days with a date
+/// are printed with their number, all other days as `.`.
+pub fn render(dates: &BTreeSet<NaiveDate>) -> String {
+    let mut out = String::new();
+    let months: BTreeSet<(i32, u32)> = dates.iter().map(|d| (d.year(), d.month())).collect();
+    for (year, month) in months {
+        let first = NaiveDate::from_ymd_opt(year, month, 1).unwrap();
+        let title = format!("{:^20}", first.format("%B %Y").to_string());
+        out.push_str(title.trim_end());
+        out.push('\n');
+        out.push_str("Mo Tu We Th Fr Sa Su\n");
+        let offset = first.weekday().num_days_from_monday() as usize;
+        let mut line = "   ".repeat(offset);
+        let mut day = first;
+        while day.month() == month {
+            if dates.contains(&day) {
+                line.push_str(&format!("{:>2}", day.day()));
+            } else {
+                line.push_str(" .");
+            }
+            if day.weekday() == Weekday::Sun {
+                out.push_str(line.trim_end());
+                out.push('\n');
+                line.clear();
+            } else {
+                line.push(' ');
+            }
+            day = day.succ_opt().unwrap();
+        }
+        if !line.trim().is_empty() {
+            out.push_str(line.trim_end());
+            out.push('\n');
+        }
+        out.push('\n');
+    }
+    out
+}
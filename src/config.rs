@@ -0,0 +1,483 @@
+use crate::errors::ZugzugError;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::error;
+use std::fs;
+use std::path::PathBuf;
+
+/// User configuration, read from `~/.zz.toml`
+///
+/// Every section is optional, a missing file is the same as an empty one.
+///
+/// ```toml
+/// confirm = "always"
+/// ignore_case = true
+///
+/// [dates]
+/// separator = "-"
+/// display = "relative"
+///
+/// [locale]
+/// lang = "de"
+/// dir = "/etc/zz/locale"
+///
+/// [editors]
+/// code = "code"
+/// idea = "idea --wait"
+///
+/// [share]
+/// output_dir = "/home/me/outbox"
+/// format = "zip"
+///
+/// [publish]
+/// kind = "rsync"
+/// target = "me@host:/srv/scratch"
+/// url = "https://host/scratch"
+///
+/// [publish.buckets.work]
+/// kind = "webdav"
+/// target = "https://dav.example.com/work"
+/// user = "me"
+/// secret = "dav"
+///
+/// [webhooks]
+/// urls = ["https://hooks.example.com/zz"]
+/// events = ["created", "removed"]
+///
+/// [direnv]
+/// auto = true
+/// allow = true
+/// template = "layout python\nexport ZZ_DIR={dir}"
+///
+/// [store]
+/// backups = 5
+/// path = "/mnt/team/zz.json"
+/// backend = "json"
+/// xattrs = true
+/// local_files = true
+///
+/// [finder]
+/// tags = true
+/// comments = true
+///
+/// [finder.colors]
+/// keep = "red"
+///
+/// [prune]
+/// older_than = 90
+/// exclude_tags = ["keep", "demo"]
+/// delete_policy = "archive"
+///
+/// [archive]
+/// dir = "/mnt/big/zz-archive"
+/// format = "zstd"
+///
+/// [backup]
+/// root = "/mnt/backup/zz"
+///
+/// [scratch]
+/// bucket = "scratch"
+/// ttl = 1
+///
+/// [links]
+/// latest = true
+///
+/// [names]
+/// space = "_"
+/// lowercase = true
+///
+/// [zoxide]
+/// add = true
+///
+/// [ls]
+/// columns = ["bucket", "date", "name", "size", "tags"]
+/// symlinks = "skip"
+///
+/// [output]
+/// style = "bordered"
+///
+/// [mkdir]
+/// min_free = "1G"
+/// warn_free = "10G"
+///
+/// [views]
+/// stale = "age>60d and tag!=keep sort:size"
+///
+/// [secrets]
+/// backend = "age"
+/// identity = "~/.config/zz/age.key"
+/// recipients = ["age1ql3z7hjy54pw3hyww5ayyfg7zqgvc7w3j2elw8zmrj2kg5sfn9aqmcac8p"]
+///
+/// [secrets.values]
+/// dav = """
+/// -----BEGIN AGE ENCRYPTED FILE-----
+/// ...
+/// -----END AGE ENCRYPTED FILE-----
+/// """
+/// ```
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Config {
+    /// Which actions are confirmed, `always`, `destructive` (the default) or
+    /// `never`
+    pub confirm: Option<String>,
+    /// Look buckets and directories up by name regardless of case, names on
+    /// disk keep theirs
+    pub ignore_case: bool,
+    /// Editor commands by name, invoked as `zz <name> <DIR>`
+    pub editors: BTreeMap<String, String>,
+    pub share: ShareConfig,
+    pub publish: PublishConfig,
+    pub webhooks: WebhookConfig,
+    pub direnv: DirenvConfig,
+    pub store: StoreConfig,
+    pub finder: FinderConfig,
+    pub locale: LocaleConfig,
+    pub dates: DatesConfig,
+    pub prune: PruneConfig,
+    pub archive: ArchiveConfig,
+    pub backup: BackupConfig,
+    pub scratch: ScratchConfig,
+    pub links: LinksConfig,
+    pub names: NamesConfig,
+    pub zoxide: ZoxideConfig,
+    pub ls: LsConfig,
+    pub output: OutputConfig,
+    /// Listings saved by name, used as `zz ls --view <name>`
+    pub views: BTreeMap<String, String>,
+    pub mkdir: MkdirConfig,
+    pub secrets: SecretsConfig,
+}
+
+/// Defaults for `zz share`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ShareConfig {
+    /// Directory the archives are written to, the working directory if unset
+    pub output_dir: Option<String>,
+    /// Archive format, `zip`, `tar.gz`, `tar.xz` or `tar.zst`
+    pub format: Option<String>,
+}
+
+/// Where `zz publish` uploads directories to
+///
+/// The top-level target applies to every bucket without a target of its own.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct PublishConfig {
+    pub kind: Option<String>,
+    pub target: Option<String>,
+    pub url: Option<String>,
+    pub user: Option<String>,
+    pub secret: Option<String>,
+    pub buckets: BTreeMap<String, PublishTarget>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct PublishTarget {
+    /// `rsync`, `scp` or `webdav`
+    pub kind: String,
+    /// rsync/scp destination or WebDAV base URL
+    pub target: String,
+    /// Public base URL the target is served under, if any
+    pub url: Option<String>,
+    /// User to log in as, for WebDAV
+    pub user: Option<String>,
+    /// Name of the secret holding the password, see `SecretsConfig`
+    pub secret: Option<String>,
+}
+
+/// Credentials kept out of the config in plain text, see `zz secret`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct SecretsConfig {
+    /// `keyring` (the default) for the OS keyring, or `age`
+    pub backend: Option<String>,
+    /// age identity file secrets are decrypted with, `~/.zz-age.key` by
+    /// default
+    pub identity: Option<String>,
+    /// age recipients `zz secret set` encrypts to
+    pub recipients: Vec<String>,
+    /// ASCII-armored age-encrypted secrets by name
+    pub values: BTreeMap<String, String>,
+}
+
+/// Webhooks notified when directories are created or removed
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct WebhookConfig {
+    pub urls: Vec<String>,
+    /// Events to send, `created` and/or `removed`. All events if empty.
+    pub events: Vec<String>,
+}
+
+/// `.envrc` generation for new directories
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct DirenvConfig {
+    /// Write a `.envrc` on every mkdir, not just with `--envrc`
+    pub auto: bool,
+    /// Run `direnv allow` on the generated file
+    pub allow: bool,
+    /// Contents of the `.envrc`, with `{bucket}`, `{dir}` and `{name}`
+    /// placeholders. Exports ZZ_BUCKET and ZZ_DIR if unset.
+    pub template: Option<String>,
+}
+
+/// Integration with the zoxide directory jumper
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ZoxideConfig {
+    /// Run `zoxide add` for every directory zz makes or opens
+    pub add: bool,
+}
+
+/// Free space required on a bucket's filesystem to make directories in it
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct MkdirConfig {
+    /// Refuse to make directories below this, e.g. `1G`
+    pub min_free: Option<String>,
+    /// Warn below this, e.g. `10G`
+    pub warn_free: Option<String>,
+}
+
+/// Defaults for `zz ls`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct LsConfig {
+    /// Columns printed, in order. `bucket`, `date`, `name` and `path` if
+    /// empty.
+    pub columns: Vec<String>,
+    /// What to do with symlinked directories, `follow`, `mark` or `skip`.
+    /// `mark` if unset.
+    pub symlinks: Option<String>,
+}
+
+/// How output is displayed
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct OutputConfig {
+    /// Table style, `minimal`, `bordered`, `ascii` or `plain`
+    pub style: Option<String>,
+}
+
+/// Settings for the store file
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct StoreConfig {
+    /// Number of rotated backups kept next to the store
+    pub backups: usize,
+    /// Store file to use instead of `~/.zz.json`, e.g. on a shared mount
+    pub path: Option<String>,
+    /// `json`, the default, or `sqlite` for large stores. SQLite stores
+    /// live in `~/.zz.db` unless `path` is set
+    pub backend: Option<String>,
+    /// Also keep directory metadata in extended attributes on the
+    /// directories, so it travels with them when they are moved by hand
+    pub xattrs: bool,
+    /// Write a `.zz.toml` with its metadata into every directory whose
+    /// metadata changes, not only into those that have one already
+    pub local_files: bool,
+}
+
+impl Default for StoreConfig {
+    fn default() -> StoreConfig {
+        StoreConfig {
+            backups: 5,
+            path: None,
+            backend: None,
+            xattrs: false,
+            local_files: false,
+        }
+    }
+}
+
+/// Mirroring directory metadata into Finder, only done on macOS
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(default)]
+pub struct FinderConfig {
+    /// Set the tags of a directory as its Finder tags whenever they change
+    pub tags: bool,
+    /// Set the description of a directory as its Spotlight comment
+    pub comments: bool,
+    /// Finder color of tags, by tag, e.g. `keep = "red"`. Tags named after
+    /// a color, like `green`, get that color
+    pub colors: BTreeMap<String, String>,
+}
+
+/// Date prefixes of new directories and how listings show dates
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct DatesConfig {
+    /// Between year, month and day of new prefixes, nothing (the default),
+    /// `-` or `.`
+    pub separator: Option<String>,
+    /// `prefix` (the default), `iso`, `locale`, `relative` or a strftime
+    /// format
+    pub display: Option<String>,
+}
+
+/// Language of messages, see `i18n`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct LocaleConfig {
+    /// Language to use instead of the one of `LANG`, e.g. `de`
+    pub lang: Option<String>,
+    /// Directory of the message catalogs, `~/.zz-locale` if unset
+    pub dir: Option<String>,
+}
+
+/// Retention policy applied by `zz prune`
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct PruneConfig {
+    /// Remove directories whose date prefix is older than this many days
+    pub older_than: Option<i64>,
+    /// Never prune directories carrying any of these tags
+    pub exclude_tags: Vec<String>,
+    /// What `zz prune` and `zz rm` do with the directories they remove
+    pub delete_policy: DeletePolicy,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DeletePolicy {
+    /// Remove directories for good
+    #[default]
+    Delete,
+    /// Pack directories into the archive location before removing them
+    Archive,
+}
+
+/// Where removed directories are archived
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// Archive location, `~/.zz-archive` if unset
+    pub dir: Option<String>,
+    /// Compression, `gzip` (the default), `xz` or `zstd`
+    pub format: Option<String>,
+}
+
+/// Where `zz backup` keeps its snapshots
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct BackupConfig {
+    pub root: Option<String>,
+}
+
+/// Throwaway directories made by `zz scratch`
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct ScratchConfig {
+    /// Bucket for scratch directories, the default bucket if unset
+    pub bucket: Option<String>,
+    /// Days until scratch directories expire
+    pub ttl: i64,
+}
+
+impl Default for ScratchConfig {
+    fn default() -> ScratchConfig {
+        ScratchConfig {
+            bucket: None,
+            ttl: 1,
+        }
+    }
+}
+
+/// Links zz maintains inside buckets
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct LinksConfig {
+    /// Point a `latest` link in the bucket at every directory `zz mkdir` makes
+    pub latest: bool,
+}
+
+/// How `zz mkdir` cleans up directory names
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub struct NamesConfig {
+    /// What runs of whitespace are replaced with
+    pub space: String,
+    /// Lowercase names
+    pub lowercase: bool,
+}
+
+impl Default for NamesConfig {
+    fn default() -> NamesConfig {
+        NamesConfig {
+            space: "-".to_string(),
+            lowercase: false,
+        }
+    }
+}
+
+impl PublishConfig {
+    /// Find the publish target for directories in a bucket
+    pub fn target_for(&self, bucket: &str) -> Option<PublishTarget> {
+        if let Some(target) = self.buckets.get(bucket) {
+            return Some(target.clone());
+        }
+        match (&self.kind, &self.target) {
+            (Some(kind), Some(target)) => Some(PublishTarget {
+                kind: kind.clone(),
+                target: target.clone(),
+                url: self.url.clone(),
+                user: self.user.clone(),
+                secret: self.secret.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Config {
+    // construct the config file path
+    fn config_path() -> Result<PathBuf, Box<dyn error::Error + 'static>> {
+        match dirs::home_dir() {
+            Some(home) => Ok(home.join(".zz.toml")),
+            None => Err(Box::new(ZugzugError::new("Could not get home directory"))),
+        }
+    }
+
+    pub fn load() -> Result<Config, Box<dyn error::Error + 'static>> {
+        let path = Config::config_path()?;
+        if !path.exists() {
+            return Ok(Config::default());
+        }
+        let data = fs::read_to_string(&path)?;
+        toml::from_str(&data).map_err(|e| {
+            Box::new(ZugzugError::new(&format!(
+                "Invalid config {}: {}",
+                path.display(),
+                e
+            ))) as Box<dyn error::Error + 'static>
+        })
+    }
+
+    /// Location archives are written to
+    pub fn archive_dir(&self) -> Result<PathBuf, Box<dyn error::Error + 'static>> {
+        match (&self.archive.dir, dirs::home_dir()) {
+            (Some(dir), _) => Ok(PathBuf::from(dir)),
+            (None, Some(home)) => Ok(home.join(".zz-archive")),
+            (None, None) => Err(Box::new(ZugzugError::new("Could not get home directory"))),
+        }
+    }
+
+    /// Location archives are mounted or extracted to by `zz archive mount`
+    pub fn archive_browse_dir(&self) -> Result<PathBuf, Box<dyn error::Error + 'static>> {
+        match dirs::cache_dir() {
+            Some(cache) => Ok(cache.join("zz").join("archives")),
+            None => Ok(self.archive_dir()?.join(".browse")),
+        }
+    }
+
+    /// Split the configured command for an editor into program and arguments
+    pub fn editor_command(&self, name: &str) -> Option<Vec<String>> {
+        self.editors
+            .get(name)
+            .map(|command| command.split_whitespace().map(String::from).collect())
+            .filter(|parts: &Vec<String>| !parts.is_empty())
+    }
+}
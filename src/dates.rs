@@ -0,0 +1,190 @@
+use crate::config::DatesConfig;
+use crate::errors::ZugzugError;
+use chrono::format::{Item, StrftimeItems};
+use chrono::prelude::*;
+use std::env;
+use std::fmt::Write;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+/// How dates are shown in listings
+#[derive(Debug, Clone, PartialEq)]
+pub enum DateDisplay {
+    /// As in the directory name, the default
+    Prefix,
+    /// `2025-03-01`
+    Iso,
+    /// The usual order of the language in `LC_TIME` or `LANG`, e.g.
+    /// `01.03.2025` in German
+    Locale,
+    /// `today`, `yesterday`, `3 days ago` and so on
+    Relative,
+    /// A strftime format of its own, e.g. `%d %b %Y`
+    Format(String),
+}
+
+impl FromStr for DateDisplay {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<DateDisplay, ZugzugError> {
+        match s {
+            "prefix" => Ok(DateDisplay::Prefix),
+            "iso" => Ok(DateDisplay::Iso),
+            "locale" => Ok(DateDisplay::Locale),
+            "relative" => Ok(DateDisplay::Relative),
+            _ if s.contains('%') => {
+                check_format(s)?;
+                Ok(DateDisplay::Format(s.to_string()))
+            }
+            _ => Err(ZugzugError::new(&format!(
+                "Unknown date display '{}', expected prefix, iso, locale, relative or a strftime format",
+                s
+            ))),
+        }
+    }
+}
+
+// reject strftime formats chrono can't render for a date alone, like
+// `%H:%M` or unknown specifiers, which would panic on every listing
+fn check_format(format: &str) -> Result<(), ZugzugError> {
+    let invalid = || ZugzugError::new(&format!("Invalid date display format '{}'", format));
+    if StrftimeItems::new(format).any(|item| item == Item::Error) {
+        return Err(invalid());
+    }
+    // time specifiers parse fine but fail once a date is formatted
+    let mut rendered = String::new();
+    let sample = NaiveDate::from_ymd_opt(2025, 3, 1).unwrap();
+    match write!(
+        rendered,
+        "{}",
+        sample.format_with_items(StrftimeItems::new(format))
+    ) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(invalid()),
+    }
+}
+
+struct Settings {
+    /// Between year, month and day in new date prefixes
+    separator: String,
+    display: DateDisplay,
+}
+
+static SETTINGS: OnceLock<Settings> = OnceLock::new();
+
+/// Separators that may appear between the parts of a prefix
+///
+/// `_` ends the date, so it can't separate its parts.
+const SEPARATORS: &[&str] = &["", "-", "."];
+
+/// Use the `[dates]` settings for the rest of the process
+pub fn init(config: &DatesConfig) -> Result<(), ZugzugError> {
+    let separator = config.separator.clone().unwrap_or_default();
+    if !SEPARATORS.contains(&separator.as_str()) {
+        return Err(ZugzugError::new(&format!(
+            "Unsupported date separator '{}', expected nothing, - or .",
+            separator
+        )));
+    }
+    let display = match &config.display {
+        Some(display) => display.parse()?,
+        None => DateDisplay::Prefix,
+    };
+    let _ = SETTINGS.set(Settings { separator, display });
+    Ok(())
+}
+
+fn settings() -> &'static Settings {
+    SETTINGS.get_or_init(|| Settings {
+        separator: String::new(),
+        display: DateDisplay::Prefix,
+    })
+}
+
+/// The prefix of a directory made on `date`, e.g. `20250301` or
+/// `2025-03-01` with separator `-`
+pub fn prefix(date: NaiveDate) -> String {
+    let separator = &settings().separator;
+    date.format(&format!("%Y{0}%m{0}%d", separator)).to_string()
+}
+
+/// The prefix of `date` in every supported form, the configured one first
+pub fn prefixes(date: NaiveDate) -> Vec<String> {
+    let mut prefixes = vec![prefix(date)];
+    for separator in SEPARATORS {
+        let other = date.format(&format!("%Y{0}%m{0}%d", separator)).to_string();
+        if !prefixes.contains(&other) {
+            prefixes.push(other);
+        }
+    }
+    prefixes
+}
+
+/// Parse a date prefix in any of the supported forms
+///
+/// Whatever the configured separator, `20250301`, `2025-03-01` and
+/// `2025.03.01` are all understood, so directories made before the
+/// separator changed keep their dates.
+pub fn parse(prefix: &str) -> Option<NaiveDate> {
+    SEPARATORS.iter().find_map(|separator| {
+        let format = format!("%Y{0}%m{0}%d", separator);
+        let expected_len = 8 + 2 * separator.len();
+        match prefix.len() == expected_len {
+            true => NaiveDate::parse_from_str(prefix, &format).ok(),
+            false => None,
+        }
+    })
+}
+
+/// A date prefix as shown in listings, according to `dates.display`
+///
+/// Prefixes that aren't dates are shown as they are.
+pub fn display(prefix: &str) -> String {
+    let date = match parse(prefix) {
+        Some(date) => date,
+        None => return prefix.to_string(),
+    };
+    match &settings().display {
+        DateDisplay::Prefix => prefix.to_string(),
+        DateDisplay::Iso => date.format("%Y-%m-%d").to_string(),
+        DateDisplay::Locale => date.format(locale_format()).to_string(),
+        DateDisplay::Relative => relative(date),
+        DateDisplay::Format(format) => date.format(format).to_string(),
+    }
+}
+
+// the usual order of day, month and year for the language of LC_TIME
+fn locale_format() -> &'static str {
+    let lang = ["LC_ALL", "LC_TIME", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let lang = lang.split(['.', '@']).next().unwrap_or_default();
+    match lang {
+        "en_US" => "%m/%d/%Y",
+        _ => match lang.split(['_', '-']).next().unwrap_or_default() {
+            "de" | "fi" | "nb" | "no" | "pl" | "ru" | "cs" | "tr" | "uk" => "%d.%m.%Y",
+            "en" | "fr" | "es" | "it" | "pt" | "el" => "%d/%m/%Y",
+            "nl" => "%d-%m-%Y",
+            "ja" | "zh" => "%Y/%m/%d",
+            "ko" => "%Y. %m. %d.",
+            _ => "%Y-%m-%d",
+        },
+    }
+}
+
+// how long ago a date was, in the largest unit that fits
+fn relative(date: NaiveDate) -> String {
+    let days = (Local::now().date_naive() - date).num_days();
+    match days {
+        i64::MIN..=-1 => date.format("%Y-%m-%d").to_string(),
+        0 => t!("today"),
+        1 => t!("yesterday"),
+        2..=13 => t!("{count} days ago", count = days),
+        14..=59 => t!("{count} weeks ago", count = days / 7),
+        60..=364 => t!("{count} months ago", count = days / 30),
+        365..=729 => t!("1 year ago"),
+        _ => t!("{count} years ago", count = days / 365),
+    }
+}
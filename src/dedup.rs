@@ -0,0 +1,166 @@
+use crate::fsutil;
+use crate::progress::Progress;
+use crate::snapshot;
+use crate::store::Dir;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::hash::Hasher;
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+/// Files with the same contents, not yet linked to each other
+#[derive(Debug)]
+pub struct Duplicate {
+    pub size: u64,
+    /// The copy the others are linked to
+    pub keep: PathBuf,
+    pub copies: Vec<PathBuf>,
+    /// Bytes freed by linking the copies, less than `size` for every copy
+    /// when some are also linked from elsewhere
+    pub saved: u64,
+}
+
+// a file and where it lives on disk, so hard links of each other are only
+// looked at once
+struct Candidate {
+    path: PathBuf,
+    device: u64,
+    inode: u64,
+    links: u64,
+}
+
+#[cfg(unix)]
+fn candidate(path: PathBuf, metadata: &fs::Metadata) -> Candidate {
+    use std::os::unix::fs::MetadataExt;
+    Candidate {
+        path,
+        device: metadata.dev(),
+        inode: metadata.ino(),
+        links: metadata.nlink(),
+    }
+}
+
+// without inode numbers every file counts as its own, and linking across
+// volumes fails when it is tried
+#[cfg(not(unix))]
+fn candidate(path: PathBuf, _metadata: &fs::Metadata) -> Candidate {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    Candidate {
+        path,
+        device: 0,
+        inode: NEXT.fetch_add(1, Ordering::SeqCst),
+        links: 1,
+    }
+}
+
+// hash of a file's contents, to tell apart files of the same size
+fn hash_file(path: &Path) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = DefaultHasher::new();
+    loop {
+        let len = {
+            let buf = reader.fill_buf()?;
+            if buf.is_empty() {
+                return Ok(hasher.finish());
+            }
+            hasher.write(buf);
+            buf.len()
+        };
+        reader.consume(len);
+    }
+}
+
+/// Find files of at least `min_size` bytes with the same contents across
+/// directories
+///
+/// Files are grouped by size and filesystem, then by a hash of their
+/// contents, and copies are compared byte by byte with the one kept before
+/// they count. Symlinks, empty files and snapshots are left alone. Biggest
+/// savings come first.
+pub fn find(dirs: &[Dir], min_size: u64) -> io::Result<Vec<Duplicate>> {
+    let _span = span!("find duplicates in {} directories", dirs.len());
+    let mut by_size: HashMap<(u64, u64), Vec<Candidate>> = HashMap::new();
+    for dir in dirs {
+        for file in fsutil::walk_files(&dir.path)? {
+            if file.starts_with(snapshot::DIR) {
+                continue;
+            }
+            let path = dir.path.join(file);
+            let metadata = fs::symlink_metadata(&path)?;
+            if !metadata.is_file() || metadata.len() == 0 || metadata.len() < min_size {
+                continue;
+            }
+            let candidate = candidate(path, &metadata);
+            let same_size = by_size
+                .entry((candidate.device, metadata.len()))
+                .or_default();
+            if !same_size.iter().any(|c| c.inode == candidate.inode) {
+                same_size.push(candidate);
+            }
+        }
+    }
+    by_size.retain(|_, candidates| candidates.len() > 1);
+
+    let mut progress = Progress::new("Hashing files", by_size.values().map(Vec::len).sum());
+    let mut duplicates = vec![];
+    for ((_, size), candidates) in by_size {
+        let mut by_hash: HashMap<u64, Vec<Candidate>> = HashMap::new();
+        for candidate in candidates {
+            progress.inc();
+            match hash_file(&candidate.path) {
+                Ok(hash) => by_hash.entry(hash).or_default().push(candidate),
+                Err(e) => warn!("Unable to read {}: {}", candidate.path.display(), e),
+            }
+        }
+        for (_, mut same) in by_hash {
+            if same.len() < 2 {
+                continue;
+            }
+            // keeping the most linked copy frees the most space
+            same.sort_by(|a, b| b.links.cmp(&a.links).then(a.path.cmp(&b.path)));
+            let keep = same.remove(0);
+            let mut duplicate = Duplicate {
+                size,
+                keep: keep.path,
+                copies: vec![],
+                saved: 0,
+            };
+            for copy in same {
+                if !fsutil::files_equal(&duplicate.keep, &copy.path)? {
+                    continue;
+                }
+                if copy.links == 1 {
+                    duplicate.saved += size;
+                }
+                duplicate.copies.push(copy.path);
+            }
+            if !duplicate.copies.is_empty() {
+                duplicates.push(duplicate);
+            }
+        }
+    }
+    progress.finish();
+    duplicates.sort_by(|a, b| b.saved.cmp(&a.saved).then(a.keep.cmp(&b.keep)));
+    Ok(duplicates)
+}
+
+/// Replace the copies of a duplicate by hard links to the one kept
+///
+/// Each link is made next to its copy and renamed over it, so a copy is
+/// never missing. The kept file's permissions and times then apply to all
+/// of them.
+pub fn link(duplicate: &Duplicate) -> io::Result<()> {
+    for copy in &duplicate.copies {
+        let mut name = copy.file_name().unwrap_or_default().to_os_string();
+        name.push(".zz-dedup");
+        let tmp = copy.with_file_name(name);
+        fs::hard_link(&duplicate.keep, &tmp)?;
+        if let Err(e) = fs::rename(&tmp, copy) {
+            let _ = fs::remove_file(&tmp);
+            return Err(e);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,52 @@
+use crate::fsutil;
+use std::collections::BTreeSet;
+use std::error;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Summary of the differences between two directory trees
+pub struct DirDiff {
+    pub added: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+    pub changed: Vec<PathBuf>,
+}
+
+impl DirDiff {
+    /// Compare the files below `a` with the files below `b`
+    ///
+    /// Files only in `b` are added, files only in `a` are removed and files
+    /// in both with different contents are changed.
+    pub fn compute(a: &Path, b: &Path) -> Result<DirDiff, Box<dyn error::Error + 'static>> {
+        let a_files: BTreeSet<PathBuf> = fsutil::walk_files(a)?.into_iter().collect();
+        let b_files: BTreeSet<PathBuf> = fsutil::walk_files(b)?.into_iter().collect();
+        let mut changed = vec![];
+        for file in a_files.intersection(&b_files) {
+            if !fsutil::files_equal(&a.join(file), &b.join(file))? {
+                changed.push(file.clone());
+            }
+        }
+        Ok(DirDiff {
+            added: b_files.difference(&a_files).cloned().collect(),
+            removed: a_files.difference(&b_files).cloned().collect(),
+            changed,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Print a unified diff of a file in two trees using the system `diff`
+pub fn print_content_diff(
+    a: &Path,
+    b: &Path,
+    file: &Path,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    Command::new("diff")
+        .arg("-u")
+        .arg(a.join(file))
+        .arg(b.join(file))
+        .status()?;
+    Ok(())
+}
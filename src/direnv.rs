@@ -0,0 +1,45 @@
+use crate::config::DirenvConfig;
+use crate::errors::ZugzugError;
+use std::error;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+const DEFAULT_TEMPLATE: &str = "export ZZ_BUCKET=\"{bucket}\"\nexport ZZ_DIR=\"{dir}\"\n";
+
+/// Write a `.envrc` into a new directory from the configured template
+///
+/// The template may use `{bucket}`, `{dir}` and `{name}` placeholders. When
+/// `allow` is configured, `direnv allow` is run on the result.
+pub fn write_envrc(
+    config: &DirenvConfig,
+    dir: &Path,
+    bucket: &str,
+    name: &str,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let template = config.template.as_deref().unwrap_or(DEFAULT_TEMPLATE);
+    let mut contents = template
+        .replace("{bucket}", bucket)
+        .replace("{dir}", &dir.to_string_lossy())
+        .replace("{name}", name);
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    let envrc = dir.join(".envrc");
+    fs::write(&envrc, contents)?;
+
+    if config.allow {
+        let status = Command::new("direnv")
+            .arg("allow")
+            .arg(&envrc)
+            .status()
+            .map_err(|e| ZugzugError::new(&format!("Unable to run direnv: {}", e)))?;
+        if !status.success() {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "direnv exited with {}",
+                status
+            ))));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use crate::errors::ZugzugError;
+use crate::store::Store;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Parse a `KEY=VALUE` assignment
+pub fn parse_assignment(assignment: &str) -> Result<(String, String), ZugzugError> {
+    match assignment.find('=') {
+        Some(i) if i > 0 => Ok((assignment[..i].to_string(), assignment[i + 1..].to_string())),
+        _ => Err(ZugzugError::new(&format!(
+            "Expected KEY=VALUE, got '{}'",
+            assignment
+        ))),
+    }
+}
+
+/// Quote a value for POSIX shells
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// The environment to run commands with inside a directory
+///
+/// Always includes `ZZ_BUCKET` and `ZZ_DIR`, followed by the variables
+/// inherited from the bucket, and finally the directory's own variables.
+pub fn dir_env(store: &Store, bucket: &str, path: &Path) -> BTreeMap<String, String> {
+    let mut env = BTreeMap::new();
+    env.insert("ZZ_BUCKET".to_string(), bucket.to_string());
+    env.insert("ZZ_DIR".to_string(), path.to_string_lossy().into_owned());
+    if let Some(bucket) = store.find_bucket(bucket) {
+        env.extend(bucket.env.clone());
+    }
+    if let Some(meta) = store.dir_meta(path) {
+        env.extend(meta.env.clone());
+    }
+    env
+}
@@ -0,0 +1,600 @@
+use crate::dates;
+use crate::errors::ZugzugError;
+use crate::fsutil;
+use crate::pattern::glob_match;
+use crate::store::{ArchivedDir, Dir, DirMeta, Store};
+use chrono::prelude::*;
+use regex::Regex;
+use std::cell::OnceCell;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+
+/// What a filter can ask about a directory
+pub trait Subject {
+    fn bucket(&self) -> &str;
+    fn name(&self) -> &str;
+    fn date(&self) -> Option<NaiveDate>;
+    /// Size in bytes, if it can be found out
+    fn size(&self) -> Option<u64>;
+    fn tags(&self) -> BTreeSet<String>;
+    fn project_type(&self) -> Option<String>;
+    fn description(&self) -> Option<String>;
+    /// Days since the directory was last opened through zz
+    fn accessed_days(&self) -> Option<i64>;
+}
+
+/// A tracked directory as seen by a filter
+///
+/// Metadata and size are only looked up when a filter asks for them, so
+/// `name=foo*` never walks the directory tree.
+pub struct DirSubject<'a> {
+    dir: &'a Dir,
+    store: &'a Store,
+    meta: OnceCell<DirMeta>,
+    size: OnceCell<Option<u64>>,
+}
+
+impl<'a> DirSubject<'a> {
+    pub fn new(dir: &'a Dir, store: &'a Store) -> DirSubject<'a> {
+        DirSubject {
+            dir,
+            store,
+            meta: OnceCell::new(),
+            size: OnceCell::new(),
+        }
+    }
+
+    fn meta(&self) -> &DirMeta {
+        self.meta
+            .get_or_init(|| self.store.dir_meta(&self.dir.path).unwrap_or_default())
+    }
+}
+
+impl Subject for DirSubject<'_> {
+    fn bucket(&self) -> &str {
+        &self.dir.bucket
+    }
+
+    fn name(&self) -> &str {
+        &self.dir.name
+    }
+
+    fn date(&self) -> Option<NaiveDate> {
+        self.dir.naive_date()
+    }
+
+    fn size(&self) -> Option<u64> {
+        *self
+            .size
+            .get_or_init(|| fsutil::dir_size(&self.dir.path).ok())
+    }
+
+    fn tags(&self) -> BTreeSet<String> {
+        self.meta().tags.clone()
+    }
+
+    fn project_type(&self) -> Option<String> {
+        self.meta().project_type.clone()
+    }
+
+    fn description(&self) -> Option<String> {
+        self.meta().description.clone()
+    }
+
+    fn accessed_days(&self) -> Option<i64> {
+        let accessed = self.store.last_accessed(&self.dir.path)?;
+        Some((Local::now().fixed_offset() - accessed).num_days())
+    }
+}
+
+impl Subject for ArchivedDir {
+    fn bucket(&self) -> &str {
+        &self.bucket
+    }
+
+    fn name(&self) -> &str {
+        self.date_and_name().1
+    }
+
+    fn date(&self) -> Option<NaiveDate> {
+        dates::parse(self.date_and_name().0)
+    }
+
+    fn size(&self) -> Option<u64> {
+        Some(self.size)
+    }
+
+    fn tags(&self) -> BTreeSet<String> {
+        BTreeSet::new()
+    }
+
+    fn project_type(&self) -> Option<String> {
+        None
+    }
+
+    fn description(&self) -> Option<String> {
+        None
+    }
+
+    fn accessed_days(&self) -> Option<i64> {
+        None
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Age,
+    Accessed,
+    Size,
+    Date,
+    Tag,
+    Name,
+    Bucket,
+    Type,
+    Description,
+}
+
+pub const FIELDS: &[&str] = &[
+    "age",
+    "accessed",
+    "size",
+    "date",
+    "tag",
+    "name",
+    "bucket",
+    "type",
+    "description",
+];
+
+impl fmt::Display for Field {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // variants are in the order of `FIELDS`
+        write!(f, "{}", FIELDS[*self as usize])
+    }
+}
+
+impl FromStr for Field {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<Field, ZugzugError> {
+        match s.to_lowercase().as_str() {
+            "age" => Ok(Field::Age),
+            "accessed" => Ok(Field::Accessed),
+            "size" => Ok(Field::Size),
+            "date" => Ok(Field::Date),
+            "tag" => Ok(Field::Tag),
+            "name" => Ok(Field::Name),
+            "bucket" => Ok(Field::Bucket),
+            "type" => Ok(Field::Type),
+            "description" => Ok(Field::Description),
+            _ => Err(ZugzugError::new(&format!(
+                "Unknown filter field '{}', expected one of: {}",
+                s,
+                FIELDS.join(", ")
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Gt,
+    Ge,
+    Lt,
+    Le,
+    Match,
+    NotMatch,
+}
+
+impl Op {
+    fn symbol(self) -> &'static str {
+        match self {
+            Op::Eq => "=",
+            Op::Ne => "!=",
+            Op::Gt => ">",
+            Op::Ge => ">=",
+            Op::Lt => "<",
+            Op::Le => "<=",
+            Op::Match => "~",
+            Op::NotMatch => "!~",
+        }
+    }
+
+    fn is_ordering(self) -> bool {
+        matches!(self, Op::Gt | Op::Ge | Op::Lt | Op::Le)
+    }
+
+    fn is_regex(self) -> bool {
+        matches!(self, Op::Match | Op::NotMatch)
+    }
+
+    fn compare<T: PartialOrd>(self, a: T, b: T) -> bool {
+        match self {
+            Op::Eq => a == b,
+            Op::Ne => a != b,
+            Op::Gt => a > b,
+            Op::Ge => a >= b,
+            Op::Lt => a < b,
+            Op::Le => a <= b,
+            Op::Match | Op::NotMatch => false,
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Value {
+    Days(i64),
+    Bytes(u64),
+    Date(NaiveDate),
+    Text(String),
+    Regex(Regex),
+}
+
+#[derive(Debug)]
+enum Expr {
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Test(Field, Op, Value),
+}
+
+/// Parse a duration like `30`, `30d`, `2w`, `6m` or `1y` into days
+fn parse_days(s: &str) -> Option<i64> {
+    let (number, unit) = match s.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&s[..i], c.to_ascii_lowercase()),
+        _ => (s, 'd'),
+    };
+    let number: i64 = number.parse().ok()?;
+    let days = match unit {
+        'd' => 1,
+        'w' => 7,
+        'm' => 30,
+        'y' => 365,
+        _ => return None,
+    };
+    number.checked_mul(days)
+}
+
+impl Expr {
+    fn test(field: Field, op: Op, value: &str) -> Result<Expr, ZugzugError> {
+        let invalid = |what: &str| {
+            ZugzugError::new(&format!(
+                "'{}' is not a valid {} for {}",
+                value, what, field
+            ))
+        };
+        let value = match field {
+            _ if op.is_regex() => {
+                if matches!(
+                    field,
+                    Field::Age | Field::Accessed | Field::Size | Field::Date
+                ) {
+                    return Err(ZugzugError::new(&format!(
+                        "{} can't be matched against a regex",
+                        field
+                    )));
+                }
+                Value::Regex(Regex::new(value).map_err(|_| invalid("regex"))?)
+            }
+            Field::Age | Field::Accessed => {
+                Value::Days(parse_days(value).ok_or_else(|| invalid("duration"))?)
+            }
+            Field::Size => Value::Bytes(fsutil::parse_size(value).ok_or_else(|| invalid("size"))?),
+            Field::Date => Value::Date(dates::parse(value).ok_or_else(|| invalid("date"))?),
+            _ if op.is_ordering() => {
+                return Err(ZugzugError::new(&format!(
+                    "{} can only be compared with =, !=, ~ and !~",
+                    field
+                )))
+            }
+            _ => Value::Text(value.to_string()),
+        };
+        Ok(Expr::Test(field, op, value))
+    }
+
+    fn matches(&self, subject: &dyn Subject) -> bool {
+        match self {
+            Expr::And(a, b) => a.matches(subject) && b.matches(subject),
+            Expr::Or(a, b) => a.matches(subject) || b.matches(subject),
+            Expr::Not(e) => !e.matches(subject),
+            Expr::Test(field, op, value) => test(subject, *field, *op, value),
+        }
+    }
+}
+
+/// Compare a text field; `=` and `!=` take globs
+fn test_text(text: &str, op: Op, value: &Value) -> bool {
+    match (op, value) {
+        (Op::Eq, Value::Text(glob)) => glob_match(glob, text),
+        (Op::Ne, Value::Text(glob)) => !glob_match(glob, text),
+        (Op::Match, Value::Regex(regex)) => regex.is_match(text),
+        (Op::NotMatch, Value::Regex(regex)) => !regex.is_match(text),
+        _ => false,
+    }
+}
+
+fn test(subject: &dyn Subject, field: Field, op: Op, value: &Value) -> bool {
+    match (field, value) {
+        (Field::Age, Value::Days(days)) => {
+            let today = Local::now().date_naive();
+            subject
+                .date()
+                .is_some_and(|date| op.compare((today - date).num_days(), *days))
+        }
+        // never opened counts as opened infinitely long ago
+        (Field::Accessed, Value::Days(days)) => {
+            op.compare(subject.accessed_days().unwrap_or(i64::MAX), *days)
+        }
+        (Field::Size, Value::Bytes(bytes)) => {
+            subject.size().is_some_and(|size| op.compare(size, *bytes))
+        }
+        (Field::Date, Value::Date(date)) => {
+            subject.date().is_some_and(|own| op.compare(own, *date))
+        }
+        (Field::Tag, _) => {
+            let tags = subject.tags();
+            match (op, value) {
+                (Op::Eq, Value::Text(glob)) => tags.iter().any(|tag| glob_match(glob, tag)),
+                (Op::Ne, Value::Text(glob)) => !tags.iter().any(|tag| glob_match(glob, tag)),
+                (Op::Match, Value::Regex(regex)) => tags.iter().any(|tag| regex.is_match(tag)),
+                (Op::NotMatch, Value::Regex(regex)) => !tags.iter().any(|tag| regex.is_match(tag)),
+                _ => false,
+            }
+        }
+        (Field::Name, _) => test_text(subject.name(), op, value),
+        (Field::Bucket, _) => test_text(subject.bucket(), op, value),
+        (Field::Type, _) => test_text(&subject.project_type().unwrap_or_default(), op, value),
+        (Field::Description, _) => test_text(&subject.description().unwrap_or_default(), op, value),
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open,
+    Close,
+    Op(Op),
+    Word(String),
+    /// A quoted string, never taken for a keyword
+    Quoted(String),
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+            Token::Op(op) => write!(f, "{}", op.symbol()),
+            Token::Word(word) => write!(f, "'{}'", word),
+            Token::Quoted(quoted) => write!(f, "\"{}\"", quoted),
+        }
+    }
+}
+
+/// Characters ending an unquoted word
+const SPECIAL: &[char] = &['(', ')', '=', '!', '<', '>', '~', '"', '\''];
+
+fn tokenize(input: &str) -> Result<Vec<Token>, ZugzugError> {
+    let mut tokens = vec![];
+    let mut chars = input.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        // values after an operator run to the next space, so regexes and
+        // globs need no quoting, up to a `)` closing an enclosing group
+        if matches!(tokens.last(), Some(Token::Op(_))) && c != '"' && c != '\'' {
+            let mut value = String::new();
+            let mut depth = 0;
+            while let Some(&next) = chars.peek() {
+                match next {
+                    _ if next.is_whitespace() => break,
+                    ')' if depth == 0 => break,
+                    ')' => depth -= 1,
+                    '(' => depth += 1,
+                    _ => {}
+                }
+                value.push(next);
+                chars.next();
+            }
+            tokens.push(Token::Word(value));
+            continue;
+        }
+        chars.next();
+        let next_is_eq = chars.peek() == Some(&'=');
+        let token = match c {
+            '(' => Token::Open,
+            ')' => Token::Close,
+            '=' => {
+                // accept `==` as well
+                if next_is_eq {
+                    chars.next();
+                }
+                Token::Op(Op::Eq)
+            }
+            '~' => Token::Op(Op::Match),
+            '!' if next_is_eq => {
+                chars.next();
+                Token::Op(Op::Ne)
+            }
+            '!' if chars.peek() == Some(&'~') => {
+                chars.next();
+                Token::Op(Op::NotMatch)
+            }
+            '>' | '<' => {
+                if next_is_eq {
+                    chars.next();
+                }
+                Token::Op(match (c, next_is_eq) {
+                    ('>', true) => Op::Ge,
+                    ('>', false) => Op::Gt,
+                    ('<', true) => Op::Le,
+                    _ => Op::Lt,
+                })
+            }
+            '"' | '\'' => {
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some('\\') if c == '"' => match chars.next() {
+                            Some(escaped) => quoted.push(escaped),
+                            None => break,
+                        },
+                        Some(other) => quoted.push(other),
+                        None => {
+                            return Err(ZugzugError::new(&format!(
+                                "Unterminated {} quote in filter",
+                                c
+                            )))
+                        }
+                    }
+                }
+                Token::Quoted(quoted)
+            }
+            '!' => return Err(ZugzugError::new("Expected != or !~ in filter")),
+            _ => {
+                let mut word = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || SPECIAL.contains(&next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                Token::Word(word)
+            }
+        };
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    // or := and ("or" and)*
+    fn or(&mut self) -> Result<Expr, ZugzugError> {
+        let mut expr = self.and()?;
+        while self.keyword("or") {
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    // and := unary ("and" unary)*
+    fn and(&mut self) -> Result<Expr, ZugzugError> {
+        let mut expr = self.unary()?;
+        while self.keyword("and") {
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    // unary := "not" unary | "(" or ")" | field op value
+    fn unary(&mut self) -> Result<Expr, ZugzugError> {
+        if self.keyword("not") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        match self.next() {
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(expr),
+                    _ => Err(ZugzugError::new("Expected ) in filter")),
+                }
+            }
+            Some(Token::Word(field)) => {
+                let field: Field = field.parse()?;
+                let op = match self.next() {
+                    Some(Token::Op(op)) => op,
+                    _ => {
+                        return Err(ZugzugError::new(&format!(
+                            "Expected an operator after {} in filter",
+                            field
+                        )))
+                    }
+                };
+                match self.next() {
+                    Some(Token::Word(value)) | Some(Token::Quoted(value)) => {
+                        Expr::test(field, op, &value)
+                    }
+                    _ => Err(ZugzugError::new(&format!(
+                        "Expected a value for {} in filter",
+                        field
+                    ))),
+                }
+            }
+            Some(token) => Err(ZugzugError::new(&format!("Unexpected {} in filter", token))),
+            None => Err(ZugzugError::new("Filter ended early")),
+        }
+    }
+}
+
+/// A parsed filter expression, e.g. `age>30d and size>1G and tag!=keep`
+///
+/// Tests compare a field with `=`, `!=`, `>`, `>=`, `<`, `<=`, or, for text,
+/// `~` and `!~` against a regex. Tests combine with `and`, `or`, `not` and
+/// parentheses.
+///
+/// - `age` and `accessed` take days, optionally as `2w`, `6m` or `1y`
+/// - `size` takes bytes, optionally as `10K`, `500M` or `1.5G`
+/// - `date` takes `YYYYMMDD`, `YYYY-MM-DD` or `YYYY.MM.DD`
+/// - `name`, `bucket`, `type`, `description` and `tag` take globs
+///
+/// `tag=keep` holds when any tag matches and `tag!=keep` when none does.
+/// Directories without a date never match `age` or `date` tests.
+#[derive(Debug)]
+pub struct Filter(Expr);
+
+impl Filter {
+    pub fn matches(&self, subject: &dyn Subject) -> bool {
+        self.0.matches(subject)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<Filter, ZugzugError> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            pos: 0,
+        };
+        let expr = parser.or()?;
+        match parser.next() {
+            None => Ok(Filter(expr)),
+            Some(token) => Err(ZugzugError::new(&format!(
+                "Unexpected {} in filter, expected and or or",
+                token
+            ))),
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use crate::config::FinderConfig;
+use crate::store::DirMeta;
+use std::path::Path;
+
+/// Finder label colors, by their number in Finder tags
+#[cfg(target_os = "macos")]
+const COLORS: &[&str] = &[
+    "none", "gray", "green", "purple", "blue", "yellow", "red", "orange",
+];
+
+/// Extended attribute Finder keeps the tags of a file in
+#[cfg(target_os = "macos")]
+const TAGS_ATTRIBUTE: &str = "com.apple.metadata:_kMDItemUserTags";
+
+/// Whether directories can be mirrored into Finder on this platform
+pub fn supported() -> bool {
+    cfg!(target_os = "macos")
+}
+
+/// Mirror the tags and description of a directory into Finder
+///
+/// What is mirrored depends on `finder.tags` and `finder.comments`. Like
+/// extended attributes this is best effort, the store stays the source of
+/// truth, so failures are only warned about.
+#[cfg(target_os = "macos")]
+pub fn sync(config: &FinderConfig, dir: &Path, meta: &DirMeta) {
+    if config.tags {
+        let result = match meta.tags.is_empty() {
+            true => match xattr::get(dir, TAGS_ATTRIBUTE) {
+                Ok(Some(_)) => xattr::remove(dir, TAGS_ATTRIBUTE),
+                _ => Ok(()),
+            },
+            false => xattr::set(dir, TAGS_ATTRIBUTE, tags_plist(config, meta).as_bytes()),
+        };
+        if let Err(e) = result {
+            warn!("Unable to set the Finder tags of {}: {}", dir.display(), e);
+        }
+    }
+    if config.comments {
+        if let Err(e) = set_comment(dir, meta.description.as_deref().unwrap_or("")) {
+            warn!(
+                "Unable to set the Spotlight comment of {}: {}",
+                dir.display(),
+                e
+            );
+        }
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+pub fn sync(_config: &FinderConfig, _dir: &Path, _meta: &DirMeta) {}
+
+// the Finder color of a tag, configured or the one it is named after
+#[cfg(target_os = "macos")]
+fn color(config: &FinderConfig, tag: &str) -> usize {
+    let name = config.colors.get(tag).map(String::as_str).unwrap_or(tag);
+    COLORS
+        .iter()
+        .position(|color| color.eq_ignore_ascii_case(name))
+        .unwrap_or(0)
+}
+
+// Finder tags are a property list array of "<name>\n<color>" strings,
+// which Finder reads in its XML form as well
+#[cfg(target_os = "macos")]
+fn tags_plist(config: &FinderConfig, meta: &DirMeta) -> String {
+    let mut plist = String::from(concat!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+        "<!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" ",
+        "\"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n",
+        "<plist version=\"1.0\">\n<array>\n",
+    ));
+    for tag in meta.tags.iter() {
+        let name = tag
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        plist.push_str(&format!(
+            "\t<string>{}\n{}</string>\n",
+            name,
+            color(config, tag)
+        ));
+    }
+    plist.push_str("</array>\n</plist>\n");
+    plist
+}
+
+// Finder keeps comments itself, so they are set through it rather than
+// the Spotlight attribute, which Finder would not show
+#[cfg(target_os = "macos")]
+fn set_comment(dir: &Path, comment: &str) -> Result<(), Box<dyn std::error::Error + 'static>> {
+    use crate::errors::ZugzugError;
+    use std::process::Command;
+
+    let output = Command::new("osascript")
+        .args([
+            "-e",
+            "on run argv",
+            "-e",
+            "tell application \"Finder\" to set comment of \
+             (POSIX file (item 1 of argv) as alias) to (item 2 of argv)",
+            "-e",
+            "end run",
+        ])
+        .arg(dir)
+        .arg(comment)
+        .output()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run osascript: {}", e)))?;
+    if !output.status.success() {
+        return Err(Box::new(ZugzugError::new(
+            String::from_utf8_lossy(&output.stderr).trim(),
+        )));
+    }
+    Ok(())
+}
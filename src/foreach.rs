@@ -0,0 +1,69 @@
+use crate::store::Dir;
+use std::collections::BTreeMap;
+use std::io::{self, Write};
+use std::process::{Command, ExitStatus};
+use std::sync::Mutex;
+use std::thread;
+
+/// A directory to run the command in, with the environment to run it with
+pub struct Job {
+    pub dir: Dir,
+    pub env: BTreeMap<String, String>,
+}
+
+fn command(command: &[&str], job: &Job) -> Command {
+    let mut cmd = Command::new(command[0]);
+    cmd.args(&command[1..])
+        .current_dir(&job.dir.path)
+        .envs(&job.env);
+    cmd
+}
+
+/// Run a command in every directory, `parallel` at a time
+///
+/// One at a time, the command's output goes straight to the terminal after
+/// a `==> <dir>` heading. In parallel, each command's output is collected and
+/// printed under its heading once it has finished, so outputs don't mix.
+/// Results come back in the order of the jobs.
+pub fn run(jobs: Vec<Job>, cmd: &[&str], parallel: usize) -> Vec<(Dir, io::Result<ExitStatus>)> {
+    if parallel <= 1 {
+        return jobs
+            .into_iter()
+            .map(|job| {
+                println!("==> {}", job.dir.path_str());
+                let status = command(cmd, &job).status();
+                (job.dir, status)
+            })
+            .collect();
+    }
+    let total = jobs.len();
+    let queue = Mutex::new(jobs.into_iter().enumerate());
+    let results = Mutex::new(Vec::with_capacity(total));
+    thread::scope(|scope| {
+        for _ in 0..parallel.min(total) {
+            scope.spawn(|| loop {
+                let next = queue.lock().unwrap().next();
+                let (i, job) = match next {
+                    Some(next) => next,
+                    None => break,
+                };
+                let output = command(cmd, &job).output();
+                let status = output.map(|output| {
+                    let stdout = io::stdout();
+                    let mut stdout = stdout.lock();
+                    let _ = writeln!(stdout, "==> {}", job.dir.path_str());
+                    let _ = stdout.write_all(&output.stdout);
+                    let _ = io::stderr().write_all(&output.stderr);
+                    output.status
+                });
+                results.lock().unwrap().push((i, job.dir, status));
+            });
+        }
+    });
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _, _)| *i);
+    results
+        .into_iter()
+        .map(|(_, dir, status)| (dir, status))
+        .collect()
+}
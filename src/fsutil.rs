@@ -0,0 +1,616 @@
+use crate::config::MkdirConfig;
+use crate::errors::ZugzugError;
+use crate::localmeta;
+use crate::platform;
+use crate::progress::Progress;
+use crate::snapshot;
+use crate::store::Dir;
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+/// Compute the total size of all files below a path
+///
+/// Symlinks are not followed, so a link pointing out of the tree only counts
+/// for the size of the link itself.
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+    let metadata = fs::symlink_metadata(path)?;
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        total += dir_size(&entry?.path())?;
+    }
+    Ok(total)
+}
+
+/// When anything below a path was last modified, the path itself included
+///
+/// Symlinks are not followed, like in `dir_size`.
+pub fn last_modified(path: &Path) -> io::Result<SystemTime> {
+    let metadata = fs::symlink_metadata(path)?;
+    let mut newest = metadata.modified()?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            newest = newest.max(last_modified(&entry?.path())?);
+        }
+    }
+    Ok(newest)
+}
+
+/// A cheap hash of what is in a directory and how many files it has
+///
+/// Covers the relative paths and sizes of all files and, of each file, the
+/// first and last 4K, so directories with the same fingerprint are very
+/// likely copies of each other without reading everything. Symlinks count
+/// with their targets, snapshots and the metadata file zz writes not at all.
+pub fn fingerprint(path: &Path) -> io::Result<(u64, usize)> {
+    const SAMPLE: u64 = 4096;
+    let mut hasher = DefaultHasher::new();
+    let mut count = 0;
+    for file in walk_files(path)? {
+        if file.starts_with(snapshot::DIR) || file == Path::new(localmeta::FILE) {
+            continue;
+        }
+        let full = path.join(&file);
+        let metadata = fs::symlink_metadata(&full)?;
+        file.hash(&mut hasher);
+        metadata.len().hash(&mut hasher);
+        count += 1;
+        if metadata.file_type().is_symlink() {
+            fs::read_link(&full)?.hash(&mut hasher);
+            continue;
+        }
+        let mut f = File::open(&full)?;
+        let mut buf = vec![0; SAMPLE.min(metadata.len()) as usize];
+        f.read_exact(&mut buf)?;
+        hasher.write(&buf);
+        if metadata.len() > SAMPLE {
+            f.seek(SeekFrom::End(-(SAMPLE as i64)))?;
+            f.read_exact(&mut buf)?;
+            hasher.write(&buf);
+        }
+    }
+    Ok((hasher.finish(), count))
+}
+
+/// List all files below a directory, relative to it
+///
+/// Directories themselves are not included, symlinks are listed but not
+/// followed. The result is sorted.
+pub fn walk_files(root: &Path) -> io::Result<Vec<PathBuf>> {
+    fn walk(root: &Path, relative: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(root.join(relative))? {
+            let entry = entry?;
+            let path = relative.join(entry.file_name());
+            if entry.file_type()?.is_dir() {
+                walk(root, &path, files)?;
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+    let mut files = vec![];
+    walk(root, Path::new(""), &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Compare two files byte by byte
+pub fn files_equal(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::symlink_metadata(a)?.len() != fs::symlink_metadata(b)?.len() {
+        return Ok(false);
+    }
+    let mut a = BufReader::new(File::open(a)?);
+    let mut b = BufReader::new(File::open(b)?);
+    loop {
+        let len = {
+            let a_buf = a.fill_buf()?;
+            let b_buf = b.fill_buf()?;
+            let len = a_buf.len().min(b_buf.len());
+            if len == 0 {
+                return Ok(a_buf.is_empty() && b_buf.is_empty());
+            }
+            if a_buf[..len] != b_buf[..len] {
+                return Ok(false);
+            }
+            len
+        };
+        a.consume(len);
+        b.consume(len);
+    }
+}
+
+/// Move a directory, also onto another filesystem
+///
+/// A rename is tried first. When `to` is on another filesystem the tree is
+/// copied next to it under a temporary name and every file is compared with
+/// its source. Only then is the copy renamed into place and the source
+/// removed, so an interrupted move leaves the source as it was. A partial
+/// copy left behind is replaced by the next attempt.
+pub fn move_dir(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {}
+        result => return result,
+    }
+    let name = to.file_name().unwrap_or_default().to_string_lossy();
+    let partial = to.with_file_name(format!(".{}.zz-partial", name));
+    if fs::symlink_metadata(&partial).is_ok() {
+        fs::remove_dir_all(&partial)?;
+    }
+    let files = walk_files(from)?;
+    let mut progress = Progress::new("Copying", files.len());
+    copy_tree(from, &partial, &mut progress)?;
+    progress.finish();
+
+    let mut progress = Progress::new("Verifying", files.len());
+    if walk_files(&partial)? != files {
+        return Err(io::Error::other(format!(
+            "The copy in {} doesn't have the same files as {}",
+            partial.display(),
+            from.display()
+        )));
+    }
+    for file in &files {
+        let (source, copy) = (from.join(file), partial.join(file));
+        let same = match fs::symlink_metadata(&source)?.file_type().is_symlink() {
+            true => fs::read_link(&source)? == fs::read_link(&copy)?,
+            false => files_equal(&source, &copy)?,
+        };
+        if !same {
+            return Err(io::Error::other(format!(
+                "{} differs from its copy in {}",
+                source.display(),
+                partial.display()
+            )));
+        }
+        progress.inc();
+    }
+    progress.finish();
+    fs::rename(&partial, to)?;
+    fs::remove_dir_all(from)
+}
+
+// copy a directory tree with permissions and modification times, and
+// symlinks as symlinks
+fn copy_tree(from: &Path, to: &Path, progress: &mut Progress) -> io::Result<()> {
+    fs::create_dir(to)?;
+    copy_entries(from, to, progress)?;
+    // last, a read-only source directory would keep its copy from filling up
+    fs::set_permissions(to, fs::metadata(from)?.permissions())
+}
+
+// copy what is inside `from` into the existing directory `to`
+fn copy_entries(from: &Path, to: &Path, progress: &mut Progress) -> io::Result<()> {
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        copy_entry(&entry.path(), &to.join(entry.file_name()), progress)?;
+    }
+    Ok(())
+}
+
+// copy a file, symlink or directory tree
+fn copy_entry(source: &Path, target: &Path, progress: &mut Progress) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(source)?;
+    if metadata.is_dir() {
+        return copy_tree(source, target, progress);
+    }
+    if metadata.file_type().is_symlink() {
+        copy_link(source, target)?;
+    } else {
+        fs::copy(source, target)?;
+        // the copy is only opened for reading, it may be read-only already
+        File::open(target)?.set_modified(metadata.modified()?)?;
+    }
+    progress.inc();
+    Ok(())
+}
+
+/// Move a file or symlink, also onto another filesystem
+///
+/// A rename is tried first, then a copy keeping the modification time
+/// before the source is removed. Either way counts as one step of
+/// `progress`.
+pub fn move_file(from: &Path, to: &Path, progress: &mut Progress) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        progress.inc();
+        return Ok(());
+    }
+    copy_entry(from, to, progress)?;
+    fs::remove_file(from)
+}
+
+/// Copy the contents of a directory into an existing one that doesn't have
+/// any of them yet
+///
+/// Files are cloned copy-on-write where the filesystem supports it (btrfs
+/// and XFS through `cp --reflink`, APFS through `cp -c`), which takes no time
+/// and no space until either copy changes. Everywhere else, or when `cp`
+/// fails, a regular copy is made. Entries of `from` named in `skip` are
+/// left out.
+pub fn copy_dir_contents(from: &Path, to: &Path, skip: &[&str]) -> io::Result<()> {
+    let mut sources = vec![];
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if !skip.iter().any(|name| entry.file_name() == *name) {
+            sources.push(entry.path());
+        }
+    }
+    if sources.is_empty() || reflink_copy(&sources, to) {
+        return Ok(());
+    }
+    // start over from what the failed clone left behind
+    for source in sources.iter() {
+        let path = to.join(source.file_name().unwrap_or_default());
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&path)?,
+            Ok(_) => fs::remove_file(&path)?,
+            Err(_) => {}
+        }
+    }
+    let files = walk_files(from)?
+        .into_iter()
+        .filter(|file| {
+            !file
+                .iter()
+                .next()
+                .is_some_and(|first| skip.iter().any(|name| first == *name))
+        })
+        .count();
+    let mut progress = Progress::new("Copying", files);
+    for source in sources {
+        let target = to.join(source.file_name().unwrap_or_default());
+        copy_entry(&source, &target, &mut progress)?;
+    }
+    progress.finish();
+    Ok(())
+}
+
+// clone entries into a directory with `cp`, reporting whether every file
+// was cloned
+fn reflink_copy(sources: &[PathBuf], to: &Path) -> bool {
+    let mut command = if cfg!(target_os = "macos") {
+        let mut command = Command::new("cp");
+        command.arg("-cRp");
+        command
+    } else if cfg!(target_os = "linux") {
+        let mut command = Command::new("cp");
+        command.args(["-a", "--reflink=always"]);
+        command
+    } else {
+        return false;
+    };
+    command
+        .args(sources)
+        .arg(to)
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(unix)]
+fn copy_link(source: &Path, target: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(fs::read_link(source)?, target)
+}
+
+#[cfg(windows)]
+fn copy_link(source: &Path, target: &Path) -> io::Result<()> {
+    use std::os::windows::fs::{symlink_dir, symlink_file};
+
+    let points_to = fs::read_link(source)?;
+    match fs::metadata(source) {
+        Ok(metadata) if metadata.is_dir() => symlink_dir(points_to, target),
+        _ => symlink_file(points_to, target),
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+fn copy_link(source: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::Error::other(format!(
+        "Unable to copy the link {} on this platform",
+        source.display()
+    )))
+}
+
+/// Check whether a directory tree contains no files
+///
+/// Nested directories are fine as long as they are empty themselves.
+pub fn is_empty_tree(path: &Path) -> io::Result<bool> {
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() || !is_empty_tree(&entry.path())? {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// Compute the sizes of many directories in parallel
+///
+/// Directories that can't be read are reported and left out of the result.
+/// A progress bar is drawn on stderr while the work is running.
+pub fn dir_sizes(dirs: Vec<Dir>) -> Vec<(Dir, u64)> {
+    let _span = span!("size {} directories", dirs.len());
+    in_parallel(dirs, "Computing sizes", "size", dir_size)
+}
+
+/// Find when the contents of many directories were last modified, in
+/// parallel
+///
+/// Like `dir_sizes`, directories that can't be read are reported and left
+/// out.
+pub fn dirs_modified(dirs: Vec<Dir>) -> Vec<(Dir, SystemTime)> {
+    let _span = span!("check {} directories for changes", dirs.len());
+    in_parallel(dirs, "Checking for changes", "check", last_modified)
+}
+
+/// Fingerprint many directories in parallel, see `fingerprint`
+///
+/// Like `dir_sizes`, directories that can't be read are reported and left
+/// out.
+pub fn dir_fingerprints(dirs: Vec<Dir>) -> Vec<(Dir, (u64, usize))> {
+    let _span = span!("fingerprint {} directories", dirs.len());
+    in_parallel(dirs, "Fingerprinting", "fingerprint", fingerprint)
+}
+
+// walk directories on a worker per CPU, drawing a progress bar with the
+// given label
+fn in_parallel<T: Send + 'static>(
+    dirs: Vec<Dir>,
+    label: &str,
+    verb: &str,
+    walk: fn(&Path) -> io::Result<T>,
+) -> Vec<(Dir, T)> {
+    let total = dirs.len();
+    let workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+        .min(total.max(1));
+    let queue = Arc::new(Mutex::new(dirs));
+    let (sender, receiver) = mpsc::channel();
+
+    for _ in 0..workers {
+        let queue = Arc::clone(&queue);
+        let sender = sender.clone();
+        thread::spawn(move || loop {
+            let next = queue.lock().unwrap().pop();
+            match next {
+                Some(dir) => {
+                    let result = walk(&dir.path);
+                    if sender.send((dir, result)).is_err() {
+                        break;
+                    }
+                }
+                None => break,
+            }
+        });
+    }
+    drop(sender);
+
+    let mut progress = Progress::new(label, total);
+    let mut results = Vec::with_capacity(total);
+    for (dir, result) in receiver {
+        progress.inc();
+        match result {
+            Ok(value) => results.push((dir, value)),
+            Err(err) => warn!("Unable to {} {}: {}", verb, dir.path_str(), err),
+        }
+    }
+    progress.finish();
+    results
+}
+
+/// Format a byte count for humans, e.g. `1.5G`
+pub fn human_size(bytes: u64) -> String {
+    let units = ["B", "K", "M", "G", "T", "P"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < units.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}{}", bytes, units[unit])
+    } else {
+        format!("{:.1}{}", size, units[unit])
+    }
+}
+
+/// Parse a size like `512`, `10K`, `1.5G` or `2GB` into bytes
+pub fn parse_size(s: &str) -> Option<u64> {
+    let upper = s.to_uppercase();
+    let trimmed = upper.trim_end_matches("IB").trim_end_matches('B');
+    let (number, power) = match trimmed.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => {
+            let power = "KMGTP".find(c)? as i32 + 1;
+            (&trimmed[..i], power)
+        }
+        _ => (trimmed, 0),
+    };
+    let number: f64 = number.parse().ok()?;
+    if number < 0.0 {
+        return None;
+    }
+    Some((number * 1024f64.powi(power)) as u64)
+}
+
+/// Check that the filesystem holding `path` has room for a new directory
+///
+/// Fails when less than `mkdir.min_free` is available and warns on stderr
+/// below `mkdir.warn_free`. Nothing is checked when the free space can't be
+/// found out.
+pub fn check_free_space(path: &Path, config: &MkdirConfig) -> Result<(), ZugzugError> {
+    let threshold = |setting: &Option<String>, key: &str| {
+        setting
+            .as_deref()
+            .map(|size| {
+                parse_size(size).ok_or_else(|| {
+                    ZugzugError::new(&format!(
+                        "Invalid mkdir.{} '{}', expected a size like 1G",
+                        key, size
+                    ))
+                })
+            })
+            .transpose()
+    };
+    let min_free = threshold(&config.min_free, "min_free")?;
+    let warn_free = threshold(&config.warn_free, "warn_free")?;
+    if min_free.is_none() && warn_free.is_none() {
+        return Ok(());
+    }
+    let free = match platform::free_space(path) {
+        Ok(free) => free,
+        Err(_) => return Ok(()),
+    };
+    if min_free.is_some_and(|min| free < min) {
+        return Err(ZugzugError::new(&format!(
+            "Only {} free on the filesystem of {}, mkdir.min_free is {}",
+            human_size(free),
+            path.display(),
+            config.min_free.as_deref().unwrap_or_default()
+        )));
+    }
+    if warn_free.is_some_and(|warn| free < warn) {
+        warn!(
+            "Only {} free on the filesystem of {}",
+            human_size(free),
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Check that a bucket can live at `path`
+///
+/// It has to be a directory the current user can make directories in,
+/// which is tried out with a temporary one.
+pub fn check_bucket_dir(path: &Path) -> Result<(), ZugzugError> {
+    let metadata = fs::metadata(path)
+        .map_err(|e| ZugzugError::new(&format!("Unable to read {}: {}", path.display(), e)))?;
+    if !metadata.is_dir() {
+        return Err(ZugzugError::new(&format!(
+            "{} is a file, a bucket has to be a directory",
+            path.display()
+        )));
+    }
+    let probe = path.join(format!(".zz-probe-{}", std::process::id()));
+    fs::create_dir(&probe).map_err(|e| {
+        ZugzugError::new(&format!(
+            "Unable to make directories in {}: {}",
+            path.display(),
+            e
+        ))
+    })?;
+    let _ = fs::remove_dir(&probe);
+    Ok(())
+}
+
+/// Expand a leading `~` and `$VAR` or `${VAR}` references in a path
+///
+/// Fails when a referenced variable isn't set. A `$` not followed by a
+/// variable name is kept as it is.
+pub fn expand_path(path: &str) -> Result<String, ZugzugError> {
+    let mut expanded = String::new();
+    let mut rest = path;
+    if rest == "~" || rest.starts_with("~/") || rest.starts_with("~\\") {
+        let home =
+            dirs::home_dir().ok_or_else(|| ZugzugError::new("Could not get home directory"))?;
+        expanded.push_str(&home.to_string_lossy());
+        rest = &rest[1..];
+    }
+    while let Some(i) = rest.find('$') {
+        expanded.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let (name, len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], end + 2),
+                None => ("", 0),
+            },
+            None => {
+                let end = after
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(after.len());
+                (&after[..end], end)
+            }
+        };
+        if name.is_empty() {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let value = std::env::var(name).map_err(|_| {
+            ZugzugError::new(&format!(
+                "Environment variable {} in '{}' is not set",
+                name, path
+            ))
+        })?;
+        expanded.push_str(&value);
+        rest = &after[len..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Parse an octal permission mode like `0700` or `750`
+pub fn parse_mode(mode: &str) -> Result<u32, ZugzugError> {
+    u32::from_str_radix(mode, 8)
+        .ok()
+        .filter(|&mode| mode <= 0o7777)
+        .ok_or_else(|| {
+            ZugzugError::new(&format!(
+                "Invalid mode '{}', expected octal like 0700",
+                mode
+            ))
+        })
+}
+
+/// Set the permission bits of a path
+///
+/// Permissions are only supported on unix, elsewhere this does nothing.
+pub fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    }
+    #[cfg(not(unix))]
+    let _ = (path, mode);
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    #[test]
+    fn copy_tree_keeps_read_only_files() {
+        let root = std::env::temp_dir().join(format!("zz-fsutil-{}", std::process::id()));
+        let (from, to) = (root.join("from"), root.join("to"));
+        fs::create_dir_all(from.join("objects")).unwrap();
+        let file = from.join("objects").join("blob");
+        fs::write(&file, "contents").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + Duration::from_secs(1_600_000_000);
+        File::open(&file).unwrap().set_modified(modified).unwrap();
+        set_mode(&file, 0o444).unwrap();
+
+        let copied = copy_tree(&from, &to, &mut Progress::new("Copying", 0));
+        let copy = to.join("objects").join("blob");
+        let result = copied.and_then(|_| Ok((fs::read_to_string(&copy)?, fs::metadata(&copy)?)));
+        fs::remove_dir_all(&root).unwrap();
+        let (contents, metadata) = result.unwrap();
+        assert_eq!(contents, "contents");
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o444);
+        assert_eq!(metadata.modified().unwrap(), modified);
+    }
+}
@@ -0,0 +1,44 @@
+/// Fuzzy matches at least this good are used without asking
+pub const CONFIDENT_SCORE: f64 = 0.75;
+/// Fuzzy matches below this are not considered at all
+pub const MINIMUM_SCORE: f64 = 0.4;
+
+/// Score how well a query matches a candidate name, from 0.0 to 1.0
+///
+/// Combines the edit distance between the two with a bonus for queries whose
+/// characters all appear in order in the candidate, so both typos
+/// ("expermient") and abbreviations ("expmt") score well. Case is ignored.
+pub fn score(query: &str, candidate: &str) -> f64 {
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    let candidate: Vec<char> = candidate.to_lowercase().chars().collect();
+    if query.is_empty() || candidate.is_empty() {
+        return 0.0;
+    }
+    let longest = query.len().max(candidate.len()) as f64;
+    let edit = 1.0 - levenshtein(&query, &candidate) as f64 / longest;
+    let subsequence = if is_subsequence(&query, &candidate) {
+        // an abbreviation is a better match the more of the name it covers
+        0.5 + 0.5 * query.len() as f64 / candidate.len() as f64
+    } else {
+        0.0
+    };
+    edit.max(subsequence)
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + if ca == cb { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+fn is_subsequence(query: &[char], candidate: &[char]) -> bool {
+    let mut chars = candidate.iter();
+    query.iter().all(|q| chars.any(|c| c == q))
+}
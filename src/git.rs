@@ -0,0 +1,92 @@
+use std::fmt;
+use std::path::Path;
+use std::process::Command;
+
+/// Working tree state of a git repository
+#[derive(Debug, Default)]
+pub struct GitStatus {
+    /// Uncommitted changes, including untracked files
+    pub dirty: bool,
+    /// Commits not on the upstream branch
+    pub ahead: u64,
+    /// Upstream commits not merged yet
+    pub behind: u64,
+    /// Whether the current branch tracks an upstream branch
+    pub upstream: bool,
+    /// Whether anything was committed yet
+    pub commits: bool,
+}
+
+impl GitStatus {
+    /// Check for work that only exists in this directory
+    ///
+    /// Commits on a branch without an upstream count as unpushed.
+    pub fn has_unsaved_work(&self) -> bool {
+        self.dirty || self.ahead > 0 || self.is_local()
+    }
+
+    fn is_local(&self) -> bool {
+        self.commits && !self.upstream
+    }
+}
+
+impl fmt::Display for GitStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = vec![];
+        if self.dirty {
+            parts.push("dirty".to_string());
+        }
+        if self.ahead > 0 {
+            parts.push(format!("ahead {}", self.ahead));
+        }
+        if self.behind > 0 {
+            parts.push(format!("behind {}", self.behind));
+        }
+        if self.is_local() {
+            parts.push("local".to_string());
+        }
+        if parts.is_empty() {
+            write!(f, "clean")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+/// Status of the git repository at `dir`, or `None` when it isn't one
+///
+/// Only the directory itself is considered, not repositories it is nested
+/// in. Runs `git status`, so it costs a process per directory.
+pub fn status(dir: &Path) -> Option<GitStatus> {
+    if !dir.join(".git").exists() {
+        return None;
+    }
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["status", "--porcelain=v2", "--branch"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let mut status = GitStatus::default();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if let Some(oid) = line.strip_prefix("# branch.oid ") {
+            status.commits = oid != "(initial)";
+        } else if let Some(upstream) = line.strip_prefix("# branch.upstream ") {
+            status.upstream = !upstream.is_empty();
+        } else if let Some(counts) = line.strip_prefix("# branch.ab ") {
+            for count in counts.split_whitespace() {
+                if let Some(n) = count.strip_prefix('+') {
+                    status.ahead = n.parse().unwrap_or(0);
+                } else if let Some(n) = count.strip_prefix('-') {
+                    status.behind = n.parse().unwrap_or(0);
+                }
+            }
+        } else if !line.starts_with('#') {
+            status.dirty = true;
+        }
+    }
+    Some(status)
+}
@@ -0,0 +1,60 @@
+use crate::errors::ZugzugError;
+use std::collections::BTreeMap;
+use std::error;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Run hook commands through the shell inside a directory
+///
+/// Stops at the first failing hook.
+pub fn run(
+    hooks: &[String],
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    run_with_stdout(hooks, dir, env, Stdio::inherit)
+}
+
+/// Run hook commands like `run`, sending their output to stderr
+///
+/// For when stdout carries a protocol, like with `zz serve`.
+pub fn run_to_stderr(
+    hooks: &[String],
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    run_with_stdout(hooks, dir, env, || Stdio::from(io::stderr()))
+}
+
+fn run_with_stdout(
+    hooks: &[String],
+    dir: &Path,
+    env: &BTreeMap<String, String>,
+    stdout: impl Fn() -> Stdio,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    for hook in hooks.iter() {
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.arg("/C").arg(hook);
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(hook);
+            command
+        };
+        let status = command
+            .current_dir(dir)
+            .envs(env)
+            .stdout(stdout())
+            .status()
+            .map_err(|e| ZugzugError::new(&format!("Unable to run hook '{}': {}", hook, e)))?;
+        if !status.success() {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "Hook '{}' exited with {}",
+                hook, status
+            ))));
+        }
+    }
+    Ok(())
+}
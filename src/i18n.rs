@@ -0,0 +1,100 @@
+use crate::config::LocaleConfig;
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+/// Translations of the current language, by English message
+///
+/// Messages are looked up by their English text, placeholders like
+/// `{name}` included, as in a catalog file:
+///
+/// ```toml
+/// "No directory named '{name}'" = "Kein Verzeichnis namens '{name}'"
+/// "Remove {count} directories?" = "{count} Verzeichnisse entfernen?"
+/// ```
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// Load the catalog of the configured language, or the one of the
+/// environment
+///
+/// The language comes from `locale.lang`, or else `LC_ALL`, `LC_MESSAGES`
+/// or `LANG`. Its catalog is `<lang>.toml` in `locale.dir` (by default
+/// `~/.zz-locale`), where `de_AT.toml` is tried before `de.toml`. Without a
+/// catalog messages stay in English.
+pub fn init(config: &LocaleConfig) {
+    let lang = match config.lang.clone().or_else(env_lang) {
+        Some(lang) => lang,
+        None => return,
+    };
+    let dir = match config.dir.as_ref().map(PathBuf::from).or_else(default_dir) {
+        Some(dir) => dir,
+        None => return,
+    };
+    // `de_AT.UTF-8@euro` is tried as `de_AT`, then `de`
+    let lang = lang.split(['.', '@']).next().unwrap_or_default();
+    let language = lang.split(['_', '-']).next().unwrap_or_default();
+    for name in [lang, language] {
+        let path = dir.join(format!("{}.toml", name));
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(_) => continue,
+        };
+        match toml::from_str::<HashMap<String, String>>(&data) {
+            Ok(catalog) => {
+                debug!("Using {} messages from {}", catalog.len(), path.display());
+                let _ = CATALOG.set(catalog);
+            }
+            Err(e) => warn!("Ignoring invalid catalog {}: {}", path.display(), e),
+        }
+        return;
+    }
+}
+
+// the language of the environment, unless it asks for no translation
+fn env_lang() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .filter(|lang| lang != "C" && lang != "POSIX" && !lang.starts_with("C."))
+}
+
+fn default_dir() -> Option<PathBuf> {
+    Some(dirs::home_dir()?.join(".zz-locale"))
+}
+
+/// Translate a message and fill in its placeholders
+///
+/// Use the `t!` macro rather than calling this directly. Messages missing
+/// from the catalog are reported at trace level, to find what is left to
+/// translate.
+pub fn message(id: &str, args: &[(&str, &dyn Display)]) -> String {
+    let template = match CATALOG.get() {
+        Some(catalog) => match catalog.get(id) {
+            Some(translated) => translated.as_str(),
+            None => {
+                trace!("No translation for \"{}\"", id);
+                id
+            }
+        },
+        None => id,
+    };
+    let mut message = template.to_string();
+    for (name, value) in args {
+        message = message.replace(&format!("{{{}}}", name), &value.to_string());
+    }
+    message
+}
+
+/// Translate a message, e.g. `t!("Remove {count} directories?", count = n)`
+///
+/// Placeholders are named, so translations may reorder them.
+macro_rules! t {
+    ($id:expr) => { $crate::i18n::message($id, &[]) };
+    ($id:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::i18n::message($id, &[$((stringify!($name), &$value)),+])
+    };
+}
@@ -0,0 +1,147 @@
+use crate::dates;
+use crate::store::{self, DatePosition};
+use regex::Regex;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// How a directory found while importing a bucket fits the naming scheme
+#[derive(Debug, Clone, PartialEq)]
+pub enum Candidate {
+    /// Already named `YYYYMMDD_name`, or a bare date, or `name_YYYYMMDD` in a
+    /// bucket with dates as suffixes
+    Recognized(String),
+    /// Dated, but not quite in the zz format, with the name it should have
+    NearMiss { name: String, adopted: String },
+    /// No date to be found
+    Other(String),
+}
+
+// dates like 2020-01-31, 2020_01_31 or 2020.01.31, then a separator and a name
+fn separated_date() -> Regex {
+    Regex::new(r"^(\d{4})[-_.]?(\d{2})[-_.]?(\d{2})(?:[-_. ]+(.+))?$").unwrap()
+}
+
+// names ending in a date, like report_20200131 or report-2020-01-31
+fn date_suffix() -> Regex {
+    Regex::new(r"^(.+?)[-_. ]+(\d{4})[-_.]?(\d{2})[-_.]?(\d{2})$").unwrap()
+}
+
+fn canonical(date: String, name: Option<&str>, date_position: DatePosition) -> Option<String> {
+    let date = dates::prefix(dates::parse(&date)?);
+    Some(match (name, date_position) {
+        (Some(name), DatePosition::Suffix) if !name.is_empty() => format!("{}_{}", name, date),
+        (Some(name), _) if !name.is_empty() => format!("{}_{}", date, name),
+        _ => date,
+    })
+}
+
+/// Sort a directory name into recognized, near miss or other
+///
+/// What is recognized depends on where the bucket puts dates. In a bucket
+/// without dates every name is.
+pub fn classify(name: &str, date_position: DatePosition) -> Candidate {
+    let (date, _) = store::split_dir_name(name);
+    let recognized = match date_position {
+        DatePosition::Prefix => store::is_date(date),
+        DatePosition::Suffix => store::split_date_suffix(name).is_some() || store::is_date(name),
+        DatePosition::None => true,
+    };
+    if recognized {
+        return Candidate::Recognized(name.to_string());
+    }
+    let adopted = if let Some(c) = separated_date().captures(name) {
+        canonical(
+            format!("{}{}{}", &c[1], &c[2], &c[3]),
+            c.get(4).map(|m| m.as_str()),
+            date_position,
+        )
+    } else if let Some(c) = date_suffix().captures(name) {
+        canonical(
+            format!("{}{}{}", &c[2], &c[3], &c[4]),
+            Some(&c[1]),
+            date_position,
+        )
+    } else {
+        None
+    };
+    match adopted {
+        Some(adopted) => Candidate::NearMiss {
+            name: name.to_string(),
+            adopted,
+        },
+        None => Candidate::Other(name.to_string()),
+    }
+}
+
+/// Classify every directory directly below `root`, sorted by name
+pub fn scan(root: &Path, date_position: DatePosition) -> io::Result<Vec<Candidate>> {
+    let mut names = vec![];
+    for entry in fs::read_dir(root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        if let Some(name) = entry.file_name().to_str() {
+            names.push(name.to_string());
+        }
+    }
+    names.sort();
+    Ok(names
+        .iter()
+        .map(|name| classify(name, date_position))
+        .collect())
+}
+
+/// A directory that looks like a zz bucket
+#[derive(Debug)]
+pub struct Discovered {
+    pub path: PathBuf,
+    /// Children named like zz directories
+    pub dated: usize,
+    /// All child directories
+    pub total: usize,
+}
+
+/// Walk `root` looking for directories with mostly dated children
+///
+/// A directory qualifies with at least `min_dated` recognized children
+/// making up at least half of its child directories. Qualifying
+/// directories aren't descended into, neither are hidden ones. `depth`
+/// limits how many levels below `root` are looked at.
+pub fn discover(root: &Path, depth: usize, min_dated: usize) -> io::Result<Vec<Discovered>> {
+    let mut found = vec![];
+    let mut pending = vec![(root.to_path_buf(), 0)];
+    while let Some((dir, level)) = pending.pop() {
+        let candidates = match scan(&dir, DatePosition::Prefix) {
+            Ok(candidates) => candidates,
+            // unreadable directories below the root are skipped
+            Err(_) if level > 0 => continue,
+            Err(e) => return Err(e),
+        };
+        let dated = candidates
+            .iter()
+            .filter(|c| matches!(c, Candidate::Recognized(_)))
+            .count();
+        if dated >= min_dated && dated * 2 >= candidates.len() {
+            found.push(Discovered {
+                path: dir,
+                dated,
+                total: candidates.len(),
+            });
+            continue;
+        }
+        if level >= depth {
+            continue;
+        }
+        for candidate in candidates {
+            if let Candidate::Other(name) = candidate {
+                if !name.starts_with('.') {
+                    pending.push((dir.join(name), level + 1));
+                }
+            }
+        }
+    }
+    found.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(found)
+}
@@ -0,0 +1,127 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Name of the link to the newest directory kept in a bucket
+pub const LATEST: &str = "latest";
+/// Text file holding the newest directory when no link can be made
+pub const LATEST_POINTER: &str = ".latest";
+
+/// How a link to a directory was made
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LinkKind {
+    Symlink,
+    /// NTFS junction, used on Windows without the symlink privilege
+    #[cfg_attr(not(windows), allow(dead_code))]
+    Junction,
+}
+
+impl fmt::Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LinkKind::Symlink => write!(f, "symlink"),
+            LinkKind::Junction => write!(f, "junction"),
+        }
+    }
+}
+
+/// Link `link` to the directory `target`
+///
+/// On Windows, directory symlinks need Developer Mode or admin rights, so
+/// when one can't be made a junction is made instead.
+#[cfg(unix)]
+pub fn link_dir(target: &Path, link: &Path) -> io::Result<LinkKind> {
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(LinkKind::Symlink)
+}
+
+#[cfg(windows)]
+pub fn link_dir(target: &Path, link: &Path) -> io::Result<LinkKind> {
+    use std::process::{Command, Stdio};
+
+    if std::os::windows::fs::symlink_dir(target, link).is_ok() {
+        return Ok(LinkKind::Symlink);
+    }
+    // junctions need an absolute target
+    let target = fs::canonicalize(target)?;
+    let status = Command::new("cmd")
+        .arg("/C")
+        .arg("mklink")
+        .arg("/J")
+        .arg(link)
+        .arg(&target)
+        .stdout(Stdio::null())
+        .status()?;
+    if !status.success() {
+        return Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("mklink /J exited with {}", status),
+        ));
+    }
+    Ok(LinkKind::Junction)
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn link_dir(_target: &Path, _link: &Path) -> io::Result<LinkKind> {
+    Err(io::Error::new(
+        io::ErrorKind::Other,
+        "Links are not supported on this platform",
+    ))
+}
+
+/// Check whether a path is a symlink or junction, without following it
+pub fn is_link(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_symlink())
+}
+
+/// Remove a link made by `link_dir`, leaving its target alone
+pub fn remove_link(link: &Path) -> io::Result<()> {
+    // directory symlinks and junctions are directories on Windows
+    if cfg!(windows) {
+        fs::remove_dir(link)
+    } else {
+        fs::remove_file(link)
+    }
+}
+
+/// Point a bucket's `latest` link at a directory
+///
+/// Falls back to writing the path into a `.latest` text file when no link
+/// can be made, e.g. on a filesystem without symlinks or junctions.
+pub fn update_latest(bucket: &Path, target: &Path) -> io::Result<()> {
+    let link = bucket.join(LATEST);
+    let pointer = bucket.join(LATEST_POINTER);
+    if is_link(&link) {
+        remove_link(&link)?;
+    }
+    if pointer.exists() {
+        fs::remove_file(&pointer)?;
+    }
+    if link.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} exists and is not a link", link.display()),
+        ));
+    }
+    // relative symlinks keep working when the bucket is moved
+    let relative = target.file_name().map(PathBuf::from);
+    let link_target = match (cfg!(unix), relative) {
+        (true, Some(relative)) => relative,
+        _ => target.to_path_buf(),
+    };
+    if link_dir(&link_target, &link).is_err() {
+        fs::write(&pointer, format!("{}\n", target.display()))?;
+    }
+    Ok(())
+}
+
+/// The directory a bucket's `latest` link or pointer file refers to
+pub fn read_latest(bucket: &Path) -> Option<PathBuf> {
+    let link = bucket.join(LATEST);
+    if is_link(&link) {
+        return fs::canonicalize(link).ok();
+    }
+    let pointer = fs::read_to_string(bucket.join(LATEST_POINTER)).ok()?;
+    Some(PathBuf::from(pointer.trim_end()))
+}
@@ -0,0 +1,37 @@
+use crate::store::DirMeta;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// File inside a directory carrying its metadata
+pub const FILE: &str = ".zz.toml";
+
+/// Check whether a directory has a `.zz.toml`
+pub fn exists(dir: &Path) -> bool {
+    dir.join(FILE).is_file()
+}
+
+/// Read the metadata from a directory's `.zz.toml`, if it has a valid one
+///
+/// An unreadable or malformed file is reported and otherwise ignored.
+pub fn read(dir: &Path) -> Option<DirMeta> {
+    let path = dir.join(FILE);
+    let content = fs::read_to_string(&path).ok()?;
+    match toml::from_str(&content) {
+        Ok(meta) => Some(meta),
+        Err(e) => {
+            warn!("Ignoring {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Write a directory's metadata into its `.zz.toml`
+pub fn write(dir: &Path, meta: &DirMeta) -> io::Result<()> {
+    // going through a value puts the env table after the plain keys, as
+    // TOML requires
+    let content = toml::Value::try_from(meta)
+        .and_then(|value| toml::to_string(&value))
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    fs::write(dir.join(FILE), content)
+}
@@ -0,0 +1,112 @@
+use crate::errors::ZugzugError;
+use std::error;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long to wait for another process to release the lock
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Locks older than this are assumed to belong to a crashed process
+const STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// An exclusive lock held by creating a lock file
+///
+/// Exclusive creation works across hosts on network filesystems, unlike
+/// `flock`. The file records the holding host and pid, and is removed when
+/// the lock is dropped.
+pub struct FileLock {
+    path: PathBuf,
+}
+
+impl FileLock {
+    pub fn acquire(path: &Path) -> Result<FileLock, Box<dyn error::Error + 'static>> {
+        let started = SystemTime::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(mut file) => {
+                    if let Err(e) =
+                        writeln!(file, "{} {} {}", hostname(), std::process::id(), now_secs())
+                    {
+                        let _ = fs::remove_file(path);
+                        return Err(Box::new(e));
+                    }
+                    return Ok(FileLock {
+                        path: path.to_path_buf(),
+                    });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if is_stale(path) {
+                        debug!("removing stale lock {}", path.display());
+                        let _ = fs::remove_file(path);
+                        continue;
+                    }
+                    if started.elapsed().unwrap_or_default() > TIMEOUT {
+                        let holder = fs::read_to_string(path).unwrap_or_default();
+                        let holder: Vec<&str> = holder.split_whitespace().collect();
+                        return Err(Box::new(ZugzugError::new(&format!(
+                            "Store is locked by pid {} on {} ({})",
+                            holder.get(1).unwrap_or(&"?"),
+                            holder.first().unwrap_or(&"?"),
+                            path.display()
+                        ))));
+                    }
+                    trace!("waiting for lock {}", path.display());
+                    thread::sleep(Duration::from_millis(100));
+                }
+                Err(e) => return Err(Box::new(e)),
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Name of this host, used to tell lock holders apart
+pub fn hostname() -> String {
+    if let Ok(name) = fs::read_to_string("/etc/hostname") {
+        if !name.trim().is_empty() {
+            return name.trim().to_string();
+        }
+    }
+    if let Ok(name) = std::env::var("COMPUTERNAME") {
+        return name;
+    }
+    Command::new("hostname")
+        .output()
+        .ok()
+        .and_then(|o| String::from_utf8(o.stdout).ok())
+        .map(|name| name.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// the timestamp inside the file is used rather than the mtime, as clocks of
+// the file server and the clients may disagree. A file that is empty or
+// garbled, e.g. after a crash right after creating it, falls back to its
+// mtime, so it doesn't block every save for good.
+fn is_stale(path: &Path) -> bool {
+    let locked_at = fs::read_to_string(path)
+        .ok()
+        .and_then(|contents| contents.split_whitespace().nth(2)?.parse::<u64>().ok());
+    match locked_at {
+        Some(locked_at) => now_secs().saturating_sub(locked_at) > STALE_AFTER.as_secs(),
+        None => fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age > STALE_AFTER),
+    }
+}
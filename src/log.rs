@@ -0,0 +1,189 @@
+use crate::errors::ZugzugError;
+use std::cell::Cell;
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+/// Names accepted by `--log-level`, quietest first
+pub const LEVELS: &[&str] = &["off", "error", "warn", "info", "debug", "trace"];
+
+/// How much zz reports on stderr
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Off,
+    Error,
+    Warn,
+    Info,
+    /// Also how long the store, bucket scans and commands take
+    Debug,
+    Trace,
+}
+
+impl FromStr for Level {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<Level, ZugzugError> {
+        match s.to_ascii_lowercase().as_str() {
+            "off" => Ok(Level::Off),
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            _ => Err(ZugzugError::new(&format!(
+                "Unknown log level '{}', expected one of {}",
+                s,
+                LEVELS.join(", ")
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(match self {
+            Level::Off => "OFF",
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        })
+    }
+}
+
+// warnings show unless asked otherwise, like they did before there were
+// levels
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Warn as u8);
+static START: OnceLock<Instant> = OnceLock::new();
+
+thread_local! {
+    // how many spans are open, to indent what happens inside them
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Report messages up to `level` for the rest of the process
+pub fn set_level(level: Level) {
+    START.get_or_init(Instant::now);
+    LEVEL.store(level as u8, Ordering::SeqCst);
+}
+
+/// The level set by `RUST_LOG`, if it names one
+///
+/// Takes a plain level, e.g. `debug`, or the level of the `zz` target in a
+/// list of directives, e.g. `warn,zz=trace`. Other tools read `RUST_LOG`
+/// too, so a value zz doesn't understand is only warned about.
+pub fn env_level() -> Option<Level> {
+    let value = env::var("RUST_LOG").ok()?;
+    let mut level = None;
+    for directive in value.split(',').map(str::trim) {
+        let (for_zz, name) = match directive.split_once('=') {
+            Some(("zz", name)) => (true, name),
+            Some(_) => continue,
+            None if !directive.is_empty() => (false, directive),
+            None => continue,
+        };
+        match name.parse() {
+            Ok(parsed) if for_zz => return Some(parsed),
+            Ok(parsed) => level = Some(parsed),
+            Err(e) => write(Level::Warn, format_args!("Ignoring RUST_LOG: {}", e)),
+        }
+    }
+    level
+}
+
+pub fn enabled(level: Level) -> bool {
+    level != Level::Off && level as u8 <= LEVEL.load(Ordering::SeqCst)
+}
+
+/// Write a message to stderr if its level is enabled
+///
+/// Use the `error!`, `warn!`, `info!`, `debug!` and `trace!` macros rather
+/// than calling this directly.
+pub fn write(level: Level, message: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    // timings only matter when profiling
+    if !enabled(Level::Debug) {
+        eprintln!("{:<5} {}", level, message);
+        return;
+    }
+    let elapsed = START.get_or_init(Instant::now).elapsed();
+    let indent = DEPTH.with(|depth| depth.get()) * 2;
+    eprintln!(
+        "{:>9.3}s {:<5} {:indent$}{}",
+        elapsed.as_secs_f64(),
+        level,
+        "",
+        message,
+        indent = indent
+    );
+}
+
+/// Something that takes time, reported with its duration when it ends
+///
+/// Spans only record anything at debug level and up. Messages logged
+/// while one is open are indented under it.
+pub struct Span {
+    started: Option<(String, Instant)>,
+}
+
+impl Span {
+    /// Open a span, naming it only if it will be reported
+    pub fn enter(name: impl FnOnce() -> String) -> Span {
+        if !enabled(Level::Debug) {
+            return Span { started: None };
+        }
+        let name = name();
+        write(Level::Trace, format_args!("{} ...", name));
+        DEPTH.with(|depth| depth.set(depth.get() + 1));
+        Span {
+            started: Some((name, Instant::now())),
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        if let Some((name, started)) = self.started.take() {
+            DEPTH.with(|depth| depth.set(depth.get().saturating_sub(1)));
+            write(
+                Level::Debug,
+                format_args!(
+                    "{} took {:.1}ms",
+                    name,
+                    started.elapsed().as_secs_f64() * 1000.0
+                ),
+            );
+        }
+    }
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Error, format_args!($($arg)*)) };
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Warn, format_args!($($arg)*)) };
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Info, format_args!($($arg)*)) };
+}
+
+macro_rules! debug {
+    ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Debug, format_args!($($arg)*)) };
+}
+
+macro_rules! trace {
+    ($($arg:tt)*) => { $crate::log::write($crate::log::Level::Trace, format_args!($($arg)*)) };
+}
+
+/// Open a `Span`, e.g. `let _span = span!("scan {}", bucket.name);`
+macro_rules! span {
+    ($($arg:tt)*) => { $crate::log::Span::enter(|| format!($($arg)*)) };
+}
@@ -5,17 +5,63 @@ use args::{handle_parsed_args, parse_args};
 #[macro_use]
 extern crate prettytable;
 
+#[macro_use]
+mod log;
+#[macro_use]
+mod i18n;
+
+mod archive;
 mod args;
+mod backend;
+mod backup;
+mod batch;
+mod calendar;
+mod config;
+mod dates;
+mod dedup;
+mod diff;
+mod direnv;
+mod envvars;
 mod errors;
+mod filter;
+mod finder;
+mod foreach;
+mod fsutil;
+mod fuzzy;
+mod git;
+mod hooks;
+mod import;
+mod link;
+mod localmeta;
+mod lock;
+mod merge;
+mod migrations;
+mod names;
+mod output;
+mod pattern;
+mod platform;
+mod powershell;
+mod progress;
+mod prompt;
+mod publish;
+mod readonly;
+mod report;
+mod scaffold;
+mod secrets;
+mod serve;
+mod shellext;
+mod snapshot;
+mod sqlite;
 mod store;
+mod watch;
+mod webhook;
+mod xattrs;
+mod zoxide;
 
 fn main() -> Result<(), Box<dyn error::Error + 'static>> {
     let parsed_args = parse_args()?;
-    match handle_parsed_args(parsed_args) {
-        Err(err) => {
-            println!("Error: {}", err.description());
-        }
-        Ok(_) => {}
+    if let Err(err) = handle_parsed_args(parsed_args) {
+        println!("{}", t!("Error: {error}", error = err));
     }
     Ok(())
 }
@@ -0,0 +1,134 @@
+use crate::errors::ZugzugError;
+use crate::fsutil;
+use crate::localmeta;
+use crate::progress::Progress;
+use crate::snapshot;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// What to do with a file that exists on both sides with other contents
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conflict {
+    /// Keep the target's file, the source's is left behind
+    Skip,
+    /// Replace the target's file by the source's
+    Overwrite,
+    /// Keep both, the source's under a new name like `notes.merged.txt`
+    Rename,
+}
+
+impl FromStr for Conflict {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<Conflict, ZugzugError> {
+        match s {
+            "skip" => Ok(Conflict::Skip),
+            "overwrite" => Ok(Conflict::Overwrite),
+            "rename" => Ok(Conflict::Rename),
+            _ => Err(ZugzugError::new(&format!(
+                "Unknown conflict handling '{}', expected skip, overwrite or rename",
+                s
+            ))),
+        }
+    }
+}
+
+/// What a merge did
+#[derive(Debug, Default)]
+pub struct MergeSummary {
+    /// Files only the source had
+    pub moved: usize,
+    /// Files both had with the same contents
+    pub identical: usize,
+    /// Conflicting files left in the source
+    pub skipped: Vec<PathBuf>,
+    pub overwritten: usize,
+    /// Conflicting files moved under a new name, as relative paths
+    pub renamed: Vec<PathBuf>,
+}
+
+/// Move the files of directory `from` into `to`, keeping their relative
+/// paths
+///
+/// Files both have with the same contents stay in `to` as they are, other
+/// conflicts are handled as `on_conflict` says. A file that meets a
+/// directory of the same name is always renamed, one whose directory is a
+/// file in `to` always skipped. Snapshots and the metadata file zz writes
+/// are left where they are.
+pub fn merge(from: &Path, to: &Path, on_conflict: Conflict) -> io::Result<MergeSummary> {
+    let files: Vec<PathBuf> = fsutil::walk_files(from)?
+        .into_iter()
+        .filter(|file| !file.starts_with(snapshot::DIR) && file != Path::new(localmeta::FILE))
+        .collect();
+    let mut summary = MergeSummary::default();
+    let mut progress = Progress::new("Merging", files.len());
+    for file in files {
+        let source = from.join(&file);
+        let target = to.join(&file);
+        // a file in the way of the directory is left alone, like the
+        // source's file
+        if let Some(Err(e)) = target.parent().map(fs::create_dir_all) {
+            debug!(
+                "Unable to create the directory of {}: {}",
+                target.display(),
+                e
+            );
+            progress.inc();
+            summary.skipped.push(file);
+            continue;
+        }
+        let existing = match fs::symlink_metadata(&target) {
+            Ok(existing) => existing,
+            Err(_) => {
+                fsutil::move_file(&source, &target, &mut progress)?;
+                summary.moved += 1;
+                continue;
+            }
+        };
+        if existing.is_file() && fsutil::files_equal(&source, &target)? {
+            progress.inc();
+            summary.identical += 1;
+            continue;
+        }
+        match on_conflict {
+            Conflict::Skip if !existing.is_dir() => {
+                progress.inc();
+                summary.skipped.push(file);
+            }
+            Conflict::Overwrite if !existing.is_dir() => {
+                fs::remove_file(&target)?;
+                fsutil::move_file(&source, &target, &mut progress)?;
+                summary.overwritten += 1;
+            }
+            _ => {
+                let renamed = free_name_in(to, &file);
+                fsutil::move_file(&source, &to.join(&renamed), &mut progress)?;
+                summary.renamed.push(renamed);
+            }
+        }
+    }
+    progress.finish();
+    Ok(summary)
+}
+
+// `notes.merged.txt`, or `notes.merged-2.txt` and so on when that is taken
+// too, for a file that is in the way in `root`
+fn free_name_in(root: &Path, file: &Path) -> PathBuf {
+    let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = match file.extension() {
+        Some(extension) => format!(".{}", extension.to_string_lossy()),
+        None => String::new(),
+    };
+    (1..)
+        .map(|i| {
+            let suffix = match i {
+                1 => "merged".to_string(),
+                _ => format!("merged-{}", i),
+            };
+            file.with_file_name(format!("{}.{}{}", stem, suffix, extension))
+        })
+        .find(|candidate| fs::symlink_metadata(root.join(candidate)).is_err())
+        .unwrap()
+}
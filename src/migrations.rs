@@ -0,0 +1,43 @@
+use crate::errors::ZugzugError;
+use serde_json::{json, Value};
+use std::error;
+
+/// Version of the store format written by this build
+pub const CURRENT_VERSION: u64 = 1;
+
+/// Upgrades from each version to the next, indexed by the version they
+/// upgrade from
+const MIGRATIONS: &[fn(&mut Value)] = &[v0_to_v1];
+
+// v0 is everything written before the store had a version field
+fn v0_to_v1(data: &mut Value) {
+    if data.get("buckets").is_none() {
+        data["buckets"] = json!([]);
+    }
+    if data.get("dirs").is_none() {
+        data["dirs"] = json!({});
+    }
+}
+
+/// Bring raw store data up to the current version
+///
+/// Returns whether anything was migrated, meaning the store should be
+/// written back to disk.
+pub fn migrate(data: &mut Value) -> Result<bool, Box<dyn error::Error + 'static>> {
+    if !data.is_object() {
+        return Err(Box::new(ZugzugError::new("Store data is not an object")));
+    }
+    let version = data.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > CURRENT_VERSION {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "Store has version {}, but this zz only understands up to version {}",
+            version, CURRENT_VERSION
+        ))));
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        info!("Migrating the store from version {} to {}", from, from + 1);
+        migration(data);
+    }
+    data["version"] = json!(CURRENT_VERSION);
+    Ok(version < CURRENT_VERSION)
+}
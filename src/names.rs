@@ -0,0 +1,100 @@
+use crate::config::NamesConfig;
+use crate::errors::ZugzugError;
+
+/// Characters Windows doesn't allow in file names, plus both path separators
+const FORBIDDEN: &[char] = &['/', '\\', '<', '>', ':', '"', '|', '?', '*'];
+
+/// Turn a name typed by the user into a safe directory name
+///
+/// Path separators, characters Windows rejects and control characters are
+/// dropped, typographic quotes and dashes become their ASCII versions, and
+/// runs of whitespace are replaced by `names.space`. Leading or trailing
+/// replacements and dots are trimmed. With `names.lowercase` the name is
+/// also lowercased.
+pub fn sanitize(name: &str, names: &NamesConfig) -> Result<String, ZugzugError> {
+    let mut sanitized = String::new();
+    let mut in_space = false;
+    for c in name.trim().chars() {
+        if c.is_whitespace() {
+            in_space = true;
+            continue;
+        }
+        if FORBIDDEN.contains(&c) || c.is_control() {
+            continue;
+        }
+        if in_space {
+            sanitized.push_str(&names.space);
+            in_space = false;
+        }
+        match c {
+            '\u{2018}' | '\u{2019}' => sanitized.push('\''),
+            '\u{201c}' | '\u{201d}' => {}
+            '\u{2010}'..='\u{2015}' => sanitized.push('-'),
+            '\u{2026}' => sanitized.push_str("..."),
+            _ if names.lowercase => sanitized.extend(c.to_lowercase()),
+            _ => sanitized.push(c),
+        }
+    }
+    let sanitized = sanitized.trim_end_matches('.');
+    let sanitized = match names.space.is_empty() {
+        true => sanitized,
+        false => sanitized
+            .trim_start_matches(names.space.as_str())
+            .trim_end_matches(names.space.as_str()),
+    };
+    if sanitized.is_empty() {
+        return Err(ZugzugError::new(&format!(
+            "'{}' has nothing left to name a directory after",
+            name
+        )));
+    }
+    Ok(sanitized.to_string())
+}
+
+/// Device names Windows reserves, with or without an extension
+const RESERVED: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Longest file name most filesystems allow, in bytes
+const MAX_NAME_BYTES: usize = 255;
+
+/// Check that a directory name stays inside its bucket and works everywhere
+///
+/// Rejects names that would escape the bucket (`..`, absolute paths or any
+/// path separator), Windows device names like `CON` or `nul.txt`, and
+/// names that make the full directory name, `full_name`, longer than
+/// filesystems allow.
+pub fn validate(name: &str, full_name: &str) -> Result<(), ZugzugError> {
+    if name.is_empty() || name == "." || name == ".." {
+        return Err(ZugzugError::new(&format!(
+            "'{}' is not a valid directory name",
+            name
+        )));
+    }
+    if name.contains(['/', '\\', '\0']) {
+        return Err(ZugzugError::new(&format!(
+            "'{}' would leave the bucket, names can't contain path separators",
+            name
+        )));
+    }
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if RESERVED
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Err(ZugzugError::new(&format!(
+            "'{}' is a reserved name on Windows",
+            name
+        )));
+    }
+    if full_name.len() > MAX_NAME_BYTES {
+        return Err(ZugzugError::new(&format!(
+            "The directory name would be {} bytes long, at most {} are allowed",
+            full_name.len(),
+            MAX_NAME_BYTES
+        )));
+    }
+    Ok(())
+}
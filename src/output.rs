@@ -0,0 +1,233 @@
+use crate::errors::ZugzugError;
+use prettytable::format::{self, FormatBuilder, LinePosition, LineSeparator, TableFormat};
+use prettytable::Table;
+use std::env;
+use std::io::{self, IsTerminal};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How command output is rendered, selected with the global `--format`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+    /// One JSON object per line, for streaming into e.g. PowerShell objects
+    JsonLines,
+    /// Stable line-oriented output for scripts, see `print_porcelain`
+    Porcelain,
+    /// GitHub-flavored markdown tables, for wiki pages and PR descriptions
+    Markdown,
+}
+
+impl Format {
+    /// Whether the output is JSON, as a whole or line by line
+    pub fn is_json(self) -> bool {
+        self == Format::Json || self == Format::JsonLines
+    }
+}
+
+pub const FORMATS: &[&str] = &["table", "json", "jsonl", "porcelain", "markdown"];
+
+impl FromStr for Format {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<Format, ZugzugError> {
+        match s {
+            "table" => Ok(Format::Table),
+            "json" => Ok(Format::Json),
+            "jsonl" => Ok(Format::JsonLines),
+            "porcelain" | "porcelain-v1" => Ok(Format::Porcelain),
+            "markdown" | "md" => Ok(Format::Markdown),
+            _ => Err(ZugzugError::new(&format!("Unknown output format '{}'", s))),
+        }
+    }
+}
+
+/// How tables are drawn, selected with `--style` or `output.style`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TableStyle {
+    /// Columns aligned with a single space, no lines
+    Minimal,
+    /// Box-drawing borders
+    Bordered,
+    /// Borders made of `+`, `-` and `|`
+    Ascii,
+    /// Columns two spaces apart and titles underlined, no borders
+    Plain,
+}
+
+pub const STYLES: &[&str] = &["minimal", "bordered", "ascii", "plain"];
+
+impl FromStr for TableStyle {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<TableStyle, ZugzugError> {
+        match s {
+            "minimal" => Ok(TableStyle::Minimal),
+            "bordered" => Ok(TableStyle::Bordered),
+            "ascii" => Ok(TableStyle::Ascii),
+            "plain" => Ok(TableStyle::Plain),
+            _ => Err(ZugzugError::new(&format!("Unknown table style '{}'", s))),
+        }
+    }
+}
+
+static STYLE: AtomicU8 = AtomicU8::new(TableStyle::Minimal as u8);
+
+/// Draw every table of the rest of the process in `style`
+pub fn set_style(style: TableStyle) {
+    STYLE.store(style as u8, Ordering::SeqCst);
+}
+
+/// The prettytable format of the selected table style
+pub fn table_format() -> TableFormat {
+    let style = STYLE.load(Ordering::SeqCst);
+    if style == TableStyle::Bordered as u8 {
+        FormatBuilder::new()
+            .column_separator('│')
+            .borders('│')
+            .separator(LinePosition::Top, LineSeparator::new('─', '┬', '┌', '┐'))
+            .separator(LinePosition::Title, LineSeparator::new('─', '┼', '├', '┤'))
+            .separator(LinePosition::Bottom, LineSeparator::new('─', '┴', '└', '┘'))
+            .padding(1, 1)
+            .build()
+    } else if style == TableStyle::Ascii as u8 {
+        *format::consts::FORMAT_NO_LINESEP_WITH_TITLE
+    } else if style == TableStyle::Plain as u8 {
+        FormatBuilder::new()
+            .separator(LinePosition::Title, LineSeparator::new('-', ' ', ' ', ' '))
+            .padding(0, 2)
+            .build()
+    } else {
+        FormatBuilder::new().padding(0, 1).build()
+    }
+}
+
+/// When output is colored, selected with `--color`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Color when stdout is a terminal, unless `NO_COLOR` is set
+    Auto,
+    Always,
+    Never,
+}
+
+pub const COLOR_CHOICES: &[&str] = &["auto", "always", "never"];
+
+impl FromStr for ColorChoice {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<ColorChoice, ZugzugError> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(ZugzugError::new(&format!("Unknown color choice '{}'", s))),
+        }
+    }
+}
+
+static COLOR: AtomicU8 = AtomicU8::new(ColorChoice::Auto as u8);
+
+/// Color output of the rest of the process according to `choice`
+pub fn set_color(choice: ColorChoice) {
+    COLOR.store(choice as u8, Ordering::SeqCst);
+}
+
+/// Whether `NO_COLOR` asks for output without color, see https://no-color.org
+pub fn no_color_env() -> bool {
+    env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty())
+}
+
+/// Whether output should be colored
+pub fn use_color() -> bool {
+    let choice = COLOR.load(Ordering::SeqCst);
+    if choice == ColorChoice::Always as u8 {
+        true
+    } else if choice == ColorChoice::Never as u8 {
+        false
+    } else {
+        !no_color_env() && io::stdout().is_terminal()
+    }
+}
+
+/// Colors `paint` can use
+#[derive(Debug, Clone, Copy)]
+pub enum Color {
+    Red,
+    Green,
+}
+
+/// Wrap text in an ANSI color escape, if `use_color` says so
+///
+/// Tables don't count the escapes when aligning columns.
+pub fn paint(text: &str, color: Color) -> String {
+    if !use_color() {
+        return text.to_string();
+    }
+    let code = match color {
+        Color::Red => 31,
+        Color::Green => 32,
+    };
+    format!("\x1b[{}m{}\x1b[0m", code, text)
+}
+
+/// Print a JSON value on a single line
+pub fn print_json(value: &serde_json::Value) {
+    println!("{}", value);
+}
+
+/// Print JSON values as an array, or one per line for `Format::JsonLines`
+pub fn print_json_records(values: Vec<serde_json::Value>, format: Format) {
+    if format == Format::JsonLines {
+        for value in values.iter() {
+            print_json(value);
+        }
+    } else {
+        print_json(&serde_json::Value::Array(values));
+    }
+}
+
+/// Print one record of porcelain output
+///
+/// This is version 1 of the porcelain format, which won't change in
+/// incompatible ways:
+///
+/// - one record per line, with tab separated fields
+/// - the first field is the record type, e.g. `dir` or `bucket`
+/// - fields are never removed or reordered, new ones are only appended
+/// - tabs, newlines and backslashes in values are escaped as `\t`, `\n`
+///   and `\\`, empty values are empty fields
+pub fn print_porcelain(record: &str, fields: &[&str]) {
+    let mut line = record.to_string();
+    for field in fields {
+        line.push('\t');
+        line.push_str(
+            &field
+                .replace('\\', "\\\\")
+                .replace('\t', "\\t")
+                .replace('\n', "\\n"),
+        );
+    }
+    println!("{}", line);
+}
+
+/// Print a table as a GitHub-flavored markdown table
+///
+/// `titles` become the header row, which markdown tables can't do without.
+/// Pipes in cells are escaped so they don't start a new column.
+pub fn print_markdown(titles: &[&str], table: &Table) {
+    let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+    println!("{}", line(titles.iter().map(|t| t.to_string()).collect()));
+    println!(
+        "{}",
+        line(titles.iter().map(|_| "---".to_string()).collect())
+    );
+    for row in table.row_iter() {
+        let cells = row
+            .iter()
+            .map(|cell| cell.get_content().replace('|', "\\|").replace('\n', " "))
+            .collect();
+        println!("{}", line(cells));
+    }
+}
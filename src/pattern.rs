@@ -0,0 +1,81 @@
+use crate::errors::ZugzugError;
+use crate::store::Dir;
+use regex::Regex;
+use std::str::FromStr;
+
+/// Match text against a shell-style glob
+///
+/// `*` matches any run of characters and `?` any single character. The
+/// whole text has to match.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // position after the last `*` and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == text[t]) {
+            p += 1;
+            t += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            p += 1;
+            backtrack = Some((p, t));
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// Field of a directory a regex is matched against
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchOn {
+    Name,
+    Bucket,
+    Path,
+}
+
+pub const MATCH_ON: &[&str] = &["name", "bucket", "path"];
+
+impl FromStr for MatchOn {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<MatchOn, ZugzugError> {
+        match s {
+            "name" => Ok(MatchOn::Name),
+            "bucket" => Ok(MatchOn::Bucket),
+            "path" => Ok(MatchOn::Path),
+            _ => Err(ZugzugError::new(&format!("Unknown field '{}'", s))),
+        }
+    }
+}
+
+/// A regex matched against one field of directories
+pub struct DirRegex {
+    regex: Regex,
+    field: MatchOn,
+}
+
+impl DirRegex {
+    pub fn new(pattern: &str, field: MatchOn) -> Result<DirRegex, ZugzugError> {
+        let regex =
+            Regex::new(pattern).map_err(|e| ZugzugError::new(&format!("Invalid regex: {}", e)))?;
+        Ok(DirRegex { regex, field })
+    }
+
+    /// Check whether the regex matches anywhere in the directory's field
+    ///
+    /// The name is matched without the date prefix, the path in full.
+    pub fn is_match(&self, dir: &Dir) -> bool {
+        let text = match self.field {
+            MatchOn::Name => &dir.name,
+            MatchOn::Bucket => &dir.bucket,
+            MatchOn::Path => dir.path_str(),
+        };
+        self.regex.is_match(text)
+    }
+}
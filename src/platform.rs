@@ -0,0 +1,149 @@
+use crate::errors::ZugzugError;
+use std::env;
+use std::error;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// The command used to open paths in the OS file manager
+fn file_manager() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "open"
+    } else if cfg!(windows) {
+        "explorer"
+    } else {
+        "xdg-open"
+    }
+}
+
+/// Open a directory in Finder, Explorer or the desktop's file manager
+pub fn reveal(path: &Path) -> Result<(), Box<dyn error::Error + 'static>> {
+    let launcher = file_manager();
+    let status = Command::new(launcher)
+        .arg(path)
+        .status()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run {}: {}", launcher, e)))?;
+    // explorer.exe exits with 1 even when it opened the window
+    if !status.success() && !cfg!(windows) {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} exited with {}",
+            launcher, status
+        ))));
+    }
+    Ok(())
+}
+
+/// Clipboard commands to try, in order
+fn clipboard_commands() -> &'static [&'static [&'static str]] {
+    if cfg!(target_os = "macos") {
+        &[&["pbcopy"]]
+    } else if cfg!(windows) {
+        &[&["clip"]]
+    } else {
+        &[
+            &["wl-copy"],
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    }
+}
+
+/// Bytes available to the user on the filesystem holding `path`
+///
+/// Asks `df` on unix and .NET's `DriveInfo` through PowerShell on Windows.
+pub fn free_space(path: &Path) -> io::Result<u64> {
+    let output = if cfg!(windows) {
+        let script = format!(
+            "([System.IO.DriveInfo]::new('{}')).AvailableFreeSpace",
+            path.display().to_string().replace('\'', "''")
+        );
+        Command::new("powershell")
+            .args(["-NoProfile", "-Command", &script])
+            .stderr(Stdio::null())
+            .output()?
+    } else {
+        Command::new("df")
+            .arg("-Pk")
+            .arg(path)
+            .stderr(Stdio::null())
+            .output()?
+    };
+    let unparsable = || io::Error::new(io::ErrorKind::InvalidData, "Unexpected free space output");
+    if !output.status.success() {
+        return Err(unparsable());
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    if cfg!(windows) {
+        return stdout.trim().parse().map_err(|_| unparsable());
+    }
+    // Filesystem 1024-blocks Used Available Capacity Mounted-on
+    let available = stdout
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().nth(3))
+        .and_then(|kb| kb.parse::<u64>().ok())
+        .ok_or_else(unparsable)?;
+    Ok(available * 1024)
+}
+
+/// Put text on the system clipboard
+///
+/// Over SSH, or when no clipboard tool works, the text is sent to the
+/// terminal as an OSC 52 escape sequence, which most terminal emulators
+/// forward to the local clipboard.
+pub fn copy_to_clipboard(text: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    let over_ssh = env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some();
+    if !over_ssh {
+        for command in clipboard_commands() {
+            let child = Command::new(command[0])
+                .args(&command[1..])
+                .stdin(Stdio::piped())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn();
+            let mut child = match child {
+                Ok(child) => child,
+                Err(_) => continue,
+            };
+            if let Some(mut stdin) = child.stdin.take() {
+                stdin.write_all(text.as_bytes())?;
+            }
+            if child.wait()?.success() {
+                return Ok(());
+            }
+        }
+    }
+    osc52(text)
+}
+
+// write an OSC 52 clipboard sequence to the controlling terminal
+fn osc52(text: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    let sequence = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+    match OpenOptions::new().write(true).open("/dev/tty") {
+        Ok(mut tty) => tty.write_all(sequence.as_bytes())?,
+        Err(_) => io::stdout().write_all(sequence.as_bytes())?,
+    }
+    Ok(())
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,52 @@
+/// PowerShell module wrapping zz in cmdlets that return objects
+///
+/// The wrappers read `--format jsonl` output, one JSON object per line, and
+/// turn each line into a PSObject.
+pub const MODULE: &str = r#"# Generated by `zz powershell-module`
+
+function Get-ZzDir {
+    [CmdletBinding()]
+    param(
+        [Parameter(Position = 0)] [string] $Name,
+        [string] $Bucket,
+        [string] $Type,
+        [switch] $Git
+    )
+    $zzArgs = @('ls', '--format', 'jsonl')
+    if ($Name) { $zzArgs += @('--name', $Name) }
+    if ($Bucket) { $zzArgs += @('--bucket', $Bucket) }
+    if ($Type) { $zzArgs += @('--type', $Type) }
+    if ($Git) { $zzArgs += '--git' }
+    & zz @zzArgs | ForEach-Object { $_ | ConvertFrom-Json }
+}
+
+function Get-ZzBucket {
+    [CmdletBinding()]
+    param([switch] $Size)
+    $zzArgs = @('bucket', 'ls', '--format', 'jsonl')
+    if ($Size) { $zzArgs += '--size' }
+    & zz @zzArgs | ForEach-Object { $_ | ConvertFrom-Json }
+}
+
+function New-ZzDir {
+    [CmdletBinding()]
+    param(
+        [Parameter(Mandatory = $true, Position = 0)] [string] $Name,
+        [string] $Bucket,
+        [string] $Type,
+        [switch] $Private
+    )
+    $zzArgs = @('mkdir', $Name)
+    if ($Bucket) { $zzArgs += @('--bucket', $Bucket) }
+    if ($Type) { $zzArgs += @('--type', $Type) }
+    if ($Private) { $zzArgs += '--private' }
+    $path = & zz @zzArgs | Select-Object -Last 1
+    if (-not $path -or -not (Test-Path -LiteralPath $path)) {
+        Write-Error "zz mkdir failed: $path"
+        return
+    }
+    Get-Item -LiteralPath $path
+}
+
+Export-ModuleMember -Function Get-ZzDir, Get-ZzBucket, New-ZzDir
+"#;
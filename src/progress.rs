@@ -0,0 +1,55 @@
+use std::io::{self, IsTerminal, Write};
+
+const WIDTH: usize = 30;
+
+/// A minimal progress bar drawn on stderr
+///
+/// Nothing is drawn when stderr isn't a terminal, so piped output stays clean.
+pub struct Progress {
+    label: String,
+    total: usize,
+    done: usize,
+    visible: bool,
+}
+
+impl Progress {
+    pub fn new(label: &str, total: usize) -> Progress {
+        let progress = Progress {
+            label: label.to_string(),
+            total,
+            done: 0,
+            visible: io::stderr().is_terminal() && total > 0,
+        };
+        progress.draw();
+        progress
+    }
+
+    pub fn inc(&mut self) {
+        self.done += 1;
+        self.draw();
+    }
+
+    /// Clear the bar from the terminal
+    pub fn finish(&self) {
+        if self.visible {
+            eprint!("\r{}\r", " ".repeat(self.label.len() + WIDTH + 24));
+            let _ = io::stderr().flush();
+        }
+    }
+
+    fn draw(&self) {
+        if !self.visible {
+            return;
+        }
+        let filled = WIDTH * self.done / self.total;
+        eprint!(
+            "\r{} [{}{}] {}/{}",
+            self.label,
+            "#".repeat(filled),
+            " ".repeat(WIDTH - filled),
+            self.done,
+            self.total
+        );
+        let _ = io::stderr().flush();
+    }
+}
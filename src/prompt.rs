@@ -0,0 +1,218 @@
+use crate::errors::ZugzugError;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Which actions zz asks about before going ahead
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    /// Also changes that are easy to undo, like moving a directory
+    Always,
+    /// Removing or overwriting things, see `confirm`
+    Destructive,
+    /// Nothing, every action goes ahead as if confirmed
+    Never,
+}
+
+impl FromStr for Policy {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<Policy, ZugzugError> {
+        match s {
+            "always" => Ok(Policy::Always),
+            "destructive" => Ok(Policy::Destructive),
+            "never" => Ok(Policy::Never),
+            _ => Err(ZugzugError::new(&format!(
+                "Unknown confirm policy '{}', expected always, destructive or never",
+                s
+            ))),
+        }
+    }
+}
+
+static POLICY: AtomicU8 = AtomicU8::new(Policy::Destructive as u8);
+
+/// Set which actions are confirmed for the rest of the process
+pub fn set_policy(policy: Policy) {
+    POLICY.store(policy as u8, Ordering::SeqCst);
+}
+
+fn policy() -> Policy {
+    match POLICY.load(Ordering::SeqCst) {
+        p if p == Policy::Always as u8 => Policy::Always,
+        p if p == Policy::Never as u8 => Policy::Never,
+        _ => Policy::Destructive,
+    }
+}
+
+/// Confirm an action that removes or overwrites something
+///
+/// Asked unless the policy is `never`, e.g. with `--yes`.
+pub fn confirm(question: &str) -> io::Result<bool> {
+    match policy() {
+        Policy::Never => Ok(true),
+        _ => ask(question),
+    }
+}
+
+/// Confirm a change that is easy to undo, only asked with policy `always`
+pub fn confirm_change(question: &str) -> io::Result<bool> {
+    match policy() {
+        Policy::Always => ask(question),
+        _ => Ok(true),
+    }
+}
+
+/// Check a guess at what the user meant, e.g. a directory name
+///
+/// Unlike actions, guesses are never taken for granted: with policy `never`
+/// the answer is no.
+pub fn confirm_guess(question: &str) -> io::Result<bool> {
+    match policy() {
+        Policy::Never => Ok(false),
+        _ => ask(question),
+    }
+}
+
+/// Pick one of a numbered list of choices, e.g. among ambiguous names
+///
+/// Returns `None` when nothing was picked, which is also the answer with
+/// policy `never`. When stdin is not a terminal a single answer is read,
+/// so choices can be piped in, and anything invalid picks nothing.
+pub fn select(question: &str, items: &[String]) -> io::Result<Option<usize>> {
+    if policy() == Policy::Never || items.is_empty() {
+        return Ok(None);
+    }
+    eprintln!("{}", question);
+    for (i, item) in items.iter().enumerate() {
+        eprintln!("{:>3}  {}", i + 1, item);
+    }
+    let pick = t!("Pick 1-{count} (enter=none):", count = items.len());
+    loop {
+        let answer = match read_answer(&format!("{} ", pick))? {
+            Some(answer) if !answer.is_empty() => answer,
+            _ => return Ok(None),
+        };
+        match answer.parse::<usize>() {
+            Ok(n) if n >= 1 && n <= items.len() => return Ok(Some(n - 1)),
+            _ if interactive() => {
+                eprintln!(
+                    "{}",
+                    t!("Not one of the choices: '{answer}'", answer = answer)
+                )
+            }
+            _ => return Ok(None),
+        }
+    }
+}
+
+/// Ask for a line of text, e.g. a name to use instead of one that is taken
+///
+/// An empty answer gives `default`, as does policy `never`.
+pub fn input(question: &str, default: Option<&str>) -> io::Result<Option<String>> {
+    if policy() == Policy::Never {
+        return Ok(default.map(String::from));
+    }
+    let prompt = match default {
+        Some(default) => format!("{} [{}] ", question, default),
+        None => format!("{} ", question),
+    };
+    Ok(match read_answer(&prompt)? {
+        Some(answer) if !answer.is_empty() => Some(answer),
+        _ => default.map(String::from),
+    })
+}
+
+/// Whether someone is there to answer, i.e. stdin is a terminal
+pub fn interactive() -> bool {
+    io::stdin().is_terminal()
+}
+
+/// Ask a yes/no question, defaulting to no
+///
+/// `y` and `yes` are understood in every language, besides their
+/// translations.
+fn ask(question: &str) -> io::Result<bool> {
+    let answer = read_answer(&format!("{} {} ", question, t!("[y/N]")))?.unwrap_or_default();
+    let answer = answer.to_lowercase();
+    Ok(["y", "yes"]
+        .iter()
+        .any(|yes| answer == *yes || answer == t!(yes).to_lowercase()))
+}
+
+// show a prompt and read a trimmed answer from stdin, `None` at its end
+//
+// Prompts go to stderr, so they show even when the output of zz is
+// captured, as in `cd "$(zz cd name)"`.
+fn read_answer(prompt: &str) -> io::Result<Option<String>> {
+    eprint!("{}", prompt);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer)? == 0 {
+        eprintln!();
+        return Ok(None);
+    }
+    Ok(Some(answer.trim().to_string()))
+}
+
+/// Let the user toggle entries of a numbered list, returning the selection
+///
+/// Entries are toggled by number or range (`3`, `1-4`), `a` selects all,
+/// `n` clears the selection and an empty line finishes. `q` aborts with an
+/// empty selection.
+pub fn checklist(items: &[String]) -> io::Result<Vec<usize>> {
+    let mut selected = vec![false; items.len()];
+    loop {
+        for (i, item) in items.iter().enumerate() {
+            let mark = if selected[i] { "x" } else { " " };
+            eprintln!("[{}] {:>3}  {}", mark, i + 1, item);
+        }
+        let toggle = t!("Toggle (numbers, ranges, a=all, n=none, q=quit, enter=done):");
+        let answer = match read_answer(&format!("{} ", toggle))? {
+            Some(answer) => answer,
+            None => return Ok(vec![]),
+        };
+        match answer.as_str() {
+            "" => break,
+            "q" => return Ok(vec![]),
+            "a" => selected.iter_mut().for_each(|s| *s = true),
+            "n" => selected.iter_mut().for_each(|s| *s = false),
+            _ => {
+                for token in answer.split(|c: char| c == ',' || c.is_whitespace()) {
+                    if token.is_empty() {
+                        continue;
+                    }
+                    match parse_range(token, items.len()) {
+                        Some((start, end)) => {
+                            for s in &mut selected[start - 1..end] {
+                                *s = !*s;
+                            }
+                        }
+                        None => eprintln!("{}", t!("Ignoring '{token}'", token = token)),
+                    }
+                }
+            }
+        }
+    }
+    Ok(selected
+        .iter()
+        .enumerate()
+        .filter(|&(_, &s)| s)
+        .map(|(i, _)| i)
+        .collect())
+}
+
+/// Parse `N` or `N-M` into an inclusive, 1-based range within `len`
+fn parse_range(token: &str, len: usize) -> Option<(usize, usize)> {
+    let (start, end) = match token.find('-') {
+        Some(i) => (token[..i].parse().ok()?, token[i + 1..].parse().ok()?),
+        None => {
+            let n = token.parse().ok()?;
+            (n, n)
+        }
+    };
+    if start == 0 || start > end || end > len {
+        return None;
+    }
+    Some((start, end))
+}
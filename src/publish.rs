@@ -0,0 +1,206 @@
+use crate::config::{PublishTarget, SecretsConfig};
+use crate::errors::ZugzugError;
+use crate::fsutil;
+use crate::secrets;
+use std::error;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+
+/// A remote location directories can be published to
+pub trait Publisher {
+    /// Upload `dir` under `name` and return where it ended up
+    fn publish(&self, dir: &Path, name: &str) -> Result<String, Box<dyn error::Error + 'static>>;
+}
+
+/// Returns the publisher for a configured target
+///
+/// The target's secret, if any, is looked up in `secrets`.
+pub fn publisher(
+    target: &PublishTarget,
+    secrets: &SecretsConfig,
+) -> Result<Box<dyn Publisher>, Box<dyn error::Error + 'static>> {
+    let destination = target.target.trim_end_matches('/').to_string();
+    let password = match &target.secret {
+        Some(name) => Some(secrets::get(secrets, name)?),
+        None => None,
+    };
+    match target.kind.as_str() {
+        "rsync" => Ok(Box::new(Rsync {
+            destination,
+            password,
+        })),
+        "scp" if password.is_some() => Err(Box::new(ZugzugError::new(
+            "scp targets log in with SSH keys, remove the secret from the target",
+        ))),
+        "scp" => Ok(Box::new(Scp { destination })),
+        "webdav" => {
+            let credentials = match (&target.user, password) {
+                (Some(user), Some(password)) => Some(format!(
+                    "user = \"{}\"\n",
+                    curl_escape(&format!("{}:{}", user, password))
+                )),
+                (None, Some(_)) => {
+                    return Err(Box::new(ZugzugError::new(
+                        "WebDAV targets with a secret need a user",
+                    )))
+                }
+                _ => None,
+            };
+            Ok(Box::new(WebDav {
+                url: destination,
+                credentials,
+            }))
+        }
+        kind => Err(Box::new(ZugzugError::new(&format!(
+            "Unknown publish kind '{}', expected rsync, scp or webdav",
+            kind
+        )))),
+    }
+}
+
+fn run(command: &mut Command) -> Result<(), Box<dyn error::Error + 'static>> {
+    run_with_input(command, None)
+}
+
+// run a command, failing unless it succeeds
+fn run_with_input(
+    command: &mut Command,
+    input: Option<&str>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let status = status(command, input)?;
+    if !status.success() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} exited with {}",
+            program, status
+        ))));
+    }
+    Ok(())
+}
+
+// run a command, writing input to its stdin if given
+fn status(
+    command: &mut Command,
+    input: Option<&str>,
+) -> Result<ExitStatus, Box<dyn error::Error + 'static>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    if input.is_some() {
+        command.stdin(Stdio::piped());
+    }
+    let mut child = command
+        .spawn()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run {}: {}", program, e)))?;
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes())?;
+    }
+    Ok(child.wait()?)
+}
+
+/// Sync with `rsync`, removing remote files that no longer exist locally
+///
+/// A password is only used by rsync daemons, SSH targets log in with keys.
+struct Rsync {
+    destination: String,
+    password: Option<String>,
+}
+
+impl Publisher for Rsync {
+    fn publish(&self, dir: &Path, name: &str) -> Result<String, Box<dyn error::Error + 'static>> {
+        let remote = format!("{}/{}/", self.destination, name);
+        let mut command = Command::new("rsync");
+        command
+            .args(["-a", "--delete"])
+            .arg(format!("{}/", dir.display()))
+            .arg(&remote);
+        if let Some(password) = &self.password {
+            command.env("RSYNC_PASSWORD", password);
+        }
+        run(&mut command)?;
+        Ok(remote)
+    }
+}
+
+/// Copy with `scp`, for hosts without rsync
+struct Scp {
+    destination: String,
+}
+
+impl Publisher for Scp {
+    fn publish(&self, dir: &Path, name: &str) -> Result<String, Box<dyn error::Error + 'static>> {
+        let remote = format!("{}/{}", self.destination, name);
+        run(Command::new("scp").args(["-r", "-q"]).arg(dir).arg(&remote))?;
+        Ok(remote)
+    }
+}
+
+/// Upload to a WebDAV share with `curl`
+///
+/// Credentials come from the target's secret, or else from `~/.netrc`.
+struct WebDav {
+    url: String,
+    /// curl config setting the user, passed on stdin so the password
+    /// doesn't show up in the process list
+    credentials: Option<String>,
+}
+
+impl WebDav {
+    fn curl(&self, args: &[&str]) -> Command {
+        let mut command = Command::new("curl");
+        match self.credentials {
+            Some(_) => command.args(["-K", "-"]),
+            None => command.arg("--netrc-optional"),
+        };
+        command.args(args);
+        command
+    }
+
+    // create a collection, ignoring failures for existing collections
+    fn mkcol(&self, url: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+        let mut command = self.curl(&["-sS", "-o", "/dev/null", "-X", "MKCOL"]);
+        command.arg(format!("{}/", url));
+        status(&mut command, self.credentials.as_deref())?;
+        Ok(())
+    }
+}
+
+impl Publisher for WebDav {
+    fn publish(&self, dir: &Path, name: &str) -> Result<String, Box<dyn error::Error + 'static>> {
+        let base = format!("{}/{}", self.url, encode_path(Path::new(name)));
+        self.mkcol(&base)?;
+        for file in fsutil::walk_files(dir)? {
+            let mut collection = base.clone();
+            if let Some(parent) = file.parent() {
+                for component in parent.components() {
+                    collection = format!("{}/{}", collection, encode_path(Path::new(&component)));
+                    self.mkcol(&collection)?;
+                }
+            }
+            let mut command = self.curl(&["-sSf", "-T"]);
+            command
+                .arg(dir.join(&file))
+                .arg(format!("{}/{}", base, encode_path(&file)));
+            run_with_input(&mut command, self.credentials.as_deref())?;
+        }
+        Ok(format!("{}/", base))
+    }
+}
+
+// escape a value for a double-quoted string in a curl config file
+fn curl_escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+// percent-encode a relative path for use in a URL
+fn encode_path(path: &Path) -> String {
+    let mut encoded = String::new();
+    for byte in path.to_string_lossy().bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
@@ -0,0 +1,27 @@
+use crate::errors::ZugzugError;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// Turn on read-only mode for the rest of the process
+pub fn enable() {
+    READ_ONLY.store(true, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    READ_ONLY.load(Ordering::SeqCst)
+}
+
+/// Fail if read-only mode is on
+///
+/// Called at the start of every code path that changes the store or the
+/// contents of buckets.
+pub fn check() -> Result<(), ZugzugError> {
+    if is_enabled() {
+        Err(ZugzugError::new(&t!(
+            "zz is in read-only mode (--read-only or ZZ_READONLY=1)"
+        )))
+    } else {
+        Ok(())
+    }
+}
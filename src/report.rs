@@ -0,0 +1,52 @@
+use crate::errors::ZugzugError;
+use chrono::{Datelike, Duration, NaiveDate};
+use std::str::FromStr;
+
+/// Length of the period `zz report` covers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    /// Monday to Sunday
+    Week,
+    /// Calendar month
+    Month,
+}
+
+pub const PERIODS: &[&str] = &["week", "month"];
+
+impl FromStr for Period {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<Period, ZugzugError> {
+        match s {
+            "week" => Ok(Period::Week),
+            "month" => Ok(Period::Month),
+            _ => Err(ZugzugError::new(&format!("Unknown period '{}'", s))),
+        }
+    }
+}
+
+/// First and last day of the period containing `today`
+///
+/// The current period ends today. With `previous`, the whole period before
+/// it is returned instead, e.g. last week from Monday to Sunday.
+pub fn range(period: Period, previous: bool, today: NaiveDate) -> (NaiveDate, NaiveDate) {
+    match period {
+        Period::Week => {
+            let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+            if previous {
+                (monday - Duration::days(7), monday - Duration::days(1))
+            } else {
+                (monday, today)
+            }
+        }
+        Period::Month => {
+            let first = today.with_day(1).unwrap();
+            if previous {
+                let last = first - Duration::days(1);
+                (last.with_day(1).unwrap(), last)
+            } else {
+                (first, today)
+            }
+        }
+    }
+}
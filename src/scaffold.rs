@@ -0,0 +1,127 @@
+use crate::errors::ZugzugError;
+use std::error;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Project types `zz mkdir --type` knows how to scaffold
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProjectType {
+    Rust,
+    Python,
+    Node,
+}
+
+pub const PROJECT_TYPES: &[&str] = &["rust", "python", "node"];
+
+impl FromStr for ProjectType {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<ProjectType, ZugzugError> {
+        match s {
+            "rust" => Ok(ProjectType::Rust),
+            "python" => Ok(ProjectType::Python),
+            "node" => Ok(ProjectType::Node),
+            _ => Err(ZugzugError::new(&format!("Unknown project type '{}'", s))),
+        }
+    }
+}
+
+impl fmt::Display for ProjectType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProjectType::Rust => write!(f, "rust"),
+            ProjectType::Python => write!(f, "python"),
+            ProjectType::Node => write!(f, "node"),
+        }
+    }
+}
+
+/// Guess the project type of an existing directory from its manifest
+pub fn detect(dir: &Path) -> Option<ProjectType> {
+    if dir.join("Cargo.toml").is_file() {
+        Some(ProjectType::Rust)
+    } else if dir.join("pyproject.toml").is_file() || dir.join("setup.py").is_file() {
+        Some(ProjectType::Python)
+    } else if dir.join("package.json").is_file() {
+        Some(ProjectType::Node)
+    } else {
+        None
+    }
+}
+
+// package names are restricted for cargo and npm, keep to [a-z0-9-]
+fn package_name(name: &str) -> String {
+    let package: String = name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let package = package.trim_matches('-').to_string();
+    if package.is_empty() {
+        "scratch".to_string()
+    } else {
+        package
+    }
+}
+
+/// Write a `.gitignore` and a minimal skeleton for the project type
+///
+/// Existing files are left alone.
+pub fn scaffold(
+    dir: &Path,
+    name: &str,
+    project_type: ProjectType,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let package = package_name(name);
+    let files: Vec<(&str, String)> = match project_type {
+        ProjectType::Rust => vec![
+            (".gitignore", "/target\n".to_string()),
+            (
+                "Cargo.toml",
+                format!(
+                    "[package]\nname = \"{}\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n[dependencies]\n",
+                    package
+                ),
+            ),
+            (
+                "src/main.rs",
+                "fn main() {\n    println!(\"Hello, world!\");\n}\n".to_string(),
+            ),
+        ],
+        ProjectType::Python => vec![
+            (
+                ".gitignore",
+                "__pycache__/\n*.py[cod]\n.venv/\n*.egg-info/\n".to_string(),
+            ),
+            ("requirements.txt", String::new()),
+            (
+                "main.py",
+                "def main():\n    pass\n\n\nif __name__ == \"__main__\":\n    main()\n".to_string(),
+            ),
+        ],
+        ProjectType::Node => vec![
+            (".gitignore", "node_modules/\n".to_string()),
+            (
+                "package.json",
+                format!(
+                    "{{\n  \"name\": \"{}\",\n  \"version\": \"0.1.0\",\n  \"private\": true,\n  \"main\": \"index.js\"\n}}\n",
+                    package
+                ),
+            ),
+            ("index.js", String::new()),
+        ],
+    };
+    for (file, contents) in files {
+        let path = dir.join(file);
+        if path.exists() {
+            continue;
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, contents)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,219 @@
+use crate::config::SecretsConfig;
+use crate::errors::ZugzugError;
+use crate::fsutil;
+use std::error;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+
+/// Service name secrets are filed under in the OS keyring
+const SERVICE: &str = "zz";
+
+/// Where secrets are kept
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// The OS keyring: Secret Service on Linux, the Keychain on macOS
+    Keyring,
+    /// age-encrypted values in `[secrets.values]` of the config
+    Age,
+}
+
+impl FromStr for Backend {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<Backend, ZugzugError> {
+        match s {
+            "keyring" => Ok(Backend::Keyring),
+            "age" => Ok(Backend::Age),
+            _ => Err(ZugzugError::new(&format!(
+                "Unknown secrets backend '{}', expected keyring or age",
+                s
+            ))),
+        }
+    }
+}
+
+impl Backend {
+    pub fn from_config(config: &SecretsConfig) -> Result<Backend, ZugzugError> {
+        config.backend.as_deref().unwrap_or("keyring").parse()
+    }
+}
+
+// run a command, feeding it input, and return what it printed
+fn output(command: &mut Command, input: &str) -> Result<String, Box<dyn error::Error + 'static>> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run {}: {}", program, e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(input.as_bytes())?;
+    }
+    let result = child.wait_with_output()?;
+    if !result.status.success() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "{} exited with {}: {}",
+            program,
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        ))));
+    }
+    Ok(String::from_utf8_lossy(&result.stdout).into_owned())
+}
+
+/// Look up a secret by name
+pub fn get(config: &SecretsConfig, name: &str) -> Result<String, Box<dyn error::Error + 'static>> {
+    let secret = match Backend::from_config(config)? {
+        Backend::Keyring => keyring_get(name)?,
+        Backend::Age => {
+            let encrypted = config.values.get(name).ok_or_else(|| {
+                ZugzugError::new(&format!("No secret '{}' in [secrets.values]", name))
+            })?;
+            let identity = match &config.identity {
+                Some(identity) => fsutil::expand_path(identity)?,
+                None => fsutil::expand_path("~/.zz-age.key")?,
+            };
+            output(
+                Command::new("age").args(["-d", "-i"]).arg(identity),
+                encrypted,
+            )?
+        }
+    };
+    Ok(secret.trim_end_matches(['\r', '\n']).to_string())
+}
+
+/// Store a secret
+///
+/// The keyring keeps it right away. With age the encrypted value is
+/// returned instead, to be added to `[secrets.values]`.
+pub fn set(
+    config: &SecretsConfig,
+    name: &str,
+    secret: &str,
+) -> Result<Option<String>, Box<dyn error::Error + 'static>> {
+    match Backend::from_config(config)? {
+        Backend::Keyring => {
+            keyring_set(name, secret)?;
+            Ok(None)
+        }
+        Backend::Age => {
+            if config.recipients.is_empty() {
+                return Err(Box::new(ZugzugError::new(
+                    "Set secrets.recipients to the age recipients to encrypt to",
+                )));
+            }
+            let mut command = Command::new("age");
+            command.arg("-a");
+            for recipient in config.recipients.iter() {
+                command.arg("-r").arg(recipient);
+            }
+            Ok(Some(output(&mut command, secret)?))
+        }
+    }
+}
+
+/// Remove a secret from the keyring
+///
+/// age secrets are removed by deleting them from the config.
+pub fn remove(config: &SecretsConfig, name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    match Backend::from_config(config)? {
+        Backend::Keyring => keyring_remove(name),
+        Backend::Age => Err(Box::new(ZugzugError::new(&format!(
+            "Remove '{}' from [secrets.values] in the config",
+            name
+        )))),
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn keyring_get(name: &str) -> Result<String, Box<dyn error::Error + 'static>> {
+    output(
+        Command::new("security").args(["find-generic-password", "-s", SERVICE, "-a", name, "-w"]),
+        "",
+    )
+}
+
+// with `-w` last and no value security prompts for the secret, and asks
+// again to confirm it, so it is read from stdin rather than being visible
+// in `ps`
+#[cfg(target_os = "macos")]
+fn keyring_set(name: &str, secret: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    output(
+        Command::new("security").args([
+            "add-generic-password",
+            "-U",
+            "-s",
+            SERVICE,
+            "-a",
+            name,
+            "-w",
+        ]),
+        &format!("{}\n{}\n", secret, secret),
+    )?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn keyring_remove(name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    output(
+        Command::new("security").args(["delete-generic-password", "-s", SERVICE, "-a", name]),
+        "",
+    )?;
+    Ok(())
+}
+
+// secret-tool talks to the Secret Service, e.g. GNOME Keyring or KWallet
+#[cfg(all(unix, not(target_os = "macos")))]
+fn keyring_get(name: &str) -> Result<String, Box<dyn error::Error + 'static>> {
+    let secret = output(
+        Command::new("secret-tool").args(["lookup", "service", SERVICE, "account", name]),
+        "",
+    )?;
+    if secret.is_empty() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "No secret '{}' in the keyring",
+            name
+        ))));
+    }
+    Ok(secret)
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn keyring_set(name: &str, secret: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    output(
+        Command::new("secret-tool")
+            .arg("store")
+            .arg(format!("--label=zz {}", name))
+            .args(["service", SERVICE, "account", name]),
+        secret,
+    )?;
+    Ok(())
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn keyring_remove(name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    output(
+        Command::new("secret-tool").args(["clear", "service", SERVICE, "account", name]),
+        "",
+    )?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn keyring_get(_name: &str) -> Result<String, Box<dyn error::Error + 'static>> {
+    Err(Box::new(ZugzugError::new(
+        "The keyring is not supported on this platform, use the age backend",
+    )))
+}
+
+#[cfg(not(unix))]
+fn keyring_set(name: &str, _secret: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    keyring_get(name).map(|_| ())
+}
+
+#[cfg(not(unix))]
+fn keyring_remove(name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+    keyring_get(name).map(|_| ())
+}
@@ -0,0 +1,200 @@
+use crate::config::Config;
+use crate::envvars;
+use crate::fsutil;
+use crate::hooks;
+use crate::store::{Dir, Store};
+use crate::webhook::{self, Event};
+use serde_json::{json, Value};
+use std::error;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const APP_ERROR: i64 = -32000;
+
+/// A JSON-RPC error: code and message
+type RpcError = (i64, String);
+
+fn dir_json(dir: &Dir) -> Value {
+    json!({
+        "bucket": dir.bucket,
+        "date": dir.date,
+        "name": dir.name,
+        "path": dir.path_str(),
+    })
+}
+
+fn app_error(e: Box<dyn error::Error + 'static>) -> RpcError {
+    (APP_ERROR, e.to_string())
+}
+
+fn string_param<'a>(params: &'a Value, name: &str) -> Result<Option<&'a str>, RpcError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err((INVALID_PARAMS, format!("'{}' must be a string", name))),
+    }
+}
+
+fn required_param<'a>(params: &'a Value, name: &str) -> Result<&'a str, RpcError> {
+    string_param(params, name)?.ok_or_else(|| (INVALID_PARAMS, format!("'{}' is required", name)))
+}
+
+fn resolve(store: &Store, name: &str) -> Result<(Dir, f64), RpcError> {
+    store
+        .best_dir_match(name)
+        .map_err(app_error)?
+        .ok_or_else(|| (APP_ERROR, format!("No directory matches '{}'", name)))
+}
+
+/// Answer a single method call
+///
+/// The store is loaded for every call so changes made by the CLI in the
+/// meantime are picked up, and saved after it if the call changed it.
+fn call(method: &str, params: &Value) -> Result<Value, RpcError> {
+    let mut store = Store::load().map_err(app_error)?;
+    let result = call_with(&mut store, method, params);
+    store.save().map_err(app_error)?;
+    result
+}
+
+fn call_with(store: &mut Store, method: &str, params: &Value) -> Result<Value, RpcError> {
+    match method {
+        "buckets" => Ok(json!(store
+            .buckets_iter()
+            .map(|b| json!({"name": b.name, "path": b.path}))
+            .collect::<Vec<_>>())),
+        "list" => {
+            let buckets = store
+                .select_buckets(string_param(params, "bucket")?)
+                .map_err(app_error)?;
+            let mut dirs = vec![];
+            for bucket in buckets {
+                dirs.extend(bucket.dirs().map_err(app_error)?.iter().map(dir_json));
+            }
+            Ok(Value::Array(dirs))
+        }
+        "resolve" => {
+            let (dir, score) = resolve(store, required_param(params, "name")?)?;
+            store.record_access(&dir.path).map_err(app_error)?;
+            let mut result = dir_json(&dir);
+            result["score"] = json!(score);
+            Ok(result)
+        }
+        "meta" => {
+            let (dir, _) = resolve(store, required_param(params, "name")?)?;
+            let meta = store.dir_meta(&dir.path).unwrap_or_default();
+            let mut result = dir_json(&dir);
+            result["meta"] = serde_json::to_value(meta).map_err(|e| (APP_ERROR, e.to_string()))?;
+            Ok(result)
+        }
+        "mkdir" => {
+            let name = required_param(params, "name")?;
+            let bucket = match string_param(params, "bucket")? {
+                Some(bucket_name) => store.resolve_bucket(bucket_name).map_err(app_error)?,
+                None => store
+                    .default_bucket()
+                    .ok_or_else(|| (APP_ERROR, "No bucket to choose from".to_string()))?,
+            }
+            .clone();
+            let config = Config::load().map_err(app_error)?;
+            fsutil::check_free_space(bucket.mkdir_root(), &config.mkdir)
+                .map_err(|e| (APP_ERROR, e.to_string()))?;
+            let path = bucket
+                .make_dir(name, false, Some(&config.names))
+                .map_err(app_error)?;
+            let env = envvars::dir_env(store, &bucket.name, &path);
+            store.save().map_err(app_error)?;
+            hooks::run_to_stderr(&bucket.hooks.post_create, &path, &env).map_err(app_error)?;
+            webhook::notify(&config.webhooks, Event::Created, &bucket.name, &path);
+            let dir = bucket
+                .dirs()
+                .map_err(app_error)?
+                .into_iter()
+                .find(|dir| dir.path == path)
+                .ok_or_else(|| (APP_ERROR, "Created directory went missing".to_string()))?;
+            Ok(dir_json(&dir))
+        }
+        _ => Err((METHOD_NOT_FOUND, format!("Unknown method '{}'", method))),
+    }
+}
+
+/// Turn one request line into a response, or `None` for notifications
+fn respond(line: &str) -> Option<Value> {
+    let request: Value = match serde_json::from_str(line) {
+        Ok(request) => request,
+        Err(e) => {
+            return Some(json!({
+                "jsonrpc": "2.0",
+                "id": null,
+                "error": {"code": PARSE_ERROR, "message": e.to_string()},
+            }))
+        }
+    };
+    let id = request.get("id").cloned();
+    let result = match request.get("method").and_then(Value::as_str) {
+        Some(method) => call(method, request.get("params").unwrap_or(&Value::Null)),
+        None => Err((INVALID_REQUEST, "Missing method".to_string())),
+    };
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+        Err((code, message)) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": {"code": code, "message": message},
+        }),
+    })
+}
+
+/// Answer line-delimited JSON-RPC requests until the input ends
+fn handle_connection(input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Some(response) = respond(&line) {
+            writeln!(output, "{}", response)?;
+            output.flush()?;
+        }
+    }
+    Ok(())
+}
+
+/// Serve JSON-RPC 2.0 requests on stdin and stdout
+pub fn stdio() -> Result<(), Box<dyn error::Error + 'static>> {
+    let stdin = io::stdin();
+    handle_connection(stdin.lock(), io::stdout())?;
+    Ok(())
+}
+
+/// Serve JSON-RPC 2.0 requests on a unix socket, one client at a time
+#[cfg(unix)]
+pub fn socket(path: &Path) -> Result<(), Box<dyn error::Error + 'static>> {
+    use std::io::BufReader;
+    use std::os::unix::net::UnixListener;
+
+    if path.exists() {
+        std::fs::remove_file(path)?;
+    }
+    let listener = UnixListener::bind(path)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let reader = BufReader::new(stream.try_clone()?);
+        if let Err(e) = handle_connection(reader, stream) {
+            error!("Connection closed: {}", e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn socket(_path: &Path) -> Result<(), Box<dyn error::Error + 'static>> {
+    Err(Box::new(crate::errors::ZugzugError::new(
+        "Unix sockets are not supported on this platform",
+    )))
+}
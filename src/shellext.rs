@@ -0,0 +1,105 @@
+use crate::errors::ZugzugError;
+use std::error;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Registry key context menu entries of the current user go below
+const CLASSES: &str = r"HKCU\Software\Classes";
+
+/// An entry of the Explorer context menu
+struct Entry {
+    /// Key below `CLASSES`
+    key: &'static str,
+    label: &'static str,
+    /// Arguments zz runs with, `%V` or `%1` being the folder clicked
+    args: &'static str,
+}
+
+/// Entries for right-clicking the background of a folder and a folder
+const ENTRIES: &[Entry] = &[
+    Entry {
+        key: r"Directory\Background\shell\zz.mkdir",
+        label: "New zz directory here",
+        args: r#"windows new-here "%V""#,
+    },
+    Entry {
+        key: r"Directory\shell\zz.mkdir",
+        label: "New zz directory here",
+        args: r#"windows new-here "%1""#,
+    },
+    Entry {
+        key: r"Directory\Background\shell\zz.open",
+        label: "Open in zz",
+        args: r#"windows open "%V""#,
+    },
+    Entry {
+        key: r"Directory\shell\zz.open",
+        label: "Open in zz",
+        args: r#"windows open "%1""#,
+    },
+];
+
+// run reg.exe, which reports what went wrong on stderr
+fn reg(args: &[&str]) -> Result<bool, Box<dyn error::Error + 'static>> {
+    let output = Command::new("reg")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run reg: {}", e)))?;
+    if !output.status.success() && args[0] != "query" {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "reg {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    Ok(output.status.success())
+}
+
+fn check_windows() -> Result<(), Box<dyn error::Error + 'static>> {
+    if !cfg!(windows) {
+        return Err(Box::new(ZugzugError::new(
+            "The Explorer context menu is only available on Windows",
+        )));
+    }
+    Ok(())
+}
+
+/// Add the zz entries to the Explorer context menu, running `exe`
+///
+/// The entries are registered for the current user only, so no
+/// administrator rights are needed. They run zz through `cmd` so that the
+/// window stays open when something goes wrong.
+pub fn install(exe: &Path) -> Result<(), Box<dyn error::Error + 'static>> {
+    check_windows()?;
+    let exe = exe.to_string_lossy();
+    for entry in ENTRIES {
+        let key = format!(r"{}\{}", CLASSES, entry.key);
+        let command = format!(r#"cmd.exe /c ""{}" {} || pause""#, exe, entry.args);
+        reg(&["add", &key, "/ve", "/d", entry.label, "/f"])?;
+        reg(&["add", &key, "/v", "Icon", "/d", &exe, "/f"])?;
+        reg(&[
+            "add",
+            &format!(r"{}\command", key),
+            "/ve",
+            "/d",
+            &command,
+            "/f",
+        ])?;
+    }
+    Ok(())
+}
+
+/// Remove the entries `install` added, returning how many there were
+pub fn uninstall() -> Result<usize, Box<dyn error::Error + 'static>> {
+    check_windows()?;
+    let mut removed = 0;
+    for entry in ENTRIES {
+        let key = format!(r"{}\{}", CLASSES, entry.key);
+        if reg(&["query", &key])? {
+            reg(&["delete", &key, "/f"])?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
@@ -0,0 +1,104 @@
+use crate::errors::ZugzugError;
+use crate::fsutil;
+use chrono::prelude::*;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory inside a tracked directory that holds its snapshots
+pub const DIR: &str = ".zz-snapshots";
+
+/// Format of snapshot names, the time they were taken
+const NAME_FORMAT: &str = "%Y%m%dT%H%M%S";
+
+/// A copy of a directory's contents at one point in time
+pub struct Snapshot {
+    pub name: String,
+    pub path: PathBuf,
+    pub taken: Option<DateTime<Local>>,
+}
+
+/// The snapshots of a directory, oldest first
+pub fn list(dir: &Path) -> Result<Vec<Snapshot>, Box<dyn error::Error + 'static>> {
+    let root = dir.join(DIR);
+    if !root.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut snapshots = vec![];
+    for entry in fs::read_dir(&root)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_dir() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let taken = NaiveDateTime::parse_from_str(&name, NAME_FORMAT)
+            .ok()
+            .and_then(|taken| Local.from_local_datetime(&taken).single());
+        snapshots.push(Snapshot {
+            name,
+            path: entry.path(),
+            taken,
+        });
+    }
+    snapshots.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(snapshots)
+}
+
+/// Copy the contents of a directory into a new snapshot of it
+///
+/// Files are cloned copy-on-write where possible, like with `zz clone`.
+pub fn create(dir: &Path) -> Result<Snapshot, Box<dyn error::Error + 'static>> {
+    let now = Local::now();
+    let name = now.format(NAME_FORMAT).to_string();
+    let path = dir.join(DIR).join(&name);
+    if path.exists() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "Snapshot {} exists already, try again in a second",
+            name
+        ))));
+    }
+    fs::create_dir_all(&path)?;
+    if let Err(e) = fsutil::copy_dir_contents(dir, &path, &[DIR]) {
+        fs::remove_dir_all(&path)?;
+        return Err(Box::new(e));
+    }
+    Ok(Snapshot {
+        name,
+        path,
+        taken: Some(now),
+    })
+}
+
+/// Find a snapshot of a directory by name, or its newest one
+pub fn find(dir: &Path, name: Option<&str>) -> Result<Snapshot, Box<dyn error::Error + 'static>> {
+    let mut snapshots = list(dir)?;
+    let found = match name {
+        Some(name) => snapshots.into_iter().find(|snapshot| snapshot.name == name),
+        None => snapshots.pop(),
+    };
+    found.ok_or_else(|| {
+        let message = match name {
+            Some(name) => format!("No snapshot '{}' of {}", name, dir.display()),
+            None => format!("{} has no snapshots", dir.display()),
+        };
+        Box::new(ZugzugError::new(&message)) as Box<dyn error::Error + 'static>
+    })
+}
+
+/// Replace the contents of a directory with those of one of its snapshots
+///
+/// The snapshots themselves are kept.
+pub fn restore(dir: &Path, snapshot: &Snapshot) -> Result<(), Box<dyn error::Error + 'static>> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name() == DIR {
+            continue;
+        }
+        match entry.file_type()?.is_dir() {
+            true => fs::remove_dir_all(entry.path())?,
+            false => fs::remove_file(entry.path())?,
+        }
+    }
+    fsutil::copy_dir_contents(&snapshot.path, dir, &[])?;
+    Ok(())
+}
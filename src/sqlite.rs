@@ -0,0 +1,182 @@
+use crate::errors::ZugzugError;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+use std::error;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// One table holds the whole store, a row per bucket, directory, access
+/// record and so on, so saving only writes the rows that changed
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS records (
+    kind TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    PRIMARY KEY (kind, key)
+);";
+
+/// Rows of top-level values like the version, under their field name
+const SCALARS: &str = "meta";
+/// Suffix of the kind of rows that are elements of an array
+const ARRAY: &str = "[]";
+
+/// Rows of the store, keyed by kind and key, with JSON values
+type Records = BTreeMap<(String, String), String>;
+
+// run the sqlite3 shell on a database, feeding it sql
+fn sqlite3(
+    path: &Path,
+    args: &[&str],
+    sql: &str,
+) -> Result<String, Box<dyn error::Error + 'static>> {
+    let mut child = Command::new("sqlite3")
+        .args(["-batch", "-bail"])
+        .args(args)
+        .arg(path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run sqlite3: {}", e)))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(sql.as_bytes())?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "sqlite3 failed on {}: {}",
+            path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+// quote a string for sql
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "''"))
+}
+
+// split store contents into rows: objects become a row per entry, arrays a
+// row per element, keyed by position, and everything else, including empty
+// objects and arrays, a `meta` row
+fn to_records(contents: &[u8]) -> Result<Records, Box<dyn error::Error + 'static>> {
+    let value: Value = serde_json::from_slice(contents)?;
+    let fields = match value {
+        Value::Object(fields) => fields,
+        _ => return Err(Box::new(ZugzugError::new("Store data is not an object"))),
+    };
+    let mut records = Records::new();
+    for (field, value) in fields {
+        match value {
+            Value::Object(entries) if !entries.is_empty() => {
+                for (key, entry) in entries {
+                    records.insert((field.clone(), key), entry.to_string());
+                }
+            }
+            Value::Array(elements) if !elements.is_empty() => {
+                let kind = format!("{}{}", field, ARRAY);
+                for (i, element) in elements.into_iter().enumerate() {
+                    records.insert((kind.clone(), format!("{:08}", i)), element.to_string());
+                }
+            }
+            value => {
+                records.insert((SCALARS.to_string(), field), value.to_string());
+            }
+        }
+    }
+    Ok(records)
+}
+
+// put rows back together into store contents
+fn from_records(records: &Records) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+    let mut fields = Map::new();
+    for ((kind, key), value) in records.iter() {
+        let value: Value = serde_json::from_str(value)?;
+        if kind == SCALARS {
+            fields.insert(key.clone(), value);
+        } else if let Some(field) = kind.strip_suffix(ARRAY) {
+            // rows come sorted by key, so elements keep their order
+            if let Value::Array(elements) = fields
+                .entry(field.to_string())
+                .or_insert_with(|| Value::Array(vec![]))
+            {
+                elements.push(value);
+            }
+        } else if let Value::Object(entries) = fields
+            .entry(kind.clone())
+            .or_insert_with(|| Value::Object(Map::new()))
+        {
+            entries.insert(key.clone(), value);
+        }
+    }
+    Ok(serde_json::to_vec(&Value::Object(fields))?)
+}
+
+// read every row of a database
+fn read_records(path: &Path) -> Result<Records, Box<dyn error::Error + 'static>> {
+    let output = sqlite3(
+        path,
+        &["-json"],
+        "SELECT kind, key, value FROM records ORDER BY kind, key;",
+    )?;
+    let mut records = Records::new();
+    if output.trim().is_empty() {
+        return Ok(records);
+    }
+    let rows: Vec<BTreeMap<String, String>> = serde_json::from_str(&output)?;
+    for mut row in rows {
+        let (kind, key, value) = match (row.remove("kind"), row.remove("key"), row.remove("value"))
+        {
+            (Some(kind), Some(key), Some(value)) => (kind, key, value),
+            _ => return Err(Box::new(ZugzugError::new("Malformed row in the store"))),
+        };
+        records.insert((kind, key), value);
+    }
+    Ok(records)
+}
+
+/// Read a database as the contents of a JSON store
+pub fn read(path: &Path) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+    from_records(&read_records(path)?)
+}
+
+/// Create a database holding the given store contents
+pub fn init(path: &Path, contents: &[u8]) -> Result<(), Box<dyn error::Error + 'static>> {
+    sqlite3(path, &[], SCHEMA)?;
+    write(path, &from_records(&Records::new())?, contents)?;
+    Ok(())
+}
+
+/// Update a database from store contents `old` to `new`
+///
+/// Only rows that changed are written, in a single transaction. Returns the
+/// contents as `read` now returns them, put back together from the rows
+/// without reading the database again.
+pub fn write(
+    path: &Path,
+    old: &[u8],
+    new: &[u8],
+) -> Result<Vec<u8>, Box<dyn error::Error + 'static>> {
+    let old = to_records(old)?;
+    let new = to_records(new)?;
+    let mut sql = String::from("BEGIN;\n");
+    for (kind, key) in old.keys().filter(|k| !new.contains_key(*k)) {
+        sql.push_str(&format!(
+            "DELETE FROM records WHERE kind = {} AND key = {};\n",
+            quote(kind),
+            quote(key)
+        ));
+    }
+    for ((kind, key), value) in new.iter().filter(|(k, v)| old.get(*k) != Some(*v)) {
+        sql.push_str(&format!(
+            "INSERT OR REPLACE INTO records (kind, key, value) VALUES ({}, {}, {});\n",
+            quote(kind),
+            quote(key),
+            quote(value)
+        ));
+    }
+    sql.push_str("COMMIT;\n");
+    sqlite3(path, &[], &sql)?;
+    from_records(&new)
+}
@@ -1,15 +1,178 @@
+use crate::backend::{self, JsonFile, Memory, Sqlite, StoreBackend};
+use crate::config::{Config, FinderConfig, NamesConfig};
+use crate::dates;
 use crate::errors::ZugzugError;
+use crate::finder;
+use crate::fsutil;
+use crate::fuzzy;
+use crate::link;
+use crate::localmeta;
+use crate::lock::FileLock;
+use crate::migrations;
+use crate::names;
+use crate::platform;
+use crate::readonly;
+use crate::xattrs;
+use crate::zoxide;
 use chrono::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
 use std::error;
+use std::fmt;
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::str::FromStr;
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
+/// A dated directory living inside a bucket
+#[derive(Debug, Clone)]
+pub struct Dir {
+    pub bucket: String,
+    pub date: String,
+    pub name: String,
+    pub path: PathBuf,
+    /// Where the date is in the name on disk
+    pub date_position: DatePosition,
+}
+
+impl Dir {
+    pub fn path_str(&self) -> &str {
+        self.path.to_str().unwrap()
+    }
+
+    /// The directory name on disk, including the date
+    pub fn full_name(&self) -> String {
+        self.name_with_date(&self.date)
+    }
+
+    /// The name on disk of this directory if it had another date
+    pub fn name_with_date(&self, date: &str) -> String {
+        if date.is_empty() || self.date_position == DatePosition::None {
+            self.name.clone()
+        } else if self.name.is_empty() {
+            date.to_string()
+        } else if self.date_position == DatePosition::Suffix {
+            format!("{}_{}", self.name, date)
+        } else {
+            format!("{}_{}", date, self.name)
+        }
+    }
+
+    /// The date in the directory's prefix, if it has a valid one
+    pub fn naive_date(&self) -> Option<NaiveDate> {
+        dates::parse(&self.date)
+    }
+
+    /// The date to sort by, the same for every form of prefix
+    pub fn date_key(&self) -> String {
+        match self.naive_date() {
+            Some(date) => date.format("%Y%m%d").to_string(),
+            None => self.date.clone(),
+        }
+    }
+
+    /// Days since the date in the directory's prefix, if it has one
+    pub fn age_days(&self) -> Option<i64> {
+        Some((Local::now().date_naive() - self.naive_date()?).num_days())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Bucket {
     pub name: String,
     pub path: String,
+    /// Environment variables inherited by every directory in the bucket
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// User who added the bucket, for stores shared by a team
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    /// Octal permission mode of new directories, e.g. `0700`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    /// Keep one directory per day, named by its date only
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub journal: bool,
+    /// More directories the bucket spans besides `path`, its primary one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_paths: Vec<String>,
+    /// Create new directories in the path with the most free space instead
+    /// of the primary one
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub most_free: bool,
+    /// Position set with `zz bucket order`, 1 first. Buckets without one
+    /// come after those with one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<u32>,
+    /// Where new directories get their date
+    #[serde(default, skip_serializing_if = "DatePosition::is_prefix")]
+    pub date_position: DatePosition,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// Where a directory name carries its date
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DatePosition {
+    /// `20250301_name`, so listings group by date
+    #[default]
+    Prefix,
+    /// `name_20250301`, so listings group by topic
+    Suffix,
+    /// Just `name`, without a date
+    None,
+}
+
+impl DatePosition {
+    pub fn is_prefix(&self) -> bool {
+        *self == DatePosition::Prefix
+    }
+}
+
+impl FromStr for DatePosition {
+    type Err = ZugzugError;
+
+    fn from_str(s: &str) -> Result<DatePosition, ZugzugError> {
+        match s {
+            "prefix" => Ok(DatePosition::Prefix),
+            "suffix" => Ok(DatePosition::Suffix),
+            "none" => Ok(DatePosition::None),
+            _ => Err(ZugzugError::new(&format!(
+                "Unknown date position '{}', expected prefix, suffix or none",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DatePosition {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DatePosition::Prefix => write!(f, "prefix"),
+            DatePosition::Suffix => write!(f, "suffix"),
+            DatePosition::None => write!(f, "none"),
+        }
+    }
+}
+
+/// Shell commands run at points in a directory's life
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Hooks {
+    /// Run inside a directory right after it was created
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_create: Vec<String>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.post_create.is_empty()
+    }
 }
 
 impl Bucket {
@@ -17,34 +180,419 @@ impl Bucket {
         Path::new(&self.path)
     }
 
-    pub fn make_dir(&self, name: &str) -> Result<PathBuf, Box<dyn error::Error + 'static>> {
-        let now: DateTime<Local> = Local::now();
-        let full_name = format!(
-            "{:04}{:02}{:02}_{}",
-            now.year(),
-            now.month(),
-            now.day(),
-            name
-        );
-        let path = self.pathbuf().join(full_name);
-        if path.exists() {
+    /// All paths of this bucket, the primary one first
+    pub fn paths(&self) -> Vec<&Path> {
+        let mut paths = vec![self.pathbuf()];
+        paths.extend(self.extra_paths.iter().map(Path::new));
+        paths
+    }
+
+    /// The path new directories are created in
+    ///
+    /// That is the primary path, or with `most_free` the path with the most
+    /// free space. Paths whose free space can't be read are passed over.
+    pub fn mkdir_root(&self) -> &Path {
+        if !self.most_free || self.extra_paths.is_empty() {
+            return self.pathbuf();
+        }
+        self.paths()
+            .into_iter()
+            .filter_map(|path| platform::free_space(path).ok().map(|free| (path, free)))
+            .max_by_key(|(_, free)| *free)
+            .map(|(path, _)| path)
+            .unwrap_or_else(|| self.pathbuf())
+    }
+
+    /// List the directories inside this bucket
+    ///
+    /// Directory names are split into date and name as the bucket's
+    /// `date_position` says. Plain files in the
+    /// bucket and its `latest` link are ignored. The directories of all the
+    /// bucket's paths are merged, extra paths that are missing, e.g. an
+    /// unmounted disk, are skipped.
+    pub fn dirs(&self) -> Result<Vec<Dir>, Box<dyn error::Error + 'static>> {
+        let _span = span!("scan bucket {}", self.name);
+        let mut dirs = vec![];
+        for (i, root) in self.paths().into_iter().enumerate() {
+            if i > 0 && !root.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(root)? {
+                let path = entry?.path();
+                if !path.is_dir() || (path.ends_with(link::LATEST) && link::is_link(&path)) {
+                    continue;
+                }
+                let name_with_date = match path.file_name().and_then(|n| n.to_str()) {
+                    Some(name) => name.to_string(),
+                    None => continue,
+                };
+                dirs.push(self.dir(root, &name_with_date));
+            }
+        }
+        trace!("{} directories in bucket {}", dirs.len(), self.name);
+        Ok(dirs)
+    }
+
+    // the directory of this bucket with the given name on disk, below root
+    //
+    // Directories named before the bucket's date position changed still get
+    // their dates.
+    fn dir(&self, root: &Path, name_with_date: &str) -> Dir {
+        let ((date, name), date_position) = match self.date_position {
+            DatePosition::Prefix => (split_dir_name(name_with_date), DatePosition::Prefix),
+            DatePosition::None => (("", name_with_date), DatePosition::None),
+            DatePosition::Suffix => match split_date_suffix(name_with_date) {
+                Some(split) => (split, DatePosition::Suffix),
+                None => match split_dir_name(name_with_date) {
+                    (date, name) if is_date(date) => ((date, name), DatePosition::Prefix),
+                    _ => (("", name_with_date), DatePosition::Suffix),
+                },
+            },
+        };
+        Dir {
+            bucket: self.name.clone(),
+            date: date.to_string(),
+            name: name.to_string(),
+            path: root.join(name_with_date),
+            date_position,
+        }
+    }
+
+    /// The directory of this bucket at `path`, which needn't exist yet
+    pub fn dir_at(&self, path: &Path) -> Dir {
+        let root = path.parent().unwrap_or(path);
+        let name_with_date = path.file_name().unwrap_or_default().to_string_lossy();
+        self.dir(root, &name_with_date)
+    }
+
+    // the path of name_with_date in whichever of the bucket's paths has it
+    fn existing(&self, name_with_date: &str) -> Option<PathBuf> {
+        self.paths()
+            .into_iter()
+            .map(|root| root.join(name_with_date))
+            .find(|path| path.exists())
+    }
+
+    /// Today's journal directory, created when it doesn't exist yet
+    ///
+    /// Returns the path and whether it was created. One made before the
+    /// date separator changed still counts.
+    pub fn journal_dir(&self) -> Result<(PathBuf, bool), Box<dyn error::Error + 'static>> {
+        let existing = dates::prefixes(Local::now().date_naive())
+            .iter()
+            .find_map(|prefix| self.existing(prefix).filter(|path| path.is_dir()));
+        if let Some(path) = existing {
+            return Ok((path, false));
+        }
+        let path = self.mkdir_root().join(today());
+        readonly::check()?;
+        fs::create_dir(&path)?;
+        if let Some(mode) = &self.mode {
+            fsutil::set_mode(&path, fsutil::parse_mode(mode)?)?;
+        }
+        Ok((path, true))
+    }
+
+    /// Create a dated directory in this bucket
+    ///
+    /// Today's date goes where the bucket's `date_position` puts it. The
+    /// name is cleaned up with `names::sanitize` unless `names` is `None`,
+    /// then checked with `names::validate`. The directory gets the bucket's
+    /// permission mode, or `0700` when `private` is set.
+    pub fn make_dir(
+        &self,
+        name: &str,
+        private: bool,
+        names: Option<&NamesConfig>,
+    ) -> Result<PathBuf, Box<dyn error::Error + 'static>> {
+        readonly::check()?;
+        let name = match names {
+            Some(names) => names::sanitize(name, names)?,
+            None => name.to_string(),
+        };
+        let mode = match (private, &self.mode) {
+            (true, _) => Some(0o700),
+            (false, Some(mode)) => Some(fsutil::parse_mode(mode)?),
+            (false, None) => None,
+        };
+        let full_name = match self.date_position {
+            DatePosition::Prefix => format!("{}_{}", today(), name),
+            DatePosition::Suffix => format!("{}_{}", name, today()),
+            DatePosition::None => name.clone(),
+        };
+        names::validate(&name, &full_name)?;
+        if self.existing(&full_name).is_some() {
             return Err(Box::new(ZugzugError::new("Path already exists")));
         }
+        let path = self.mkdir_root().join(full_name);
         fs::create_dir(&path)?;
+        if let Some(mode) = mode {
+            fsutil::set_mode(&path, mode)?;
+        }
         Ok(path)
     }
 }
 
+/// Metadata tracked for a directory
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DirMeta {
+    /// Project type the directory was scaffolded as
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub project_type: Option<String>,
+    /// Environment variables exported when working in the directory
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    /// Free-form labels, e.g. `keep`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// Days after its date the directory expires and is pruned
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl: Option<i64>,
+    /// What the directory is about
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Set aside: hidden from `zz ls` and pruned first
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub deprecated: bool,
+}
+
+impl DirMeta {
+    /// Check whether the directory carries any of the given tags
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        tags.iter().any(|tag| self.tags.contains(tag))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.project_type.is_none()
+            && self.env.is_empty()
+            && self.tags.is_empty()
+            && self.ttl.is_none()
+            && self.description.is_none()
+            && !self.deprecated
+    }
+}
+
+/// A directory that was packed into the archive location and removed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ArchivedDir {
+    pub bucket: String,
+    /// Directory name on disk, including the date prefix
+    pub name: String,
+    /// Path of the archive file
+    pub archive: String,
+    /// Size of the directory when it was archived
+    pub size: u64,
+    /// When the directory was archived, as RFC 3339
+    pub archived: String,
+    /// Archive format, see `ArchiveFormat`
+    #[serde(default = "ArchivedDir::default_format")]
+    pub format: String,
+}
+
+impl ArchivedDir {
+    // archives recorded before the format was configurable
+    fn default_format() -> String {
+        "tar.gz".to_string()
+    }
+
+    /// Date and name of the archived directory
+    ///
+    /// The date is its prefix, or a date suffix when the prefix isn't one.
+    pub fn date_and_name(&self) -> (&str, &str) {
+        match self.name.find('_') {
+            Some(i) if !is_date(&self.name[..i]) => match split_date_suffix(&self.name) {
+                Some(split) => split,
+                None => (&self.name[..i], &self.name[i + 1..]),
+            },
+            Some(i) => (&self.name[..i], &self.name[i + 1..]),
+            None => ("", &self.name[..]),
+        }
+    }
+}
+
+/// A named mode to work in, with its own default bucket
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Context {
+    /// Default bucket while the context is in use, instead of the global one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_bucket: Option<String>,
+    /// Buckets listed while the context is in use, all when empty
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub buckets: Vec<String>,
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct StoreData {
+    /// Format version, see `migrations`
+    #[serde(default)]
+    pub version: u64,
     pub default_bucket: Option<String>,
     pub buckets: Vec<Bucket>,
+    /// Directory metadata keyed by the directory's path
+    #[serde(default)]
+    pub dirs: BTreeMap<String, DirMeta>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archives: Vec<ArchivedDir>,
+    /// When directories were last opened, keyed by the directory's path
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub access: BTreeMap<String, Access>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<RemovedDir>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub contexts: BTreeMap<String, Context>,
+    /// Name of the context in use, see `zz context`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context: Option<String>,
+    /// Whether anything changed since the store was loaded or saved
+    #[serde(skip)]
+    pub dirty: bool,
+}
+
+/// A directory zz removed, kept for `zz report`
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RemovedDir {
+    pub bucket: String,
+    /// Directory name on disk, including the date prefix
+    pub name: String,
+    /// Size in bytes when it was removed
+    pub size: u64,
+    /// When the directory was removed, as RFC 3339
+    pub removed: String,
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+}
+
+/// When and how often zz opened a directory
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Access {
+    /// RFC 3339 timestamp
+    pub last: String,
+    /// Number of times the directory was opened
+    #[serde(default)]
+    pub count: u64,
+}
+
+/// Check whether a directory name is a bare date, like journal directories
+pub fn is_date(name: &str) -> bool {
+    dates::parse(name).is_some()
+}
+
+/// Split a directory name on disk into its date prefix and name
+///
+/// The split is on the first underscore. Journal directories, named by
+/// their date only, get an empty name.
+pub fn split_dir_name(name_with_date: &str) -> (&str, &str) {
+    match name_with_date.find('_') {
+        Some(i) => (&name_with_date[..i], &name_with_date[i + 1..]),
+        None if is_date(name_with_date) => (name_with_date, ""),
+        None => ("", name_with_date),
+    }
+}
+
+/// Split a name like `report_20250301` into its date suffix and name
+///
+/// `None` when the name doesn't end in a date.
+pub fn split_date_suffix(name_with_date: &str) -> Option<(&str, &str)> {
+    let i = name_with_date.rfind('_')?;
+    let (name, date) = (&name_with_date[..i], &name_with_date[i + 1..]);
+    match is_date(date) && !name.is_empty() {
+        true => Some((date, name)),
+        false => None,
+    }
+}
+
+/// Outcome of looking up a directory by name
+pub enum DirMatch {
+    Missing,
+    /// The directory and how well it matches, 1 for an exact match
+    Found(Dir, f64),
+    /// Directories that match equally well
+    Ambiguous(Vec<Dir>),
+}
+
+/// The error for a name that matches several directories
+pub fn ambiguous_dirs(name: &str, dirs: &[Dir]) -> Box<dyn error::Error + 'static> {
+    let candidates: Vec<String> = dirs.iter().map(|d| d.full_name()).collect();
+    Box::new(ZugzugError::new(&t!(
+        "Directory name '{name}' is ambiguous: {candidates}",
+        name = name,
+        candidates = candidates.join(", ")
+    )))
+}
+
+/// Today's date as used in the prefixes of new directories
+pub fn today() -> String {
+    dates::prefix(Local::now().date_naive())
+}
+
+/// Name of the user running zz
+pub fn current_user() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
 }
 
 pub struct Store {
-    location: PathBuf,
+    backend: Box<dyn StoreBackend>,
     data: StoreData,
-    bucket_names: HashSet<String>,
+    // position of each bucket in `data.buckets`, by name
+    bucket_index: HashMap<String, usize>,
+    backups: usize,
+    // mirror directory metadata into extended attributes
+    xattrs: bool,
+    // write `.zz.toml` files into directories whose metadata changes
+    local_files: bool,
+    // mirror tags and descriptions into Finder
+    finder: FinderConfig,
+    // look buckets and directories up by name regardless of case
+    ignore_case: bool,
+    // tell zoxide about directories when they are opened
+    zoxide: bool,
+    // store file contents as last read or written, to detect concurrent
+    // changes by other processes
+    on_disk: Vec<u8>,
+    // bucket paths with `~` or variables as stored, keyed by bucket name,
+    // along with what they expanded to
+    stored_paths: HashMap<String, (String, String)>,
+}
+
+/// The store of a single zz invocation, loaded on first use
+///
+/// Commands that don't need the store never read it, the ones that do
+/// share one copy, and `save` writes it back once if anything changed.
+#[derive(Default)]
+pub struct LazyStore {
+    store: Option<Store>,
+}
+
+impl LazyStore {
+    pub fn get(&mut self) -> Result<&mut Store, Box<dyn error::Error + 'static>> {
+        if self.store.is_none() {
+            self.store = Some(Store::load()?);
+        }
+        Ok(self.store.as_mut().unwrap())
+    }
+
+    /// Write the store back if it was loaded and changed
+    pub fn save(&mut self) -> Result<(), Box<dyn error::Error + 'static>> {
+        match self.store.as_mut() {
+            Some(store) => store.save(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Outcome of `Store::recover`
+pub struct Recovery {
+    /// The backup that was restored
+    pub restored: PathBuf,
+    /// When the restored backup was written
+    pub written: Option<DateTime<Local>>,
+    /// Newer backups that couldn't be read either
+    pub skipped: Vec<PathBuf>,
+    /// Where the broken store was moved to
+    pub corrupt: Option<PathBuf>,
+    pub buckets: Vec<String>,
 }
 
 impl Store {
@@ -53,42 +601,929 @@ impl Store {
         name: &str,
         dir: &str,
     ) -> Result<(), Box<dyn error::Error + 'static>> {
+        // stored as given, used expanded
+        let expanded = fsutil::expand_path(dir)?;
+        if expanded != dir {
+            self.stored_paths
+                .insert(name.to_string(), (dir.to_string(), expanded.clone()));
+        }
         self.data.buckets.push(Bucket {
             name: name.to_string(),
-            path: dir.to_string(),
+            path: expanded,
+            owner: current_user(),
+            ..Default::default()
         });
-        match self.default_bucket() {
-            None => {
-                self.set_default_bucket(name)?;
-            }
-            Some(_) => {}
+        self.reindex();
+        if self.default_bucket().is_none() {
+            self.set_default_bucket(name)?;
         }
-        self.persist()
+        self.mark_dirty();
+        Ok(())
     }
 
+    /// All buckets in their configured order
+    ///
+    /// Buckets without a priority keep the order they were added in.
     pub fn buckets(&self) -> Vec<Bucket> {
-        self.data.buckets.clone()
+        self.buckets_iter().cloned().collect()
+    }
+
+    /// All buckets in their configured order, without copying them
+    pub fn buckets_iter(&self) -> impl Iterator<Item = &Bucket> {
+        let mut buckets: Vec<&Bucket> = self.data.buckets.iter().collect();
+        buckets.sort_by_key(|b| (b.priority.is_none(), b.priority));
+        buckets.into_iter()
+    }
+
+    // rebuild the index of buckets by name after buckets were added or
+    // removed
+    fn reindex(&mut self) {
+        self.bucket_index = self
+            .data
+            .buckets
+            .iter()
+            .enumerate()
+            .map(|(i, bucket)| (bucket.name.clone(), i))
+            .collect();
+    }
+
+    /// Give buckets an explicit order, the first name first
+    ///
+    /// Buckets not named lose their priority and come after the named ones.
+    pub fn set_bucket_order(
+        &mut self,
+        names: &[&str],
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        for name in names.iter() {
+            self.find_bucket_mut(name)?;
+        }
+        for bucket in self.data.buckets.iter_mut() {
+            bucket.priority = names
+                .iter()
+                .position(|name| *name == bucket.name)
+                .map(|i| i as u32 + 1);
+        }
+        self.mark_dirty();
+        Ok(())
+    }
+
+    // index of the candidate whose bucket is ordered before all the others,
+    // if there is one
+    //
+    // Buckets without a priority never win, so ambiguity stays an error
+    // until an order is set.
+    fn preferred<T>(&self, candidates: &[T], bucket: impl Fn(&T) -> &str) -> Option<usize> {
+        let priority = |candidate: &T| {
+            self.find_bucket(bucket(candidate))
+                .and_then(|b| b.priority)
+                .unwrap_or(u32::MAX)
+        };
+        let (best, best_priority) = candidates
+            .iter()
+            .enumerate()
+            .map(|(i, candidate)| (i, priority(candidate)))
+            .min_by_key(|(_, priority)| *priority)?;
+        let tied = candidates
+            .iter()
+            .filter(|candidate| priority(candidate) == best_priority)
+            .count();
+        match best_priority != u32::MAX && tied == 1 {
+            true => Some(best),
+            false => None,
+        }
+    }
+
+    // a name as compared when looking things up by name
+    fn fold_case(&self, name: &str) -> String {
+        match self.ignore_case {
+            true => name.to_lowercase(),
+            false => name.to_string(),
+        }
     }
 
     pub fn find_bucket(&self, name: &str) -> Option<&Bucket> {
-        self.data.buckets.iter().filter(|b| b.name == name).next()
+        self.bucket_index
+            .get(name)
+            .and_then(|&i| self.data.buckets.get(i))
     }
 
-    pub fn default_bucket(&self) -> Option<&Bucket> {
+    /// Find the bucket a path is in, and the bucket's directory it is in
+    ///
+    /// The path may be anywhere below the bucket. With nested buckets the
+    /// innermost one wins. Symlinks are resolved on both sides.
+    pub fn locate(&self, path: &Path) -> Option<(Bucket, Option<Dir>)> {
+        let path = fs::canonicalize(path).ok()?;
+        let (bucket, root, relative) = self
+            .data
+            .buckets
+            .iter()
+            .flat_map(|bucket| bucket.paths().into_iter().map(move |root| (bucket, root)))
+            .filter_map(|(bucket, root)| {
+                let canonical = fs::canonicalize(root).ok()?;
+                let relative = path.strip_prefix(&canonical).ok()?.to_path_buf();
+                let depth = canonical.components().count();
+                Some((bucket, root, relative, depth))
+            })
+            .max_by_key(|(_, _, _, depth)| *depth)
+            .map(|(bucket, root, relative, _)| (bucket, root, relative))?;
+        let dir = relative
+            .components()
+            .next()
+            .and_then(|c| c.as_os_str().to_str())
+            .map(|name| bucket.dir(root, name));
+        Some((bucket.clone(), dir))
+    }
+
+    /// Find a bucket by name or by an unambiguous prefix of its name
+    ///
+    /// An exact match always wins. With `ignore_case` names and prefixes
+    /// match regardless of case, after names of the exact case. Returns
+    /// `None` when nothing matches, and an error listing the candidates when
+    /// the prefix is ambiguous.
+    pub fn match_bucket(
+        &self,
+        name: &str,
+    ) -> Result<Option<&Bucket>, Box<dyn error::Error + 'static>> {
+        if let Some(bucket) = self.find_bucket(name) {
+            return Ok(Some(bucket));
+        }
+        let prefix = self.fold_case(name);
+        let candidates: Vec<&Bucket> = self
+            .data
+            .buckets
+            .iter()
+            .filter(|b| self.fold_case(&b.name).starts_with(&prefix))
+            .collect();
+        // only reached with `ignore_case`, when the name differs in case
+        if let Some(bucket) = candidates
+            .iter()
+            .find(|b| self.fold_case(&b.name) == prefix)
+        {
+            return Ok(Some(bucket));
+        }
+        if let Some(i) = self.preferred(&candidates, |b| &b.name) {
+            return Ok(Some(candidates[i]));
+        }
+        match candidates.len() {
+            0 => Ok(None),
+            1 => Ok(Some(candidates[0])),
+            _ => {
+                let names: Vec<&str> = candidates.iter().map(|b| b.name.as_str()).collect();
+                Err(Box::new(ZugzugError::new(&t!(
+                    "Bucket name '{name}' is ambiguous: {candidates}",
+                    name = name,
+                    candidates = names.join(", ")
+                ))))
+            }
+        }
+    }
+
+    /// Like `match_bucket`, but a missing bucket is an error too
+    pub fn resolve_bucket(&self, name: &str) -> Result<&Bucket, Box<dyn error::Error + 'static>> {
+        match self.match_bucket(name)? {
+            Some(bucket) => Ok(bucket),
+            None => Err(Box::new(ZugzugError::new(&t!(
+                "Bucket '{name}' doesn't exist",
+                name = name
+            )))),
+        }
+    }
+
+    /// The buckets selected by an optional `-b/--bucket` filter
+    pub fn select_buckets(
+        &self,
+        filter: Option<&str>,
+    ) -> Result<Vec<&Bucket>, Box<dyn error::Error + 'static>> {
+        match filter {
+            Some(name) => Ok(vec![self.resolve_bucket(name)?]),
+            None => Ok(self.visible_buckets()),
+        }
+    }
+
+    /// The buckets the context in use shows, in order
+    pub fn visible_buckets(&self) -> Vec<&Bucket> {
+        let context = self
+            .active_context()
+            .map(|(_, context)| context)
+            .filter(|context| !context.buckets.is_empty());
+        self.buckets_iter()
+            .filter(|b| context.is_none_or(|context| context.buckets.contains(&b.name)))
+            .collect()
+    }
+
+    /// The context in use and its name
+    ///
+    /// `ZZ_CONTEXT` wins over the one chosen with `zz context use`, so a
+    /// shell can work in a context of its own. Unknown names are ignored.
+    pub fn active_context(&self) -> Option<(&str, &Context)> {
+        let name = match env::var("ZZ_CONTEXT") {
+            Ok(name) if !name.is_empty() => name,
+            _ => self.data.context.clone()?,
+        };
         self.data
-            .default_bucket
-            .as_ref()
-            .and_then(|name| self.data.buckets.iter().find(|&b| b.name == *name))
+            .contexts
+            .get_key_value(&name)
+            .map(|(name, context)| (name.as_str(), context))
     }
 
+    pub fn contexts(&self) -> &BTreeMap<String, Context> {
+        &self.data.contexts
+    }
+
+    /// Add a context, or replace the one with the same name
+    pub fn set_context(
+        &mut self,
+        name: &str,
+        context: Context,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        for bucket in context.default_bucket.iter().chain(context.buckets.iter()) {
+            self.find_bucket_mut(bucket)?;
+        }
+        self.data.contexts.insert(name.to_string(), context);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    pub fn remove_context(&mut self, name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+        if self.data.contexts.remove(name).is_none() {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "Context '{}' doesn't exist",
+                name
+            ))));
+        }
+        if self.data.context.as_deref() == Some(name) {
+            self.data.context = None;
+        }
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Switch to a context, or back to none
+    pub fn use_context(
+        &mut self,
+        name: Option<&str>,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        if let Some(name) = name {
+            if !self.data.contexts.contains_key(name) {
+                return Err(Box::new(ZugzugError::new(&format!(
+                    "Context '{}' doesn't exist",
+                    name
+                ))));
+            }
+        }
+        self.data.context = name.map(str::to_string);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    // bucket by name, for changing its settings
+    fn find_bucket_mut(
+        &mut self,
+        name: &str,
+    ) -> Result<&mut Bucket, Box<dyn error::Error + 'static>> {
+        let buckets = &mut self.data.buckets;
+        match self
+            .bucket_index
+            .get(name)
+            .and_then(move |&i| buckets.get_mut(i))
+        {
+            Some(bucket) => Ok(bucket),
+            None => Err(Box::new(ZugzugError::new("Bucket doesn't exist"))),
+        }
+    }
+
+    /// Set environment variables inherited by a bucket's directories
+    pub fn set_bucket_env(
+        &mut self,
+        name: &str,
+        vars: &[(String, String)],
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let bucket = self.find_bucket_mut(name)?;
+        for (key, value) in vars.iter() {
+            bucket.env.insert(key.clone(), value.clone());
+        }
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Remove environment variables from a bucket
+    pub fn unset_bucket_env(
+        &mut self,
+        name: &str,
+        keys: &[&str],
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let bucket = self.find_bucket_mut(name)?;
+        for key in keys.iter() {
+            bucket.env.remove(*key);
+        }
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Set or clear the permission mode of a bucket's new directories
+    pub fn set_bucket_mode(
+        &mut self,
+        name: &str,
+        mode: Option<&str>,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        if let Some(mode) = mode {
+            fsutil::parse_mode(mode)?;
+        }
+        self.find_bucket_mut(name)?.mode = mode.map(String::from);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Turn journal mode of a bucket on or off
+    pub fn set_bucket_journal(
+        &mut self,
+        name: &str,
+        journal: bool,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.find_bucket_mut(name)?.journal = journal;
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Add a post-create hook to a bucket
+    pub fn add_bucket_hook(
+        &mut self,
+        name: &str,
+        command: &str,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.find_bucket_mut(name)?
+            .hooks
+            .post_create
+            .push(command.to_string());
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Remove all post-create hooks from a bucket
+    pub fn clear_bucket_hooks(
+        &mut self,
+        name: &str,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.find_bucket_mut(name)?.hooks.post_create.clear();
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Let a bucket span another path
+    ///
+    /// With `primary` the path becomes the bucket's primary one, and the old
+    /// primary path an extra one.
+    pub fn add_bucket_path(
+        &mut self,
+        name: &str,
+        path: &str,
+        primary: bool,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let bucket = self.find_bucket_mut(name)?;
+        if bucket.paths().contains(&Path::new(path)) {
+            return Err(Box::new(ZugzugError::new(&format!(
+                "Bucket '{}' already spans {}",
+                name, path
+            ))));
+        }
+        if primary {
+            let old = std::mem::replace(&mut bucket.path, path.to_string());
+            bucket.extra_paths.insert(0, old);
+        } else {
+            bucket.extra_paths.push(path.to_string());
+        }
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Stop a bucket from spanning a path
+    ///
+    /// Removing the primary path makes the first extra path the primary one.
+    /// The directories on disk are left alone.
+    pub fn remove_bucket_path(
+        &mut self,
+        name: &str,
+        path: &str,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let bucket = self.find_bucket_mut(name)?;
+        if bucket.path == path {
+            if bucket.extra_paths.is_empty() {
+                return Err(Box::new(ZugzugError::new(&format!(
+                    "{} is the only path of bucket '{}'",
+                    path, name
+                ))));
+            }
+            bucket.path = bucket.extra_paths.remove(0);
+        } else {
+            let count = bucket.extra_paths.len();
+            bucket.extra_paths.retain(|extra| extra != path);
+            if bucket.extra_paths.len() == count {
+                return Err(Box::new(ZugzugError::new(&format!(
+                    "Bucket '{}' doesn't span {}",
+                    name, path
+                ))));
+            }
+        }
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Set where a bucket's new directories get their date
+    pub fn set_bucket_date_position(
+        &mut self,
+        name: &str,
+        date_position: DatePosition,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.find_bucket_mut(name)?.date_position = date_position;
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Choose whether a bucket creates directories in its primary path or in
+    /// the path with the most free space
+    pub fn set_bucket_most_free(
+        &mut self,
+        name: &str,
+        most_free: bool,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.find_bucket_mut(name)?.most_free = most_free;
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// List the directories of every bucket
+    ///
+    /// Buckets that can't be read are reported and skipped.
+    pub fn all_dirs(&self) -> Vec<Dir> {
+        let mut dirs = vec![];
+        for bucket in self.data.buckets.iter() {
+            match bucket.dirs() {
+                Ok(bucket_dirs) => dirs.extend(bucket_dirs),
+                Err(err) => warn!("Unable to read dir: {}", err),
+            }
+        }
+        dirs
+    }
+
+    /// Find a tracked directory by its exact name
+    ///
+    /// The name can either be the full directory name including the date
+    /// prefix, or just the part after the date. The latter must be
+    /// unambiguous. Returns `None` when nothing matches.
+    pub fn match_dir(&self, name: &str) -> Result<Option<Dir>, Box<dyn error::Error + 'static>> {
+        match self.exact_dir_match(name) {
+            DirMatch::Found(dir, _) => Ok(Some(dir)),
+            DirMatch::Missing => Ok(None),
+            DirMatch::Ambiguous(dirs) => Err(ambiguous_dirs(name, &dirs)),
+        }
+    }
+
+    fn exact_dir_match(&self, name: &str) -> DirMatch {
+        let dirs = self.all_dirs();
+        match self.dir_named(&dirs, name, |a, b| a == b) {
+            DirMatch::Missing if self.ignore_case => {
+                self.dir_named(&dirs, name, |a, b| a.to_lowercase() == b.to_lowercase())
+            }
+            found => found,
+        }
+    }
+
+    // the directory whose full name, or else name, is `name` by `eq`
+    fn dir_named(&self, dirs: &[Dir], name: &str, eq: impl Fn(&str, &str) -> bool) -> DirMatch {
+        if let Some(dir) = dirs.iter().find(|d| eq(&d.full_name(), name)) {
+            return DirMatch::Found(dir.clone(), 1.0);
+        }
+        let mut matches: Vec<Dir> = dirs.iter().filter(|d| eq(&d.name, name)).cloned().collect();
+        if let Some(i) = self.preferred(&matches, |d| &d.bucket) {
+            return DirMatch::Found(matches.swap_remove(i), 1.0);
+        }
+        match matches.len() {
+            0 => DirMatch::Missing,
+            1 => DirMatch::Found(matches.remove(0), 1.0),
+            _ => DirMatch::Ambiguous(matches),
+        }
+    }
+
+    /// Score every tracked directory against an approximate name
+    ///
+    /// The best matches come first.
+    pub fn fuzzy_match_dirs(&self, name: &str) -> Vec<(Dir, f64)> {
+        let mut scored: Vec<(Dir, f64)> = self
+            .all_dirs()
+            .into_iter()
+            .map(|d| {
+                let score = fuzzy::score(name, &d.name).max(fuzzy::score(name, &d.full_name()));
+                (d, score)
+            })
+            .collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        scored
+    }
+
+    /// Find the directory best matching a name, exactly or approximately
+    ///
+    /// Exact matches score 1. Fuzzy matches below `fuzzy::MINIMUM_SCORE`
+    /// are ignored, and a tie for the best score is an error.
+    pub fn best_dir_match(
+        &self,
+        name: &str,
+    ) -> Result<Option<(Dir, f64)>, Box<dyn error::Error + 'static>> {
+        match self.dir_match(name) {
+            DirMatch::Found(dir, score) => Ok(Some((dir, score))),
+            DirMatch::Missing => Ok(None),
+            DirMatch::Ambiguous(dirs) => Err(ambiguous_dirs(name, &dirs)),
+        }
+    }
+
+    /// Like `best_dir_match`, but a tie gives the tied directories to choose
+    /// from
+    pub fn dir_match(&self, name: &str) -> DirMatch {
+        match self.exact_dir_match(name) {
+            DirMatch::Missing => {}
+            found => return found,
+        }
+        let mut scored = self.fuzzy_match_dirs(name);
+        let best_score = match scored.first() {
+            Some((_, score)) if *score >= fuzzy::MINIMUM_SCORE => *score,
+            _ => return DirMatch::Missing,
+        };
+        let tied_count = scored
+            .iter()
+            .take_while(|(_, score)| *score == best_score)
+            .count();
+        if let Some(i) = self.preferred(&scored[..tied_count], |(d, _)| &d.bucket) {
+            let (dir, score) = scored.swap_remove(i);
+            return DirMatch::Found(dir, score);
+        }
+        if tied_count > 1 {
+            scored.truncate(tied_count);
+            return DirMatch::Ambiguous(scored.into_iter().map(|(dir, _)| dir).collect());
+        }
+        let (dir, score) = scored.swap_remove(0);
+        DirMatch::Found(dir, score)
+    }
+
+    /// Metadata recorded for a directory, if any
+    ///
+    /// A non-empty `.zz.toml` in the directory wins over the store, since it
+    /// moves along with the directory and may have been edited by hand. With
+    /// `store.xattrs`, metadata stored in extended attributes is used when
+    /// the store has none, e.g. after the directory was moved by hand.
+    pub fn dir_meta(&self, path: &Path) -> Option<DirMeta> {
+        if let Some(meta) = localmeta::read(path).filter(|meta| !meta.is_empty()) {
+            return Some(meta);
+        }
+        match self.data.dirs.get(path.to_str()?) {
+            Some(meta) => Some(meta.clone()),
+            None if self.xattrs => xattrs::read(path),
+            None => None,
+        }
+    }
+
+    // metadata of a directory, created on first use
+    fn dir_meta_mut(&mut self, path: &Path) -> &mut DirMeta {
+        let key = path.to_str().unwrap().to_string();
+        if let Some(meta) = localmeta::read(path).filter(|meta| !meta.is_empty()) {
+            self.data.dirs.insert(key.clone(), meta);
+        }
+        let xattrs = self.xattrs;
+        self.data.dirs.entry(key).or_insert_with(|| {
+            if xattrs {
+                xattrs::read(path).unwrap_or_default()
+            } else {
+                DirMeta::default()
+            }
+        })
+    }
+
+    // persist a change to a directory's metadata, mirroring it onto the
+    // directory with `store.xattrs`, into Finder with `finder.tags` or
+    // `finder.comments` and into its `.zz.toml` when it has one or
+    // `store.local_files` is set
+    fn persist_dir_meta(&mut self, path: &Path) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.mark_dirty();
+        if let Some(meta) = path.to_str().and_then(|key| self.data.dirs.get(key)) {
+            if self.xattrs {
+                xattrs::write(path, meta);
+            }
+            if self.finder.tags || self.finder.comments {
+                finder::sync(&self.finder, path, meta);
+            }
+            if (self.local_files || localmeta::exists(path)) && path.is_dir() {
+                if let Err(e) = localmeta::write(path, meta) {
+                    warn!(
+                        "Unable to update {}: {}",
+                        path.join(localmeta::FILE).display(),
+                        e
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Record the project type of a directory
+    pub fn set_project_type(
+        &mut self,
+        path: &Path,
+        project_type: &str,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.dir_meta_mut(path).project_type = Some(project_type.to_string());
+        self.persist_dir_meta(path)
+    }
+
+    /// Set environment variables for a directory
+    pub fn set_dir_env(
+        &mut self,
+        path: &Path,
+        vars: &[(String, String)],
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let meta = self.dir_meta_mut(path);
+        for (key, value) in vars.iter() {
+            meta.env.insert(key.clone(), value.clone());
+        }
+        self.persist_dir_meta(path)
+    }
+
+    /// Remove environment variables from a directory
+    pub fn unset_dir_env(
+        &mut self,
+        path: &Path,
+        keys: &[&str],
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let meta = self.dir_meta_mut(path);
+        for key in keys.iter() {
+            meta.env.remove(*key);
+        }
+        self.persist_dir_meta(path)
+    }
+
+    /// Add tags to a directory
+    pub fn add_dir_tags(
+        &mut self,
+        path: &Path,
+        tags: &[&str],
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let meta = self.dir_meta_mut(path);
+        for tag in tags.iter() {
+            meta.tags.insert(tag.to_string());
+        }
+        self.persist_dir_meta(path)
+    }
+
+    /// Remove tags from a directory
+    pub fn remove_dir_tags(
+        &mut self,
+        path: &Path,
+        tags: &[&str],
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let meta = self.dir_meta_mut(path);
+        for tag in tags.iter() {
+            meta.tags.remove(*tag);
+        }
+        self.persist_dir_meta(path)
+    }
+
+    /// Set the number of days after which a directory expires
+    pub fn set_dir_ttl(
+        &mut self,
+        path: &Path,
+        ttl: i64,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.dir_meta_mut(path).ttl = Some(ttl);
+        self.persist_dir_meta(path)
+    }
+
+    /// Mark a directory as deprecated, or take the mark off again
+    pub fn set_dir_deprecated(
+        &mut self,
+        path: &Path,
+        deprecated: bool,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.dir_meta_mut(path).deprecated = deprecated;
+        self.persist_dir_meta(path)
+    }
+
+    pub fn dir_deprecated(&self, path: &Path) -> bool {
+        self.dir_meta(path).is_some_and(|meta| meta.deprecated)
+    }
+
+    /// Set or clear the description of a directory
+    pub fn set_dir_description(
+        &mut self,
+        path: &Path,
+        description: Option<&str>,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.dir_meta_mut(path).description = description.map(|d| d.to_string());
+        self.persist_dir_meta(path)
+    }
+
+    /// Give a copy of a directory the type, environment, tags and
+    /// description of the original
+    ///
+    /// The TTL is left out, a copy starts its life afresh.
+    pub fn copy_dir_meta(
+        &mut self,
+        from: &Path,
+        to: &Path,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let meta = match self.dir_meta(from) {
+            Some(meta) => meta,
+            None => return Ok(()),
+        };
+        let copy = self.dir_meta_mut(to);
+        copy.project_type = meta.project_type;
+        copy.env = meta.env;
+        copy.tags = meta.tags;
+        copy.description = meta.description;
+        self.persist_dir_meta(to)
+    }
+
+    /// Add the metadata of a directory merged into another to the other's
+    ///
+    /// Tags add up, and its environment fills in variables the other doesn't
+    /// set. Type and description are only taken when the other has none.
+    pub fn merge_dir_meta(
+        &mut self,
+        from: &Path,
+        to: &Path,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let meta = match self.dir_meta(from) {
+            Some(meta) => meta,
+            None => return Ok(()),
+        };
+        let merged = self.dir_meta_mut(to);
+        merged.project_type = merged.project_type.take().or(meta.project_type);
+        merged.description = merged.description.take().or(meta.description);
+        for (key, value) in meta.env {
+            merged.env.entry(key).or_insert(value);
+        }
+        merged.tags.extend(meta.tags);
+        self.persist_dir_meta(to)
+    }
+
+    /// Check whether a directory outlived its TTL
+    pub fn dir_expired(&self, dir: &Dir) -> bool {
+        match (
+            self.dir_meta(&dir.path).and_then(|meta| meta.ttl),
+            dir.age_days(),
+        ) {
+            (Some(ttl), Some(age)) => age >= ttl,
+            _ => false,
+        }
+    }
+
+    /// Check whether a directory carries any of the given tags
+    pub fn dir_has_any_tag(&self, path: &Path, tags: &[String]) -> bool {
+        self.dir_meta(path)
+            .is_some_and(|meta| meta.has_any_tag(tags))
+    }
+
+    /// Remember that a directory was just opened, e.g. by `zz exec`
+    ///
+    /// With `zoxide.add` the directory is passed on to zoxide as well. Does
+    /// nothing in read-only mode, opening a directory never fails because
+    /// of it.
+    pub fn record_access(&mut self, path: &Path) -> Result<(), Box<dyn error::Error + 'static>> {
+        if readonly::is_enabled() {
+            return Ok(());
+        }
+        if self.zoxide {
+            zoxide::add(path);
+        }
+        let key = path.to_string_lossy().into_owned();
+        let count = self.data.access.get(&key).map_or(0, |a| a.count);
+        self.data.access.insert(
+            key,
+            Access {
+                last: Local::now().to_rfc3339(),
+                count: count + 1,
+            },
+        );
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Merge access counts from elsewhere, e.g. zoxide, into the store
+    ///
+    /// Each directory keeps the higher count and the later access time of
+    /// the two.
+    pub fn import_access(
+        &mut self,
+        entries: &[(PathBuf, u64, DateTime<Local>)],
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        for (path, count, last) in entries {
+            let key = path.to_string_lossy().into_owned();
+            let merged = match (self.data.access.get(&key), self.last_accessed(path)) {
+                (Some(access), Some(previous)) => Access {
+                    last: previous.max(last.fixed_offset()).to_rfc3339(),
+                    count: access.count.max(*count),
+                },
+                _ => Access {
+                    last: last.to_rfc3339(),
+                    count: *count,
+                },
+            };
+            self.data.access.insert(key, merged);
+        }
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Rank how much a directory is in use, combining frequency and recency
+    ///
+    /// Like zoxide, the number of times it was opened is weighted by how
+    /// long ago that last happened: 4x within the hour, 2x within the day,
+    /// 0.5x within the week and 0.25x after that. Directories never opened
+    /// through zz score 0.
+    pub fn frecency(&self, path: &Path) -> f64 {
+        let (last, count) = match path.to_str().and_then(|key| self.data.access.get(key)) {
+            Some(access) => match DateTime::parse_from_rfc3339(&access.last) {
+                Ok(last) => (last, access.count.max(1)),
+                Err(_) => return 0.0,
+            },
+            None => return 0.0,
+        };
+        let age = Local::now().signed_duration_since(last);
+        let weight = if age < chrono::Duration::hours(1) {
+            4.0
+        } else if age < chrono::Duration::days(1) {
+            2.0
+        } else if age < chrono::Duration::weeks(1) {
+            0.5
+        } else {
+            0.25
+        };
+        count as f64 * weight
+    }
+
+    /// When a directory was last opened through zz, if ever
+    pub fn last_accessed(&self, path: &Path) -> Option<DateTime<FixedOffset>> {
+        let access = self.data.access.get(path.to_str()?)?;
+        DateTime::parse_from_rfc3339(&access.last).ok()
+    }
+
+    /// Record a directory that was archived before being removed
+    pub fn add_archived(
+        &mut self,
+        archived: ArchivedDir,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.data.archives.push(archived);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Record that a directory was removed
+    pub fn add_removed(
+        &mut self,
+        removed: RemovedDir,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.data.removed.push(removed);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Directories removed so far, oldest first
+    pub fn removed(&self) -> &[RemovedDir] {
+        &self.data.removed
+    }
+
+    pub fn archives(&self) -> &[ArchivedDir] {
+        &self.data.archives
+    }
+
+    /// Forget an archive, e.g. once it was restored
+    pub fn remove_archived(
+        &mut self,
+        archive: &str,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.data.archives.retain(|a| a.archive != archive);
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// The default bucket, that of the context in use if it has one
+    pub fn default_bucket(&self) -> Option<&Bucket> {
+        let context_default = self
+            .active_context()
+            .and_then(|(_, context)| context.default_bucket.as_ref());
+        context_default
+            .or(self.data.default_bucket.as_ref())
+            .and_then(|name| self.find_bucket(name))
+    }
+
+    /// Set the default bucket, of the context in use if there is one
     pub fn set_default_bucket(
         &mut self,
         name: &str,
     ) -> Result<(), Box<dyn error::Error + 'static>> {
         match self.find_bucket(name) {
             Some(_) => {
-                self.data.default_bucket = Some(name.to_string());
-                self.persist()?;
+                let default = self.default_slot();
+                if default.as_deref() != Some(name) {
+                    *default = Some(name.to_string());
+                    self.mark_dirty();
+                }
                 Ok(())
             }
             None => Err(Box::new(ZugzugError::new("Bucket doesn't exist"))),
@@ -96,88 +1531,415 @@ impl Store {
     }
 
     pub fn unset_default_bucket(&mut self) -> Result<(), Box<dyn error::Error + 'static>> {
-        self.data.default_bucket = None;
-        self.persist()
+        if self.default_slot().take().is_some() {
+            self.mark_dirty();
+        }
+        Ok(())
     }
 
-    pub fn forget_bucket(&mut self, name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
-        match self.default_bucket() {
-            Some(bucket) => {
-                if bucket.name == name {
-                    self.unset_default_bucket()?;
-                }
-            }
-            None => {}
+    // where the default bucket is kept: in the context in use, or globally
+    fn default_slot(&mut self) -> &mut Option<String> {
+        let name = self.active_context().map(|(name, _)| name.to_string());
+        match name {
+            Some(name) => &mut self.data.contexts.entry(name).or_default().default_bucket,
+            None => &mut self.data.default_bucket,
         }
-        self.data.buckets = self
+    }
+
+    /// Point a bucket at the directory it was moved to
+    ///
+    /// Metadata and access history of the directories inside it move along.
+    pub fn move_bucket(
+        &mut self,
+        name: &str,
+        path: &str,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let bucket = self.find_bucket_mut(name)?;
+        let old = std::mem::replace(&mut bucket.path, path.to_string());
+        self.rekey(Path::new(&old), Path::new(path));
+        self.mark_dirty();
+        Ok(())
+    }
+
+    /// Carry a directory's metadata and access history over to its new path
+    pub fn move_dir_records(
+        &mut self,
+        from: &Path,
+        to: &Path,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        self.rekey(from, to);
+        // extended attributes don't survive a copy onto another filesystem
+        self.persist_dir_meta(to)
+    }
+
+    /// Drop the metadata and access history of a removed directory, and of
+    /// the directories inside it
+    pub fn forget_dir_records(
+        &mut self,
+        path: &Path,
+    ) -> Result<(), Box<dyn error::Error + 'static>> {
+        let gone = |key: &String| Path::new(key).starts_with(path);
+        self.data.dirs.retain(|key, _| !gone(key));
+        self.data.access.retain(|key, _| !gone(key));
+        self.mark_dirty();
+        Ok(())
+    }
+
+    // move store entries keyed by paths at or below `from` below `to`
+    fn rekey(&mut self, from: &Path, to: &Path) {
+        let moved = |key: &String| {
+            Path::new(key)
+                .strip_prefix(from)
+                .ok()
+                .map(|rest| match rest.as_os_str().is_empty() {
+                    // joining an empty path would add a trailing separator
+                    true => to.to_string_lossy().into_owned(),
+                    false => to.join(rest).to_string_lossy().into_owned(),
+                })
+        };
+        let keys: Vec<(String, String)> = self
             .data
-            .buckets
-            .iter()
-            .filter(|bucket| bucket.name != name)
-            .map(|bucket| bucket.clone())
+            .dirs
+            .keys()
+            .chain(self.data.access.keys())
+            .filter_map(|key| Some((key.clone(), moved(key)?)))
             .collect();
-        self.persist()
+        for (old, new) in keys {
+            if let Some(meta) = self.data.dirs.remove(&old) {
+                self.data.dirs.insert(new.clone(), meta);
+            }
+            if let Some(access) = self.data.access.remove(&old) {
+                self.data.access.insert(new, access);
+            }
+        }
     }
 
-    fn new(location: PathBuf) -> Store {
+    pub fn forget_bucket(&mut self, name: &str) -> Result<(), Box<dyn error::Error + 'static>> {
+        if self.data.default_bucket.as_deref() == Some(name) {
+            self.data.default_bucket = None;
+        }
+        for context in self.data.contexts.values_mut() {
+            if context.default_bucket.as_deref() == Some(name) {
+                context.default_bucket = None;
+            }
+            context.buckets.retain(|bucket| bucket != name);
+        }
+        self.data.buckets.retain(|bucket| bucket.name != name);
+        self.reindex();
+        self.mark_dirty();
+        Ok(())
+    }
+
+    fn new(backend: Box<dyn StoreBackend>) -> Store {
         let buckets: Vec<Bucket> = vec![];
         Store {
-            location: location,
+            backend,
             data: StoreData {
-                buckets: buckets,
+                version: migrations::CURRENT_VERSION,
+                buckets,
                 default_bucket: None,
+                dirs: BTreeMap::new(),
+                archives: vec![],
+                access: BTreeMap::new(),
+                removed: vec![],
+                contexts: BTreeMap::new(),
+                context: None,
+                dirty: false,
             },
-            bucket_names: HashSet::new(),
+            bucket_index: HashMap::new(),
+            backups: 0,
+            xattrs: false,
+            local_files: false,
+            finder: FinderConfig::default(),
+            ignore_case: false,
+            zoxide: false,
+            on_disk: vec![],
+            stored_paths: HashMap::new(),
         }
     }
 
-    // load the store from the home directory
+    // load the store from the configured path, or the home directory
     pub fn from_home() -> Result<Store, Box<dyn error::Error + 'static>> {
-        if let Some(location) = dirs::home_dir() {
-            Ok(Store::new(location))
-        } else {
-            Err(Box::new(ZugzugError::new("Could not get home directory")))
+        let full_config = Config::load()?;
+        let config = full_config.store;
+        let sqlite = match config.backend.as_deref() {
+            None | Some("json") => false,
+            Some("sqlite") => true,
+            Some(backend) => {
+                return Err(Box::new(ZugzugError::new(&format!(
+                    "Unknown store backend '{}', expected json or sqlite",
+                    backend
+                ))))
+            }
+        };
+        let location = match (config.path, Store::default_location(sqlite)) {
+            (Some(path), _) => PathBuf::from(path),
+            (None, Some(location)) => location,
+            (None, None) => return Err(Box::new(ZugzugError::new("Could not get home directory"))),
+        };
+        let backend: Box<dyn StoreBackend> = match sqlite {
+            true => Box::new(Sqlite {
+                path: location,
+                import: Store::default_location(false),
+            }),
+            false => Box::new(JsonFile { path: location }),
+        };
+        let mut store = Store::new(backend);
+        store.backups = config.backups;
+        store.xattrs = config.xattrs;
+        store.local_files = config.local_files;
+        store.zoxide = full_config.zoxide.add;
+        store.finder = full_config.finder;
+        store.ignore_case = full_config.ignore_case;
+        if (store.finder.tags || store.finder.comments) && !finder::supported() {
+            debug!("Not mirroring into Finder, which is only done on macOS");
         }
+        Ok(store)
+    }
+
+    // where the store is kept unless configured otherwise
+    fn default_location(sqlite: bool) -> Option<PathBuf> {
+        let home = dirs::home_dir()?;
+        Some(home.join(if sqlite { ".zz.db" } else { ".zz.json" }))
+    }
+
+    // Initialize Store data and persist it
+    fn init(&mut self) -> Result<(), Box<dyn error::Error + 'static>> {
+        let _lock = self.lock()?;
+        if self.backend.exists() {
+            return Ok(());
+        }
+        let contents = serde_json::to_vec(&self.data)?;
+        self.backend.create(&contents)
+    }
+
+    // lock the store against other processes, if it is kept on disk
+    fn lock(&self) -> Result<Option<FileLock>, Box<dyn error::Error + 'static>> {
+        match self.backend.path() {
+            Some(path) => Ok(Some(FileLock::acquire(&backend::sibling(path, "lock"))?)),
+            None => Ok(None),
+        }
+    }
+
+    // construct the path of the nth backup, counting from 1 for the newest
+    fn backup_path(path: &Path, n: usize) -> PathBuf {
+        backend::sibling(path, &n.to_string())
     }
 
-    // Initialize Store data and persist to disk
-    fn init(&self) -> Result<(), Box<dyn error::Error + 'static>> {
-        let path = self.store_path();
-        fs::write(path, serde_json::to_string(&self.data)?)?;
+    // shift the backups by one and copy the current store in as the newest
+    fn rotate_backups(&self) -> Result<(), Box<dyn error::Error + 'static>> {
+        let path = match self.backend.path() {
+            Some(path) if self.backups > 0 && path.exists() => path,
+            _ => return Ok(()),
+        };
+        for n in (1..self.backups).rev() {
+            let backup = Store::backup_path(path, n);
+            if backup.exists() {
+                fs::rename(&backup, Store::backup_path(path, n + 1))?;
+            }
+        }
+        fs::copy(path, Store::backup_path(path, 1))?;
         Ok(())
     }
 
-    // construct the Store's data file path
-    fn store_path(&self) -> PathBuf {
-        Path::new(&self.location).join(".zz.json")
+    // remember that the data changed, so `save` writes it
+    fn mark_dirty(&mut self) {
+        self.data.dirty = true;
+    }
+
+    /// Write the store to disk if anything changed since it was loaded
+    ///
+    /// Changes are only kept in memory until then, so a command that changes
+    /// several things writes the store, and rotates its backups, once.
+    pub fn save(&mut self) -> Result<(), Box<dyn error::Error + 'static>> {
+        if self.data.dirty {
+            self.persist()?;
+            self.data.dirty = false;
+        }
+        Ok(())
     }
 
-    // persist Store contents to disk
-    fn persist(&self) -> Result<(), Box<dyn error::Error + 'static>> {
-        fs::write(self.store_path(), serde_json::to_string(&self.data)?)?;
+    // persist Store contents through its backend
+    //
+    // The store is locked while writing, and the write is refused if another
+    // process changed the store since it was loaded.
+    fn persist(&mut self) -> Result<(), Box<dyn error::Error + 'static>> {
+        readonly::check()?;
+        let _span = span!("save store");
+        let _lock = self.lock()?;
+        if self.backend.read()? != self.on_disk {
+            return Err(Box::new(ZugzugError::new(
+                "Store was changed by another process, please retry",
+            )));
+        }
+        self.rotate_backups()?;
+        // keep `~` and variables in bucket paths, so shared stores work for
+        // everyone
+        self.use_stored_paths(true);
+        let contents = serde_json::to_vec(&self.data);
+        self.use_stored_paths(false);
+        let contents = contents?;
+        self.on_disk = self.backend.write(&self.on_disk, &contents)?;
+        trace!("wrote {} bytes", contents.len());
         Ok(())
     }
 
-    // load Store contents from disk
+    // switch bucket paths to the form they were stored in, or back to
+    // their expansion
+    fn use_stored_paths(&mut self, stored: bool) {
+        for bucket in self.data.buckets.iter_mut() {
+            if let Some((raw, expanded)) = self.stored_paths.get(&bucket.name) {
+                let (from, to) = if stored {
+                    (expanded, raw)
+                } else {
+                    (raw, expanded)
+                };
+                if bucket.path == *from {
+                    bucket.path = to.clone();
+                }
+            }
+        }
+    }
+
+    // parse Store contents, upgrading older formats
+    //
+    // Also returns whether the data was migrated.
+    fn parse(data: &[u8]) -> Result<(StoreData, bool), Box<dyn error::Error + 'static>> {
+        let mut raw: serde_json::Value = serde_json::from_slice(data)?;
+        let migrated = migrations::migrate(&mut raw)?;
+        Ok((serde_json::from_value(raw)?, migrated))
+    }
+
+    // load Store contents from its backend
     fn internal_load(&mut self) -> Result<(), Box<dyn error::Error + 'static>> {
-        let data = String::from_utf8(fs::read(self.store_path())?)?;
-        let v: StoreData = serde_json::from_str(&data)?;
-        let mut names = HashSet::new();
-        for bucket in v.buckets.iter() {
-            names.insert(bucket.name.to_string());
+        let _span = span!(
+            "load store{}",
+            self.backend
+                .path()
+                .map(|path| format!(" {}", path.display()))
+                .unwrap_or_default()
+        );
+        let unreadable = |e: Box<dyn error::Error + 'static>| {
+            let location = match self.backend.path() {
+                Some(path) => format!(" {}", path.display()),
+                None => String::new(),
+            };
+            ZugzugError::new(&format!(
+                "Unable to read store{}: {}. Run 'zz store recover' to restore a backup",
+                location, e
+            ))
+        };
+        let data = self.backend.read().map_err(unreadable)?;
+        let (mut v, migrated) = Store::parse(&data).map_err(unreadable)?;
+        self.on_disk = data;
+        self.stored_paths.clear();
+        for bucket in v.buckets.iter_mut() {
+            // paths that can't be expanded fail when the bucket is used
+            if let Ok(expanded) = fsutil::expand_path(&bucket.path) {
+                if expanded != bucket.path {
+                    let stored = std::mem::replace(&mut bucket.path, expanded.clone());
+                    self.stored_paths
+                        .insert(bucket.name.clone(), (stored, expanded));
+                }
+            }
+            if Path::new(&bucket.path).is_relative() {
+                warn!(
+                    "Bucket '{}' has the relative path '{}', which depends on where zz runs. Add it again to store an absolute path",
+                    bucket.name, bucket.path
+                );
+            }
         }
+        debug!(
+            "store version {} with {} buckets and {} directories",
+            v.version,
+            v.buckets.len(),
+            v.dirs.len()
+        );
         self.data = v;
-        self.bucket_names = names;
+        self.reindex();
+        // in read-only mode the migration only happens in memory
+        if migrated && !readonly::is_enabled() {
+            self.mark_dirty();
+        }
         Ok(())
     }
 
     pub fn load() -> Result<Store, Box<dyn error::Error + 'static>> {
         let mut store = Store::from_home()?;
-        if !store.store_path().exists() {
-            println!("location does not exist yet");
+        if !store.backend.exists() {
+            if readonly::is_enabled() {
+                // nothing may be created, an empty store stands in
+                let mut memory = Store::in_memory()?;
+                memory.xattrs = store.xattrs;
+                memory.ignore_case = store.ignore_case;
+                memory.zoxide = store.zoxide;
+                return Ok(memory);
+            }
+            info!("The store does not exist yet, creating it");
             store.init()?;
         }
         store.internal_load()?;
         Ok(store)
     }
+
+    /// An empty store that is kept in memory and never touches disk
+    ///
+    /// Saving it keeps the changes in memory only, and none of the
+    /// configuration is read. Creating directories in its buckets still
+    /// creates them on disk.
+    pub fn in_memory() -> Result<Store, Box<dyn error::Error + 'static>> {
+        let mut store = Store::new(Box::<Memory>::default());
+        store.init()?;
+        store.internal_load()?;
+        Ok(store)
+    }
+
+    /// Restore the newest backup that can be read
+    ///
+    /// The broken store, if any, is kept as `.zz.json.corrupt`.
+    pub fn recover() -> Result<Recovery, Box<dyn error::Error + 'static>> {
+        readonly::check()?;
+        let store = Store::from_home()?;
+        let path = match store.backend.path() {
+            Some(path) => path,
+            None => return Err(Box::new(ZugzugError::new("Store has no backups"))),
+        };
+        let mut skipped = vec![];
+        for n in 1..=store.backups.max(1) {
+            let backup = Store::backup_path(path, n);
+            if !backup.exists() {
+                continue;
+            }
+            let data = match store
+                .backend
+                .read_backup(&backup)
+                .and_then(|data| Store::parse(&data))
+            {
+                Ok((data, _)) => data,
+                Err(_) => {
+                    skipped.push(backup);
+                    continue;
+                }
+            };
+            let corrupt = if path.exists() {
+                let corrupt = backend::sibling(path, "corrupt");
+                fs::rename(path, &corrupt)?;
+                Some(corrupt)
+            } else {
+                None
+            };
+            fs::copy(&backup, path)?;
+            let written = fs::metadata(&backup)?.modified().ok().map(DateTime::from);
+            return Ok(Recovery {
+                restored: backup,
+                written,
+                skipped,
+                corrupt,
+                buckets: data.buckets.into_iter().map(|b| b.name).collect(),
+            });
+        }
+        Err(Box::new(ZugzugError::new(
+            "No readable backup to recover from",
+        )))
+    }
 }
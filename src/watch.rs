@@ -0,0 +1,152 @@
+use crate::config::Config;
+use crate::store::{RemovedDir, Store};
+use crate::webhook::{self, Event};
+use chrono::prelude::*;
+use std::collections::HashMap;
+use std::error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+/// A directory as last seen, by path
+struct Seen {
+    bucket: String,
+    name: String,
+    /// Device and inode, to tell a rename from a removal and a creation
+    id: Option<(u64, u64)>,
+}
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = fs::symlink_metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+// without inode numbers a rename looks like a removal and a creation
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+// the directories of every bucket right now
+fn scan(store: &Store) -> HashMap<PathBuf, Seen> {
+    store
+        .all_dirs()
+        .into_iter()
+        .map(|dir| {
+            let seen = Seen {
+                id: file_id(&dir.path),
+                name: dir.full_name(),
+                bucket: dir.bucket,
+            };
+            (dir.path, seen)
+        })
+        .collect()
+}
+
+/// Keep the store in step with changes made to buckets outside zz, e.g.
+/// with `mv` or `rm -r`, checking every `interval`
+///
+/// Listings always read the buckets themselves, but metadata, access
+/// history and the log of removed directories are kept by path. A
+/// directory renamed by hand keeps its metadata and access history, as if
+/// `zz mv` had moved it, and one removed by hand is logged and reported to
+/// the webhooks like those `zz rm` removes. Directories that vanish with
+/// the path they were in, like on an unmounted disk, are left alone.
+///
+/// Changes only count once the buckets looked the same on two checks in a
+/// row, so a zz command still busy with them finishes first. Runs until
+/// the process ends.
+pub fn run(interval: Duration) -> Result<(), Box<dyn error::Error + 'static>> {
+    let mut seen = scan(&Store::load()?);
+    let mut seen_at = Local::now();
+    let mut last: Vec<PathBuf> = vec![];
+    debug!("Watching {} directories", seen.len());
+    loop {
+        thread::sleep(interval);
+        let started = Local::now();
+        let store = match Store::load() {
+            Ok(store) => store,
+            Err(e) => {
+                warn!("Unable to check buckets for changes: {}", e);
+                continue;
+            }
+        };
+        let now = scan(&store);
+        let mut paths: Vec<PathBuf> = now.keys().cloned().collect();
+        paths.sort();
+        let settled = paths == last;
+        last = paths;
+        let unchanged = now.len() == seen.len() && now.keys().all(|path| seen.contains_key(path));
+        if !settled || unchanged {
+            continue;
+        }
+        match record(store, &seen, &now, seen_at) {
+            Ok(()) => {
+                seen = now;
+                seen_at = started;
+            }
+            Err(e) => warn!("Unable to record changes to buckets: {}", e),
+        }
+    }
+}
+
+// record what changed between two scans of the buckets, the first one made
+// at `since`
+fn record(
+    mut store: Store,
+    seen: &HashMap<PathBuf, Seen>,
+    now: &HashMap<PathBuf, Seen>,
+    since: DateTime<Local>,
+) -> Result<(), Box<dyn error::Error + 'static>> {
+    let _span = span!("record changes to buckets");
+    let mut gone: Vec<(&PathBuf, &Seen)> = seen
+        .iter()
+        .filter(|(path, _)| !now.contains_key(*path))
+        .filter(|(path, _)| path.parent().is_some_and(Path::is_dir))
+        .collect();
+    for (path, dir) in now.iter().filter(|(path, _)| !seen.contains_key(*path)) {
+        let renamed = gone
+            .iter()
+            .position(|(_, old)| old.id.is_some() && old.id == dir.id);
+        match renamed {
+            Some(i) => {
+                let (old_path, _) = gone.remove(i);
+                info!("{} moved to {}", old_path.display(), path.display());
+                // unless zz moved it itself, records and all
+                if store.dir_meta(old_path).is_some() || store.last_accessed(old_path).is_some() {
+                    store.move_dir_records(old_path, path)?;
+                }
+            }
+            None => info!("{} appeared", path.display()),
+        }
+    }
+
+    let config = Config::load()?;
+    for (path, dir) in gone {
+        // zz logged it already when it removed it itself
+        let logged = store.removed().iter().any(|removed| {
+            removed.bucket == dir.bucket
+                && removed.name == dir.name
+                && DateTime::parse_from_rfc3339(&removed.removed).is_ok_and(|at| at >= since)
+        });
+        if logged {
+            continue;
+        }
+        info!("{} disappeared", path.display());
+        let tags = store.dir_meta(path).map(|m| m.tags).unwrap_or_default();
+        store.add_removed(RemovedDir {
+            bucket: dir.bucket.clone(),
+            name: dir.name.clone(),
+            // it is gone, and sizing every directory on each check would
+            // cost more than the log is worth
+            size: 0,
+            removed: Local::now().to_rfc3339(),
+            tags,
+        })?;
+        webhook::notify(&config.webhooks, Event::Removed, &dir.bucket, path);
+    }
+    store.save()
+}
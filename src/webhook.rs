@@ -0,0 +1,73 @@
+use crate::config::WebhookConfig;
+use chrono::prelude::*;
+use serde_json::json;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// Directory lifecycle events reported to webhooks
+#[derive(Debug, Clone, Copy)]
+pub enum Event {
+    Created,
+    Removed,
+}
+
+impl Event {
+    fn name(self) -> &'static str {
+        match self {
+            Event::Created => "created",
+            Event::Removed => "removed",
+        }
+    }
+}
+
+/// POST a JSON payload describing the event to every configured webhook
+///
+/// Delivery is best-effort: failures are reported but never fail the
+/// command that triggered them.
+pub fn notify(config: &WebhookConfig, event: Event, bucket: &str, path: &Path) {
+    if config.urls.is_empty()
+        || !(config.events.is_empty() || config.events.iter().any(|e| e == event.name()))
+    {
+        return;
+    }
+    let payload = json!({
+        "event": event.name(),
+        "bucket": bucket,
+        "name": path.file_name().map(|n| n.to_string_lossy().into_owned()),
+        "path": path.to_string_lossy(),
+        "timestamp": Local::now().to_rfc3339(),
+    })
+    .to_string();
+    for url in config.urls.iter() {
+        if let Err(err) = post(url, &payload) {
+            warn!("Unable to notify webhook {}: {}", url, err);
+        }
+    }
+}
+
+// send the payload with curl, which keeps TLS out of this crate
+fn post(url: &str, payload: &str) -> Result<(), String> {
+    let mut child = Command::new("curl")
+        .args(["-sf", "-o", "/dev/null", "-X", "POST"])
+        .args([
+            "-H",
+            "Content-Type: application/json",
+            "--data-binary",
+            "@-",
+        ])
+        .arg(url)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("unable to run curl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(payload.as_bytes())
+            .map_err(|e| e.to_string())?;
+    }
+    let status = child.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("curl exited with {}", status));
+    }
+    Ok(())
+}
@@ -0,0 +1,32 @@
+use crate::store::DirMeta;
+use std::path::Path;
+
+/// Extended attribute holding a directory's metadata as JSON
+#[cfg(unix)]
+const ATTRIBUTE: &str = "user.zz.meta";
+
+/// Read the metadata stored on a directory, if there is any
+#[cfg(unix)]
+pub fn read(dir: &Path) -> Option<DirMeta> {
+    let data = xattr::get(dir, ATTRIBUTE).ok()??;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Store a directory's metadata on the directory itself
+///
+/// This is best effort: filesystems without extended attributes are
+/// skipped silently, the store stays the source of truth.
+#[cfg(unix)]
+pub fn write(dir: &Path, meta: &DirMeta) {
+    if let Ok(data) = serde_json::to_vec(meta) {
+        let _ = xattr::set(dir, ATTRIBUTE, &data);
+    }
+}
+
+#[cfg(not(unix))]
+pub fn read(_dir: &Path) -> Option<DirMeta> {
+    None
+}
+
+#[cfg(not(unix))]
+pub fn write(_dir: &Path, _meta: &DirMeta) {}
@@ -0,0 +1,40 @@
+use crate::errors::ZugzugError;
+use std::error;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Tell zoxide about a directory, so `z` can jump to it
+///
+/// Best effort: when zoxide isn't installed nothing happens.
+pub fn add(dir: &Path) {
+    let _ = Command::new("zoxide")
+        .arg("add")
+        .arg(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status();
+}
+
+/// Every directory zoxide knows, with its score
+pub fn scores() -> Result<Vec<(f64, PathBuf)>, Box<dyn error::Error + 'static>> {
+    let output = Command::new("zoxide")
+        .args(["query", "--list", "--score"])
+        .output()
+        .map_err(|e| ZugzugError::new(&format!("Unable to run zoxide: {}", e)))?;
+    if !output.status.success() {
+        return Err(Box::new(ZugzugError::new(&format!(
+            "zoxide exited with {}",
+            output.status
+        ))));
+    }
+    let mut scores = vec![];
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let line = line.trim_start();
+        if let Some((score, path)) = line.split_once(' ') {
+            if let Ok(score) = score.parse() {
+                scores.push((score, PathBuf::from(path.trim_start())));
+            }
+        }
+    }
+    Ok(scores)
+}